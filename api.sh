@@ -7,6 +7,8 @@
 API_URL="https://terradominus.life"
 ADMIN_USER=${ADMIN_USER:-"admin"}
 ADMIN_PASS=${ADMIN_PASS:-"1234"}
+# Optional personal access token (np_...); when set, login is skipped
+API_TOKEN=${API_TOKEN:-""}
 
 # Colors for output
 RED='\033[0;31m'
@@ -54,6 +56,13 @@ check_server() {
 test_auth() {
     print_header "Testing Authentication"
 
+    if [ -n "$API_TOKEN" ]; then
+        print_success "Using personal access token: ${API_TOKEN:0:9}..."
+        export AUTH_TOKEN="$API_TOKEN"
+        echo
+        return 0
+    fi
+
     echo "Attempting login with username: $ADMIN_USER"
 
     response=$(curl -s -w "\n%{http_code}" \
@@ -1,6 +1,9 @@
 // ===== API Configuration =====
 const API_BASE_URL = "https://np.terradominus.life";
-let authToken = localStorage.getItem("authToken");
+// Notes shown per list; the API returns pages of at most 100
+const PAGE_SIZE = 100;
+// The session lives in HttpOnly cookies; only the CSRF token is visible to scripts
+let csrfToken = localStorage.getItem("csrfToken");
 let currentUser = null;
 
 // ===== DOM Elements =====
@@ -35,6 +38,7 @@ const elements = {
   noteTitle: document.getElementById("noteTitle"),
   noteContent: document.getElementById("noteContent"),
   notePublic: document.getElementById("notePublic"),
+  noteTags: document.getElementById("noteTags"),
   submitBtnText: document.getElementById("submitBtnText"),
   cancelBtn: document.getElementById("cancelBtn"),
 
@@ -54,6 +58,8 @@ const elements = {
   // Login
   loginUsername: document.getElementById("loginUsername"),
   loginPassword: document.getElementById("loginPassword"),
+  loginCodeGroup: document.getElementById("loginCodeGroup"),
+  loginCode: document.getElementById("loginCode"),
 
   // Toast
   toast: document.getElementById("toast"),
@@ -68,11 +74,15 @@ const elements = {
 
   // Action buttons
   createNoteBtn: document.getElementById("createNoteBtn"),
+  publicSearch: document.getElementById("publicSearch"),
+  privateSearch: document.getElementById("privateSearch"),
 };
 
 // ===== State Management =====
 let currentView = "public";
 let editingNoteId = null;
+// Challenge from /login while waiting for the user's 2FA code
+let pendingMfaToken = null;
 let notesCache = {
   public: [],
   private: [],
@@ -106,6 +116,20 @@ function escapeHtml(text) {
   return div.innerHTML;
 }
 
+function renderTags(tags) {
+  if (!tags || tags.length === 0) return "";
+  return `<div class="note-tags">${tags
+    .map((tag) => `<span class="note-tag">${escapeHtml(tag)}</span>`)
+    .join("")}</div>`;
+}
+
+function parseTags(value) {
+  return value
+    .split(",")
+    .map((tag) => tag.trim())
+    .filter((tag) => tag.length > 0);
+}
+
 function showToast(message, type = "success") {
   elements.toastMessage.textContent = message;
   elements.toastIcon.className = `toast-icon fas ${type === "success" ? "fa-check-circle" : type === "error" ? "fa-exclamation-circle" : "fa-info-circle"}`;
@@ -126,20 +150,31 @@ function showToast(message, type = "success") {
 // ===== API Functions =====
 async function apiRequest(endpoint, options = {}) {
   const url = `${API_BASE_URL}${endpoint}`;
+  const method = (options.method || "GET").toUpperCase();
   const config = {
+    ...options,
+    credentials: "include",
     headers: {
       "Content-Type": "application/json",
       ...options.headers,
     },
-    ...options,
   };
 
-  if (authToken) {
-    config.headers.Authorization = `Bearer ${authToken}`;
+  if (csrfToken && method !== "GET" && method !== "HEAD") {
+    config.headers["X-CSRF-Token"] = csrfToken;
   }
 
   try {
-    const response = await fetch(url, config);
+    let response = await fetch(url, config);
+
+    // Access token expired: rotate the refresh cookie once and retry
+    if (response.status === 401 && currentUser && (await refreshSession())) {
+      if (config.headers["X-CSRF-Token"]) {
+        config.headers["X-CSRF-Token"] = csrfToken;
+      }
+      response = await fetch(url, config);
+    }
+
     const data = await response.json();
 
     if (!response.ok) {
@@ -154,41 +189,132 @@ async function apiRequest(endpoint, options = {}) {
   }
 }
 
+function storeSession(data) {
+  csrfToken = data.csrf_token;
+  localStorage.setItem("csrfToken", csrfToken);
+}
+
+function clearSession() {
+  csrfToken = null;
+  currentUser = null;
+  localStorage.removeItem("csrfToken");
+  localStorage.removeItem("currentUser");
+}
+
+async function refreshSession() {
+  try {
+    const response = await fetch(`${API_BASE_URL}/token/refresh`, {
+      method: "POST",
+      credentials: "include",
+      headers: { "X-CSRF-Token": csrfToken || "" },
+    });
+
+    if (!response.ok) {
+      clearSession();
+      updateAuthUI();
+      return false;
+    }
+
+    storeSession(await response.json());
+    return true;
+  } catch (error) {
+    console.error("Token refresh error:", error);
+    return false;
+  }
+}
+
 async function login(username, password) {
   try {
     const data = await apiRequest("/login", {
       method: "POST",
-      body: JSON.stringify({ username, password }),
+      body: JSON.stringify({ username, password, use_cookie: true }),
     });
 
-    authToken = data.token;
-    currentUser = username;
-    localStorage.setItem("authToken", authToken);
-    localStorage.setItem("currentUser", username);
+    // Accounts with 2FA get a challenge instead of a session
+    if (data.mfa_required) {
+      showLoginCodePrompt(data.mfa_token);
+      return false;
+    }
 
-    updateAuthUI();
-    showToast("Login successful!", "success");
+    await completeLogin(data, username);
+    return true;
+  } catch (error) {
+    showToast("Login failed: " + error.message, "error");
+    return false;
+  }
+}
 
-    if (currentView === "private") {
-      await loadPrivateNotes();
-    } else if (currentView === "stats") {
-      await loadStats();
-    }
+async function loginTwoFactor(username, code) {
+  try {
+    const data = await apiRequest("/login/2fa", {
+      method: "POST",
+      body: JSON.stringify({
+        mfa_token: pendingMfaToken,
+        code,
+        use_cookie: true,
+      }),
+    });
 
-    elements.loginModal.classList.remove("active");
+    await completeLogin(data, username);
     return true;
   } catch (error) {
+    // An expired challenge cannot be retried; start over with the password
+    if (error.message.includes("challenge")) {
+      resetLoginCodePrompt();
+    }
     showToast("Login failed: " + error.message, "error");
     return false;
   }
 }
 
+async function completeLogin(data, username) {
+  storeSession(data);
+  currentUser = username;
+  localStorage.setItem("currentUser", username);
+  resetLoginCodePrompt();
+
+  updateAuthUI();
+  showToast("Login successful!", "success");
+
+  if (currentView === "private") {
+    await loadPrivateNotes();
+  } else if (currentView === "stats") {
+    await loadStats();
+  }
+
+  elements.loginModal.classList.remove("active");
+}
+
+function showLoginCodePrompt(mfaToken) {
+  pendingMfaToken = mfaToken;
+  elements.loginCodeGroup.style.display = "block";
+  elements.loginCode.required = true;
+  elements.loginPassword.required = false;
+  elements.loginCode.value = "";
+  elements.loginCode.focus();
+  showToast("Enter the code from your authenticator app", "info");
+}
+
+function resetLoginCodePrompt() {
+  pendingMfaToken = null;
+  elements.loginCodeGroup.style.display = "none";
+  elements.loginCode.required = false;
+  elements.loginPassword.required = true;
+  elements.loginCode.value = "";
+}
+
 async function logout() {
   try {
-    authToken = null;
-    currentUser = null;
-    localStorage.removeItem("authToken");
-    localStorage.removeItem("currentUser");
+    if (currentUser) {
+      // Revoke the session and clear its cookies; clear local state even if this fails
+      await fetch(`${API_BASE_URL}/logout`, {
+        method: "POST",
+        credentials: "include",
+        headers: { "X-CSRF-Token": csrfToken || "" },
+      }).catch((error) => console.error("Logout request error:", error));
+    }
+
+    clearSession();
 
     updateAuthUI();
     showToast("Logged out successfully", "success");
@@ -212,7 +338,8 @@ async function loadPublicNotes() {
             </div>
         `;
 
-    const notes = await apiRequest("/contents");
+    const page = await apiRequest(`/contents?limit=${PAGE_SIZE}`);
+    const notes = page.items;
     notesCache.public = notes;
     renderPublicNotes(notes);
   } catch (error) {
@@ -227,7 +354,7 @@ async function loadPublicNotes() {
 }
 
 async function loadPrivateNotes() {
-  if (!authToken) {
+  if (!currentUser) {
     elements.privateNotesGrid.innerHTML = `
             <div class="empty-state">
                 <i class="fas fa-lock"></i>
@@ -243,7 +370,8 @@ async function loadPrivateNotes() {
   }
 
   try {
-    const notes = await apiRequest("/admin/contents");
+    const page = await apiRequest(`/admin/contents?limit=${PAGE_SIZE}`);
+    const notes = page.items;
     notesCache.private = notes;
     renderPrivateNotes(notes);
   } catch (error) {
@@ -258,7 +386,7 @@ async function loadPrivateNotes() {
 }
 
 async function loadStats() {
-  if (!authToken) {
+  if (!currentUser) {
     elements.statsContainer.innerHTML = `
             <div class="empty-state">
                 <i class="fas fa-chart-bar"></i>
@@ -315,9 +443,13 @@ async function createNote(noteData) {
 }
 
 async function updateNote(id, noteData) {
+  const cached = notesCache.private.find((note) => note.id === id);
+  const headers = cached?.version ? { "If-Match": `"${cached.version}"` } : {};
+
   try {
-    await apiRequest(`/admin/contents/${id}`, {
+    const result = await apiRequest(`/admin/contents/${id}`, {
       method: "PUT",
+      headers,
       body: JSON.stringify(noteData),
     });
 
@@ -331,6 +463,7 @@ async function updateNote(id, noteData) {
       notesCache.private[index] = {
         ...notesCache.private[index],
         ...noteData,
+        version: result.version,
         updated_at: new Date().toISOString(),
       };
       renderPrivateNotes(notesCache.private);
@@ -343,7 +476,7 @@ async function updateNote(id, noteData) {
 async function deleteNote(id) {
   if (
     !confirm(
-      "Are you sure you want to delete this note? This action cannot be undone.",
+      "Move this note to the trash? It can be restored until the trash is purged.",
     )
   ) {
     return;
@@ -354,7 +487,7 @@ async function deleteNote(id) {
       method: "DELETE",
     });
 
-    showToast("Note deleted successfully!", "success");
+    showToast("Note moved to trash", "success");
 
     // Remove from cache
     notesCache.private = notesCache.private.filter((note) => note.id !== id);
@@ -366,7 +499,7 @@ async function deleteNote(id) {
 
 async function getNoteDetails(id) {
   try {
-    const note = await apiRequest(`/admin/contents/${id}`);
+    const note = await apiRequest(`/admin/contents/${id}?format=html`);
     showNoteDetail(note);
   } catch (error) {
     console.error("Get note details error:", error);
@@ -375,13 +508,77 @@ async function getNoteDetails(id) {
 
 async function getPublicNoteDetails(id) {
   try {
-    const note = await apiRequest(`/contents/${id}`);
+    const note = await apiRequest(`/contents/${id}?format=html`);
     showNoteDetail(note);
   } catch (error) {
     console.error("Get public note details error:", error);
   }
 }
 
+// ===== Search =====
+// Results carry server-escaped HTML in title_highlight and snippet, with matches in <mark>
+function renderSearchResults(grid, results, openDetail) {
+  if (results.length === 0) {
+    grid.innerHTML = `
+            <div class="empty-state">
+                <i class="fas fa-search"></i>
+                <h3>No matching notes</h3>
+                <p>Try other words or fewer operators</p>
+            </div>
+        `;
+    return;
+  }
+
+  grid.innerHTML = results
+    .map(
+      (note) => `
+        <div class="note-card" onclick="${openDetail}(${note.id})">
+            <div class="note-header">
+                <h3 class="note-title">${note.title_highlight}</h3>
+                <div class="note-meta">
+                    <span class="note-author">
+                        <i class="fas fa-user"></i>
+                        ${escapeHtml(note.user)}
+                    </span>
+                </div>
+            </div>
+            <div class="note-content">
+                <p class="note-text">${note.snippet}</p>
+            </div>
+            <div class="note-meta">
+                <span class="note-date">
+                    <i class="fas fa-clock"></i>
+                    ${formatDate(note.created_at)}
+                </span>
+            </div>
+        </div>
+    `,
+    )
+    .join("");
+}
+
+function setupSearch(input, endpoint, grid, openDetail, reload) {
+  let timer = null;
+  input.addEventListener("input", () => {
+    clearTimeout(timer);
+    timer = setTimeout(async () => {
+      const query = input.value.trim();
+      if (!query) {
+        reload();
+        return;
+      }
+      try {
+        const page = await apiRequest(
+          `${endpoint}?q=${encodeURIComponent(query)}&limit=${PAGE_SIZE}`,
+        );
+        renderSearchResults(grid, page.items, openDetail);
+      } catch (error) {
+        console.error("Search error:", error);
+      }
+    }, 300);
+  });
+}
+
 // ===== Rendering Functions =====
 function renderPublicNotes(notes) {
   if (notes.length === 0) {
@@ -414,6 +611,7 @@ function renderPublicNotes(notes) {
             </div>
             <div class="note-content">
                 <p class="note-text">${escapeHtml(truncateText(note.content))}</p>
+                ${renderTags(note.tags)}
             </div>
             <div class="note-meta">
                 <span class="note-date">
@@ -459,6 +657,7 @@ function renderPrivateNotes(notes) {
             </div>
             <div class="note-content">
                 <p class="note-text">${escapeHtml(truncateText(note.content))}</p>
+                ${renderTags(note.tags)}
             </div>
             <div class="note-actions">
                 <button class="btn btn-secondary btn-sm" onclick="event.stopPropagation(); editNote(${note.id})">
@@ -490,7 +689,7 @@ function renderStats(stats) {
 
 // ===== Modal Functions =====
 function showCreateModal() {
-  if (!authToken) {
+  if (!currentUser) {
     showLoginModal();
     return;
   }
@@ -514,12 +713,14 @@ function showEditModal(noteId) {
   elements.noteTitle.value = note.title;
   elements.noteContent.value = note.content;
   elements.notePublic.checked = note.is_public;
+  elements.noteTags.value = (note.tags || []).join(", ");
 
   elements.noteModal.classList.add("active");
   elements.noteTitle.focus();
 }
 
 function showLoginModal() {
+  resetLoginCodePrompt();
   elements.loginModal.classList.add("active");
   elements.loginUsername.focus();
 }
@@ -533,10 +734,17 @@ function showNoteDetail(note) {
         ${note.is_public ? "Public" : "Private"}
     `;
   elements.detailVisibility.className = `note-visibility ${note.is_public ? "visibility-public" : "visibility-private"}`;
-  elements.detailContent.textContent = note.content;
+  // content_html is sanitized by the server; fall back to plain text without it
+  if (note.content_html) {
+    elements.detailContent.innerHTML = note.content_html;
+    elements.detailContent.classList.add("markdown");
+  } else {
+    elements.detailContent.textContent = note.content;
+    elements.detailContent.classList.remove("markdown");
+  }
 
   // Add action buttons for owned notes
-  if (authToken && note.user === currentUser) {
+  if (note.user === currentUser) {
     elements.noteActions.innerHTML = `
             <button class="btn btn-secondary" onclick="editNote(${note.id})">
                 <i class="fas fa-edit"></i>
@@ -570,7 +778,7 @@ function closeModal(modal) {
 
 // ===== UI Updates =====
 function updateAuthUI() {
-  if (authToken && currentUser) {
+  if (currentUser) {
     elements.loginBtn.style.display = "none";
     elements.userInfo.style.display = "flex";
     elements.username.textContent = currentUser;
@@ -629,6 +837,21 @@ elements.loginBtn.addEventListener("click", showLoginModal);
 elements.logoutBtn.addEventListener("click", logout);
 elements.createNoteBtn.addEventListener("click", showCreateModal);
 
+setupSearch(
+  elements.publicSearch,
+  "/contents/search",
+  elements.publicNotesGrid,
+  "getPublicNoteDetails",
+  loadPublicNotes,
+);
+setupSearch(
+  elements.privateSearch,
+  "/admin/contents/search",
+  elements.privateNotesGrid,
+  "getNoteDetails",
+  loadPrivateNotes,
+);
+
 // Modal close buttons
 elements.closeModal.addEventListener("click", () =>
   closeModal(elements.noteModal),
@@ -663,6 +886,7 @@ elements.noteForm.addEventListener("submit", async (e) => {
     title: elements.noteTitle.value.trim(),
     content: elements.noteContent.value.trim(),
     is_public: elements.notePublic.checked,
+    tags: parseTags(elements.noteTags.value),
   };
 
   if (!noteData.title || !noteData.content) {
@@ -683,6 +907,17 @@ elements.loginForm.addEventListener("submit", async (e) => {
   const username = elements.loginUsername.value.trim();
   const password = elements.loginPassword.value;
 
+  // The password was already checked (or the login came from the identity provider)
+  if (pendingMfaToken) {
+    const code = elements.loginCode.value.trim();
+    if (!code) {
+      showToast("Please enter your authentication code", "error");
+      return;
+    }
+    await loginTwoFactor(username, code);
+    return;
+  }
+
   if (!username || !password) {
     showToast("Please enter both username and password", "error");
     return;
@@ -707,7 +942,7 @@ document.addEventListener("keydown", (e) => {
   // Ctrl/Cmd + N for new note
   if ((e.ctrlKey || e.metaKey) && e.key === "n") {
     e.preventDefault();
-    if (authToken) {
+    if (currentUser) {
       showCreateModal();
     } else {
       showLoginModal();
@@ -715,11 +950,38 @@ document.addEventListener("keydown", (e) => {
   }
 });
 
+// Back from /login/oidc: the callback leaves the login result in the URL fragment
+function handleOidcRedirect() {
+  const params = new URLSearchParams(window.location.hash.slice(1));
+  const user = params.get("user");
+  if (!user) {
+    return;
+  }
+  history.replaceState(null, "", window.location.pathname + window.location.search);
+
+  // Accounts with 2FA still need their code
+  if (params.get("mfa_token")) {
+    showLoginModal();
+    elements.loginUsername.value = user;
+    showLoginCodePrompt(params.get("mfa_token"));
+    return;
+  }
+
+  if (params.get("csrf_token")) {
+    storeSession({ csrf_token: params.get("csrf_token") });
+    currentUser = user;
+    localStorage.setItem("currentUser", user);
+    showToast("Login successful!", "success");
+  }
+}
+
 // ===== Initialize Application =====
 function initApp() {
+  handleOidcRedirect();
+
   // Load cached user data
   const cachedUser = localStorage.getItem("currentUser");
-  if (cachedUser && authToken) {
+  if (cachedUser && csrfToken) {
     currentUser = cachedUser;
     updateAuthUI();
   }
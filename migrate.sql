@@ -12,8 +12,11 @@ CREATE TABLE notes (
     content TEXT NOT NULL,
     user VARCHAR(100) NOT NULL,
     is_public BOOLEAN NOT NULL DEFAULT FALSE,
+    notebook_id BIGINT NULL DEFAULT NULL,
+    version INT NOT NULL DEFAULT 1,
     created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
-    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    deleted_at TIMESTAMP NULL DEFAULT NULL
 );
 
 -- Create indexes for better performance
@@ -21,6 +24,178 @@ CREATE INDEX idx_user ON notes (user);
 CREATE INDEX idx_public ON notes (is_public);
 CREATE INDEX idx_user_public ON notes (user, is_public);
 CREATE INDEX idx_created_at ON notes (created_at);
+-- Keyset pagination: (created_at, id) ordering within the public listing and per owner
+CREATE INDEX idx_public_created ON notes (is_public, created_at, id);
+CREATE INDEX idx_user_created ON notes (user, created_at, id);
+-- Full-text search over title and content (GET /contents/search)
+CREATE FULLTEXT INDEX idx_notes_fulltext ON notes (title, content);
+CREATE INDEX idx_notebook ON notes (notebook_id);
+-- Trash listing per owner and the background purge
+CREATE INDEX idx_user_deleted ON notes (user, deleted_at);
+CREATE INDEX idx_deleted_at ON notes (deleted_at);
+
+-- Create the users table (one row per account, per-user Argon2id hashes; legacy bcrypt
+-- hashes still verify and are rehashed with Argon2id at the next login)
+-- The first account is seeded from ADMIN_USER/ADMIN_PASS_HASH on server start
+DROP TABLE IF EXISTS users;
+
+CREATE TABLE users (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    username VARCHAR(100) NOT NULL UNIQUE,
+    password_hash VARCHAR(255) NOT NULL,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    role VARCHAR(20) NOT NULL DEFAULT 'editor',
+    sessions_revoked_at TIMESTAMP(3) NULL DEFAULT NULL,
+    totp_secret VARCHAR(64) NULL DEFAULT NULL,
+    totp_enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    totp_last_step BIGINT NULL DEFAULT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP
+);
+
+-- Create the refresh_tokens table (opaque tokens stored as SHA-256 hashes)
+-- Tokens issued from one login share a family_id; reusing a rotated token revokes the family
+DROP TABLE IF EXISTS refresh_tokens;
+
+CREATE TABLE refresh_tokens (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    user VARCHAR(100) NOT NULL,
+    token_hash CHAR(64) NOT NULL UNIQUE,
+    family_id CHAR(36) NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP NULL DEFAULT NULL,
+    revoked_at TIMESTAMP NULL DEFAULT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_refresh_family ON refresh_tokens (family_id);
+CREATE INDEX idx_refresh_user ON refresh_tokens (user);
+
+-- Create the sessions table (one row per login; the ID is the refresh token family
+-- and the sid claim of its access tokens, so a single login can be revoked)
+DROP TABLE IF EXISTS sessions;
+
+CREATE TABLE sessions (
+    id CHAR(36) PRIMARY KEY,
+    user VARCHAR(100) NOT NULL,
+    ip_address VARCHAR(45) NOT NULL,
+    user_agent VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_used_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    revoked_at TIMESTAMP NULL DEFAULT NULL
+);
+
+CREATE INDEX idx_sessions_user ON sessions (user);
+CREATE INDEX idx_sessions_revoked ON sessions (revoked_at);
+
+-- Create the revoked_tokens table (access token IDs revoked by logout before expiry)
+DROP TABLE IF EXISTS revoked_tokens;
+
+CREATE TABLE revoked_tokens (
+    jti CHAR(36) PRIMARY KEY,
+    user VARCHAR(100) NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    revoked_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_revoked_expires ON revoked_tokens (expires_at);
+
+-- Create the recovery_codes table (one-time 2FA recovery codes, stored as SHA-256 hashes)
+DROP TABLE IF EXISTS recovery_codes;
+
+CREATE TABLE recovery_codes (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    user VARCHAR(100) NOT NULL,
+    code_hash CHAR(64) NOT NULL,
+    used_at TIMESTAMP NULL DEFAULT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_recovery_user ON recovery_codes (user);
+
+-- Create the personal_access_tokens table (long-lived scoped tokens for scripts, stored hashed)
+DROP TABLE IF EXISTS personal_access_tokens;
+
+CREATE TABLE personal_access_tokens (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    user VARCHAR(100) NOT NULL,
+    name VARCHAR(100) NOT NULL,
+    token_hash CHAR(64) NOT NULL UNIQUE,
+    token_prefix VARCHAR(16) NOT NULL,
+    scopes VARCHAR(255) NOT NULL,
+    expires_at TIMESTAMP NULL DEFAULT NULL,
+    last_used_at TIMESTAMP NULL DEFAULT NULL,
+    revoked_at TIMESTAMP NULL DEFAULT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_pat_user ON personal_access_tokens (user);
+
+-- Create the password_reset_tokens table (single-use admin-issued reset tokens, stored hashed)
+DROP TABLE IF EXISTS password_reset_tokens;
+
+CREATE TABLE password_reset_tokens (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    user VARCHAR(100) NOT NULL,
+    token_hash CHAR(64) NOT NULL UNIQUE,
+    created_by VARCHAR(100) NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP NULL DEFAULT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_password_reset_user ON password_reset_tokens (user);
+
+-- Create the tags table (per-user tag names; names compare case-insensitively)
+DROP TABLE IF EXISTS tags;
+
+CREATE TABLE tags (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    user VARCHAR(100) NOT NULL,
+    name VARCHAR(50) NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE KEY uq_tags_user_name (user, name)
+);
+
+-- Create the note_tags table (many-to-many link between notes and tags)
+DROP TABLE IF EXISTS note_tags;
+
+CREATE TABLE note_tags (
+    note_id BIGINT NOT NULL,
+    tag_id BIGINT NOT NULL,
+    PRIMARY KEY (note_id, tag_id)
+);
+
+CREATE INDEX idx_note_tags_tag ON note_tags (tag_id);
+
+-- Create the notebooks table (nested folders per user; parent_id is NULL at the top level)
+DROP TABLE IF EXISTS notebooks;
+
+CREATE TABLE notebooks (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    user VARCHAR(100) NOT NULL,
+    name VARCHAR(100) NOT NULL,
+    parent_id BIGINT NULL DEFAULT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_notebooks_user_parent ON notebooks (user, parent_id);
+CREATE INDEX idx_notebooks_parent ON notebooks (parent_id);
+
+-- Create the note_revisions table (previous versions of a note, archived on every edit)
+DROP TABLE IF EXISTS note_revisions;
+
+CREATE TABLE note_revisions (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    note_id BIGINT NOT NULL,
+    revision INT NOT NULL,
+    title VARCHAR(255) NOT NULL,
+    content TEXT NOT NULL,
+    edited_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE KEY uq_note_revisions_note_revision (note_id, revision)
+);
 
 -- Optional: Create sample data for testing
 INSERT INTO notes (title, content, user, is_public) VALUES
@@ -30,3 +205,14 @@ INSERT INTO notes (title, content, user, is_public) VALUES
 
 -- Verify the table structure
 DESCRIBE notes;
+DESCRIBE users;
+DESCRIBE refresh_tokens;
+DESCRIBE sessions;
+DESCRIBE revoked_tokens;
+DESCRIBE recovery_codes;
+DESCRIBE personal_access_tokens;
+DESCRIBE password_reset_tokens;
+DESCRIBE tags;
+DESCRIBE note_tags;
+DESCRIBE notebooks;
+DESCRIBE note_revisions;
@@ -0,0 +1,338 @@
+use crate::{
+    extractors::{AuthUser, SessionUser, TokenInfo, TokenKind},
+    logging,
+    models::PersonalAccessToken,
+    state::AppState,
+    utils::{generate_opaque_token, hash_token},
+};
+use axum::{
+    extract::{Json, Path, State},
+    http::StatusCode,
+};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::{MySqlConnection, MySqlPool, Row};
+
+/// Prefix that marks a bearer token as a personal access token rather than a JWT
+pub const TOKEN_PREFIX: &str = "np_";
+
+/// Permissions a personal access token can be granted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scope {
+    NotesRead,
+    NotesWrite,
+    StatsRead,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 3] = [Scope::NotesRead, Scope::NotesWrite, Scope::StatsRead];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::NotesRead => "notes:read",
+            Scope::NotesWrite => "notes:write",
+            Scope::StatsRead => "stats:read",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Scope> {
+        Scope::ALL.into_iter().find(|scope| scope.as_str() == value)
+    }
+}
+
+/// Resolve a personal access token to its owner and granted scopes.
+///
+/// Returns `None` for unknown, revoked or expired tokens and for disabled accounts.
+pub async fn lookup(db: &MySqlPool, token: &str) -> Result<Option<AuthUser>, sqlx::Error> {
+    let row = sqlx::query(
+        "SELECT p.id, p.user, p.scopes, p.expires_at, u.role
+         FROM personal_access_tokens p
+         JOIN users u ON u.username = p.user
+         WHERE p.token_hash = ?
+           AND p.revoked_at IS NULL
+           AND (p.expires_at IS NULL OR p.expires_at > NOW())
+           AND u.is_active = true",
+    )
+    .bind(hash_token(token))
+    .fetch_optional(db)
+    .await?;
+
+    let Some(row) = row else {
+        return Ok(None);
+    };
+
+    let id: i64 = row.try_get("id")?;
+    let username: String = row.try_get("user")?;
+    let scopes: String = row.try_get("scopes")?;
+    let expires_at: Option<chrono::DateTime<chrono::Utc>> = row.try_get("expires_at")?;
+    let role: String = row.try_get("role")?;
+
+    if let Err(e) =
+        sqlx::query("UPDATE personal_access_tokens SET last_used_at = NOW() WHERE id = ?")
+            .bind(id)
+            .execute(db)
+            .await
+    {
+        logging::log_db_error("update", &e.to_string());
+    }
+
+    Ok(Some(AuthUser {
+        username,
+        roles: vec![role],
+        token: TokenInfo {
+            kind: TokenKind::PersonalAccessToken,
+            id: id.to_string(),
+            session_id: None,
+            scopes: scopes.split_whitespace().filter_map(Scope::parse).collect(),
+            expires_at,
+        },
+    }))
+}
+
+/// Parse and deduplicate requested scopes, rejecting unknown ones
+pub fn parse_scopes(requested: &[String]) -> Result<Vec<Scope>, String> {
+    let mut scopes = Vec::new();
+    for value in requested {
+        let scope = Scope::parse(value).ok_or_else(|| format!("Unknown scope: {}", value))?;
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+    if scopes.is_empty() {
+        return Err("At least one scope is required".to_string());
+    }
+    Ok(scopes)
+}
+
+/// Trim a token name and check its length
+pub fn validate_name(name: &str) -> Result<&str, String> {
+    let name = name.trim();
+    if name.is_empty() || name.len() > 100 {
+        return Err("Token name must be between 1 and 100 characters".to_string());
+    }
+    Ok(name)
+}
+
+/// Check a requested token lifetime; `None` means the token never expires
+pub fn validate_expiry(expires_in_days: Option<u32>) -> Result<Option<u32>, String> {
+    if expires_in_days == Some(0) {
+        return Err("expires_in_days must be at least 1".to_string());
+    }
+    Ok(expires_in_days)
+}
+
+/// Revoke every personal access token of a user inside the caller's transaction, so
+/// API access obtained before a password change or reset ends with it
+pub async fn revoke_all_in(conn: &mut MySqlConnection, username: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE personal_access_tokens SET revoked_at = NOW()
+         WHERE user = ? AND revoked_at IS NULL",
+    )
+    .bind(username)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Store a new personal access token for `username`; returns its ID and the plain token
+pub async fn create(
+    db: &MySqlPool,
+    username: &str,
+    name: &str,
+    scopes: &[Scope],
+    expires_in_days: Option<u32>,
+) -> Result<(u64, String), sqlx::Error> {
+    let scopes = scopes
+        .iter()
+        .map(Scope::as_str)
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let token = format!("{}{}", TOKEN_PREFIX, generate_opaque_token());
+    let token_prefix: String = token.chars().take(TOKEN_PREFIX.len() + 6).collect();
+
+    let result = sqlx::query(
+        "INSERT INTO personal_access_tokens
+             (user, name, token_hash, token_prefix, scopes, expires_at, created_at)
+         VALUES (?, ?, ?, ?, ?,
+                 IF(? IS NULL, NULL, DATE_ADD(NOW(), INTERVAL ? DAY)), NOW())",
+    )
+    .bind(username)
+    .bind(name)
+    .bind(hash_token(&token))
+    .bind(&token_prefix)
+    .bind(&scopes)
+    .bind(expires_in_days)
+    .bind(expires_in_days)
+    .execute(db)
+    .await?;
+
+    logging::log_db_operation("insert", "personal_access_tokens");
+    logging::log_security_event(
+        "access_token_created",
+        &format!("user {} token '{}' scopes [{}]", username, name, scopes),
+    );
+
+    Ok((result.last_insert_id(), token))
+}
+
+#[derive(Deserialize)]
+pub struct CreateTokenRequest {
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub expires_in_days: Option<u32>,
+}
+
+/// List the current user's active personal access tokens
+pub async fn list_tokens(
+    State(state): State<AppState>,
+    SessionUser(auth): SessionUser,
+) -> Result<Json<Vec<PersonalAccessToken>>, StatusCode> {
+    let username = auth.username;
+
+    match sqlx::query_as::<_, PersonalAccessToken>(
+        "SELECT id, name, token_prefix, scopes, expires_at, last_used_at, created_at
+         FROM personal_access_tokens
+         WHERE user = ? AND revoked_at IS NULL
+         ORDER BY created_at DESC",
+    )
+    .bind(&username)
+    .fetch_all(&*state.db)
+    .await
+    {
+        Ok(tokens) => Ok(Json(tokens)),
+        Err(e) => {
+            logging::log_db_error("select", &e.to_string());
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Create a personal access token; the plain token is only returned here
+pub async fn create_token(
+    State(state): State<AppState>,
+    SessionUser(auth): SessionUser,
+    Json(request): Json<CreateTokenRequest>,
+) -> Result<(StatusCode, Json<serde_json::Value>), StatusCode> {
+    let username = auth.username;
+
+    let name = match validate_name(&request.name) {
+        Ok(name) => name,
+        Err(message) => {
+            return Ok((StatusCode::BAD_REQUEST, Json(json!({ "error": message }))));
+        }
+    };
+
+    let scopes = match parse_scopes(&request.scopes) {
+        Ok(scopes) => scopes,
+        Err(message) => {
+            return Ok((StatusCode::BAD_REQUEST, Json(json!({ "error": message }))));
+        }
+    };
+
+    let expires_in_days = match validate_expiry(request.expires_in_days) {
+        Ok(days) => days,
+        Err(message) => {
+            return Ok((StatusCode::BAD_REQUEST, Json(json!({ "error": message }))));
+        }
+    };
+
+    let (id, token) = create(&state.db, &username, name, &scopes, expires_in_days)
+        .await
+        .map_err(|e| {
+            logging::log_db_error("insert", &e.to_string());
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let scopes = scopes
+        .iter()
+        .map(Scope::as_str)
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "id": id,
+            "name": name,
+            "scopes": scopes,
+            "token": token
+        })),
+    ))
+}
+
+/// Revoke one of the current user's personal access tokens
+pub async fn revoke_token(
+    State(state): State<AppState>,
+    SessionUser(auth): SessionUser,
+    Path(id): Path<i64>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let username = auth.username;
+
+    let result = sqlx::query(
+        "UPDATE personal_access_tokens SET revoked_at = NOW()
+         WHERE id = ? AND user = ? AND revoked_at IS NULL",
+    )
+    .bind(id)
+    .bind(&username)
+    .execute(&*state.db)
+    .await
+    .map_err(|e| {
+        logging::log_db_error("update", &e.to_string());
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    if result.rows_affected() == 0 {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    logging::log_security_event(
+        "access_token_revoked",
+        &format!("user {} token {}", username, id),
+    );
+    Ok(Json(json!({ "message": "Token revoked" })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scope_round_trip() {
+        for scope in Scope::ALL {
+            assert_eq!(Scope::parse(scope.as_str()), Some(scope));
+        }
+        assert_eq!(Scope::parse("notes:delete"), None);
+    }
+
+    #[test]
+    fn test_parse_scopes() {
+        let requested = vec![
+            "notes:read".to_string(),
+            "stats:read".to_string(),
+            "notes:read".to_string(),
+        ];
+        assert_eq!(
+            parse_scopes(&requested).unwrap(),
+            vec![Scope::NotesRead, Scope::StatsRead]
+        );
+        assert!(parse_scopes(&[]).is_err());
+        assert!(parse_scopes(&["admin".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_validate_expiry() {
+        assert_eq!(validate_expiry(None), Ok(None));
+        assert_eq!(validate_expiry(Some(30)), Ok(Some(30)));
+        assert!(validate_expiry(Some(0)).is_err());
+    }
+
+    #[test]
+    fn test_create_token_request_deserialization() {
+        let json_data = r#"{"name":"ci","scopes":["notes:read"]}"#;
+        let request: CreateTokenRequest = serde_json::from_str(json_data).unwrap();
+        assert_eq!(request.name, "ci");
+        assert_eq!(request.scopes, vec!["notes:read"]);
+        assert_eq!(request.expires_in_days, None);
+    }
+}
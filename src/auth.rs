@@ -1,125 +1,658 @@
 use crate::{
+    auth_provider::AuthFailure,
+    client_ip::ClientIp,
+    cookies,
+    extractors::SessionUser,
     logging,
+    models::User,
+    rbac::Role,
+    refresh_tokens::{self, RotateOutcome},
+    sessions,
     state::AppState,
-    utils::{generate_token, verify_password},
+    two_factor, users,
+    utils::{generate_opaque_token, generate_token, hash_password},
 };
 use axum::{
     extract::{Json, State},
-    http::StatusCode,
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
 };
 use serde::Deserialize;
 use serde_json::json;
-use std::env;
+use std::{env, time::Duration};
 
 #[derive(Deserialize)]
 pub struct LoginRequest {
     username: String,
     password: String,
+    /// Deliver the session as HttpOnly cookies instead of tokens in the body
+    #[serde(default)]
+    use_cookie: bool,
+}
+
+/// Build the 429 response returned while a username or client IP is locked out
+fn too_many_attempts(retry_after: Duration) -> Response {
+    // Round up so clients never retry a moment too early
+    let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, seconds.to_string())],
+        Json(json!({
+            "error": "Too many failed login attempts",
+            "retry_after": seconds
+        })),
+    )
+        .into_response()
 }
 
 pub async fn login(
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
+    client_ip: ClientIp,
+    headers: HeaderMap,
     Json(req): Json<LoginRequest>,
-) -> (StatusCode, Json<serde_json::Value>) {
-    // Get admin user from environment
-    let admin_user = match env::var("ADMIN_USER") {
-        Ok(user) => {
-            if user.is_empty() {
-                logging::log_config_error("ADMIN_USER", "empty value");
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({ "error": "Server configuration error" })),
+) -> Response {
+    let ip = client_ip.to_string();
+
+    // Refuse attempts while the account or the client IP is locked out
+    if let Some(retry_after) = state.login_throttle.check(&req.username, &ip) {
+        logging::log_security_event(
+            "login_locked_out",
+            &format!("user '{}' from {}", req.username, client_ip),
+        );
+        return too_many_attempts(retry_after);
+    }
+
+    // Verify credentials against the configured providers (LDAP, then local accounts)
+    let verified = state
+        .auth_providers
+        .authenticate(&state.db, &req.username, &req.password)
+        .await;
+
+    // Log authentication attempt (without exposing sensitive data)
+    let user = match verified {
+        Ok(user) => user,
+        Err(AuthFailure::Unavailable(e)) => {
+            logging::log_db_error("select", &e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Internal server error" })),
+            )
+                .into_response();
+        }
+        Err(AuthFailure::Rejected(failure_reason)) => {
+            logging::log_auth_failure(&req.username, failure_reason);
+
+            if let Some(lockout) = state.login_throttle.record_failure(&req.username, &ip) {
+                logging::log_security_event(
+                    "login_lockout",
+                    &format!(
+                        "user '{}' from {} locked for {}s",
+                        req.username,
+                        client_ip,
+                        lockout.as_secs()
+                    ),
                 );
+                return too_many_attempts(lockout);
             }
-            user
+
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(json!({ "error": "Invalid credentials" })),
+            )
+                .into_response();
+        }
+    };
+
+    // Accounts with 2FA get a short-lived challenge instead of tokens
+    if user.totp_enabled {
+        let mfa_token = state.mfa_challenges.create(&req.username);
+        return (
+            StatusCode::OK,
+            Json(json!({ "mfa_required": true, "mfa_token": mfa_token })),
+        )
+            .into_response();
+    }
+
+    state.login_throttle.record_success(&req.username);
+
+    match issue_session(
+        &state,
+        &user.username,
+        user_role(&user),
+        client_ip,
+        &headers,
+    )
+    .await
+    {
+        Ok(tokens) => {
+            logging::log_auth_success(&req.username);
+            session_response(StatusCode::OK, tokens, req.use_cookie)
         }
-        Err(_) => {
-            logging::log_config_error("ADMIN_USER", "not set");
+        Err(response) => response.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LoginTwoFactorRequest {
+    mfa_token: String,
+    code: String,
+    #[serde(default)]
+    use_cookie: bool,
+}
+
+/// Second login step for accounts with 2FA: exchange the challenge and a TOTP or
+/// recovery code for tokens
+pub async fn login_2fa(
+    State(state): State<AppState>,
+    client_ip: ClientIp,
+    headers: HeaderMap,
+    Json(req): Json<LoginTwoFactorRequest>,
+) -> Response {
+    let ip = client_ip.to_string();
+
+    let Some(username) = state.mfa_challenges.username(&req.mfa_token) else {
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(json!({ "error": "Invalid or expired login challenge" })),
+        )
+            .into_response();
+    };
+
+    if let Some(retry_after) = state.login_throttle.check(&username, &ip) {
+        logging::log_security_event(
+            "login_locked_out",
+            &format!("user '{}' from {}", username, client_ip),
+        );
+        return too_many_attempts(retry_after);
+    }
+
+    let user = match users::find_by_username(&state.db, &username).await {
+        Ok(Some(user)) if user.is_active && user.totp_enabled => user,
+        Ok(_) => {
+            state.mfa_challenges.complete(&req.mfa_token);
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(json!({ "error": "Invalid or expired login challenge" })),
+            )
+                .into_response();
+        }
+        Err(e) => {
+            logging::log_db_error("select", &e.to_string());
             return (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({ "error": "Server configuration error" })),
-            );
+                Json(json!({ "error": "Internal server error" })),
+            )
+                .into_response();
         }
     };
 
-    // Get admin password hash from environment
-    let admin_hash = match env::var("ADMIN_PASS_HASH") {
-        Ok(mut hash) => {
-            // Remove surrounding quotes if present
-            if hash.starts_with('"') && hash.ends_with('"') {
-                hash = hash.trim_matches('"').to_string();
+    match two_factor::verify_second_factor(&state.db, &user, &req.code).await {
+        Ok(true) => {}
+        Ok(false) => {
+            logging::log_auth_failure(&username, "invalid 2fa code");
+            state.mfa_challenges.record_failure(&req.mfa_token);
+
+            if let Some(lockout) = state.login_throttle.record_failure(&username, &ip) {
+                logging::log_security_event(
+                    "login_lockout",
+                    &format!(
+                        "user '{}' from {} locked for {}s",
+                        username,
+                        client_ip,
+                        lockout.as_secs()
+                    ),
+                );
+                return too_many_attempts(lockout);
             }
 
-            // Validate hash format in production
-            let is_development =
-                env::var("RUST_ENV").unwrap_or_else(|_| "development".to_string()) != "production";
-
-            if !is_development {
-                // In production, only log hash format validation issues
-                if !hash.starts_with("$2b$")
-                    && !hash.starts_with("$2a$")
-                    && !hash.starts_with("$2y$")
-                {
-                    logging::log_config_error("ADMIN_PASS_HASH", "invalid bcrypt format");
-                    return (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(json!({ "error": "Server configuration error" })),
-                    );
-                }
-
-                if hash.len() != 60 {
-                    logging::log_config_error("ADMIN_PASS_HASH", "invalid bcrypt hash length");
-                    return (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(json!({ "error": "Server configuration error" })),
-                    );
-                }
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(json!({ "error": "Invalid code" })),
+            )
+                .into_response();
+        }
+        Err(e) => {
+            logging::log_db_error("update", &e.to_string());
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Internal server error" })),
+            )
+                .into_response();
+        }
+    }
+
+    state.mfa_challenges.complete(&req.mfa_token);
+    state.login_throttle.record_success(&username);
+
+    match issue_session(&state, &username, user_role(&user), client_ip, &headers).await {
+        Ok(tokens) => {
+            logging::log_auth_success(&username);
+            session_response(StatusCode::OK, tokens, req.use_cookie)
+        }
+        Err(response) => response.into_response(),
+    }
+}
+
+/// Send issued tokens as JSON, or in cookie session mode as HttpOnly cookies with a
+/// CSRF token in the body for the client to echo in the `X-CSRF-Token` header
+fn session_response(status: StatusCode, tokens: serde_json::Value, use_cookie: bool) -> Response {
+    if !use_cookie {
+        return (status, Json(tokens)).into_response();
+    }
+
+    let csrf_token = generate_opaque_token();
+    let mut response = (
+        status,
+        Json(json!({ "csrf_token": csrf_token, "expires_in": 3600 })),
+    )
+        .into_response();
+    set_session_cookies(&mut response, &tokens, &csrf_token);
+    response
+}
+
+/// Put the tokens from `issue_session` into the session cookies of `response`
+pub(crate) fn set_session_cookies(
+    response: &mut Response,
+    tokens: &serde_json::Value,
+    csrf_token: &str,
+) {
+    for cookie in cookies::session_cookies(
+        tokens["token"].as_str().unwrap_or_default(),
+        tokens["refresh_token"].as_str().unwrap_or_default(),
+        csrf_token,
+    ) {
+        response.headers_mut().append(header::SET_COOKIE, cookie);
+    }
+}
+
+/// Clear session cookies on logout if the client used cookie session mode
+fn clear_cookies_if_present(headers: &HeaderMap, response: &mut Response) {
+    if cookies::get(headers, cookies::SESSION_COOKIE).is_some()
+        || cookies::get(headers, cookies::REFRESH_COOKIE).is_some()
+    {
+        for cookie in cookies::clear_session_cookies() {
+            response.headers_mut().append(header::SET_COOKIE, cookie);
+        }
+    }
+}
+
+/// Role stored for an account; unknown values fall back to the least privileged role
+pub(crate) fn user_role(user: &User) -> Role {
+    Role::parse(&user.role).unwrap_or_default()
+}
+
+/// Start a session for a user: record the login, then issue an access token plus
+/// a fresh refresh token family tied to it
+pub(crate) async fn issue_session(
+    state: &AppState,
+    username: &str,
+    role: Role,
+    client_ip: ClientIp,
+    headers: &HeaderMap,
+) -> Result<serde_json::Value, (StatusCode, Json<serde_json::Value>)> {
+    let session_id = sessions::create(&state.db, username, client_ip, headers)
+        .await
+        .map_err(|e| {
+            logging::log_db_error("insert", &e.to_string());
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Token generation failed" })),
+            )
+        })?;
+
+    let token = generate_token(username, role, &session_id).map_err(|e| {
+        logging::log_auth_error("token generation", &e.to_string());
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": "Token generation failed" })),
+        )
+    })?;
+
+    let refresh_token = refresh_tokens::issue(&state.db, username, &session_id)
+        .await
+        .map_err(|e| {
+            logging::log_db_error("insert", &e.to_string());
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Token generation failed" })),
+            )
+        })?;
+
+    Ok(json!({ "token": token, "refresh_token": refresh_token }))
+}
+
+#[derive(Deserialize)]
+pub struct RegisterRequest {
+    username: String,
+    password: String,
+    invite_code: Option<String>,
+}
+
+/// Self-service signup policy, configured through SIGNUP_MODE
+#[derive(Debug, PartialEq)]
+pub enum SignupMode {
+    Open,
+    InviteOnly,
+    Disabled,
+}
+
+impl SignupMode {
+    /// Read SIGNUP_MODE (`open`, `invite` or `disabled`); anything else disables signup
+    pub fn from_env() -> Self {
+        match env::var("SIGNUP_MODE")
+            .unwrap_or_default()
+            .to_lowercase()
+            .as_str()
+        {
+            "open" => SignupMode::Open,
+            "invite" | "invite_only" => SignupMode::InviteOnly,
+            _ => SignupMode::Disabled,
+        }
+    }
+}
+
+/// Check an invite code against the comma-separated SIGNUP_INVITE_CODES list
+fn is_valid_invite_code(code: &str) -> bool {
+    let codes = env::var("SIGNUP_INVITE_CODES").unwrap_or_default();
+    !code.is_empty() && codes.split(',').map(str::trim).any(|c| c == code)
+}
+
+/// Validate username format: 3-100 characters of letters, digits, '.', '_' or '-'
+fn validate_username(username: &str) -> Result<(), &'static str> {
+    if username.len() < 3 || username.len() > 100 {
+        return Err("Username must be between 3 and 100 characters");
+    }
+    if !username
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-')
+    {
+        return Err("Username may only contain letters, digits, '.', '_' and '-'");
+    }
+    Ok(())
+}
+
+/// Validate password strength: at least 8 characters
+pub fn validate_password(password: &str) -> Result<(), &'static str> {
+    if password.chars().count() < 8 {
+        return Err("Password must be at least 8 characters");
+    }
+    Ok(())
+}
+
+pub async fn register(
+    State(state): State<AppState>,
+    client_ip: ClientIp,
+    headers: HeaderMap,
+    Json(req): Json<RegisterRequest>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    // Enforce the configured signup policy
+    match SignupMode::from_env() {
+        SignupMode::Open => {}
+        SignupMode::InviteOnly => {
+            if !is_valid_invite_code(req.invite_code.as_deref().unwrap_or("")) {
+                logging::log_security_event("registration_rejected", "invalid invite code");
+                return (
+                    StatusCode::FORBIDDEN,
+                    Json(json!({ "error": "A valid invite code is required" })),
+                );
             }
+        }
+        SignupMode::Disabled => {
+            return (
+                StatusCode::FORBIDDEN,
+                Json(json!({ "error": "Registration is disabled" })),
+            );
+        }
+    }
 
-            hash
+    if let Err(message) = validate_username(&req.username).and(validate_password(&req.password)) {
+        return (StatusCode::BAD_REQUEST, Json(json!({ "error": message })));
+    }
+
+    match users::find_by_username(&state.db, &req.username).await {
+        Ok(Some(_)) => {
+            return (
+                StatusCode::CONFLICT,
+                Json(json!({ "error": "Username already taken" })),
+            );
+        }
+        Ok(None) => {}
+        Err(e) => {
+            logging::log_db_error("select", &e.to_string());
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Internal server error" })),
+            );
         }
-        Err(_) => {
-            logging::log_config_error("ADMIN_PASS_HASH", "not set");
+    }
+
+    let password_hash = match hash_password(&req.password) {
+        Ok(hash) => hash,
+        Err(e) => {
+            logging::log_auth_error("password hashing", &e.to_string());
             return (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({ "error": "Server configuration error" })),
+                Json(json!({ "error": "Failed to create account" })),
             );
         }
     };
 
-    // Verify credentials
-    let username_match = req.username == admin_user;
-    let password_match = verify_password(&admin_hash, &req.password);
+    // Self-registered accounts can manage their own notes; admins grant other roles
+    let role = Role::Editor;
 
-    // Log authentication attempt (without exposing sensitive data)
-    if username_match && password_match {
-        match generate_token(&req.username) {
-            Ok(token) => {
-                logging::log_auth_success(&req.username);
-                (StatusCode::OK, Json(json!({ "token": token })))
+    if let Err(e) = users::create_user(&state.db, &req.username, &password_hash, role).await {
+        // A concurrent registration may have claimed the username in between
+        if let sqlx::Error::Database(db_err) = &e
+            && db_err.is_unique_violation()
+        {
+            return (
+                StatusCode::CONFLICT,
+                Json(json!({ "error": "Username already taken" })),
+            );
+        }
+        logging::log_db_error("insert", &e.to_string());
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": "Failed to create account" })),
+        );
+    }
+
+    logging::log_db_operation("insert", "users");
+    logging::log_security_event("user_registered", &req.username);
+
+    match issue_session(&state, &req.username, role, client_ip, &headers).await {
+        Ok(tokens) => (StatusCode::CREATED, Json(tokens)),
+        Err(response) => response,
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RefreshRequest {
+    /// Omitted in cookie session mode, where the refresh cookie is used instead
+    refresh_token: Option<String>,
+}
+
+pub async fn refresh(
+    State(state): State<AppState>,
+    client_ip: ClientIp,
+    headers: HeaderMap,
+    body: Option<Json<RefreshRequest>>,
+) -> Response {
+    let (presented, use_cookie) = match body.and_then(|Json(req)| req.refresh_token) {
+        Some(token) => (token, false),
+        None => match cookies::get(&headers, cookies::REFRESH_COOKIE) {
+            Some(token) if cookies::verify_csrf(&headers) => (token.to_string(), true),
+            Some(_) => {
+                logging::log_security_event("csrf_rejected", "POST /token/refresh");
+                return (
+                    StatusCode::FORBIDDEN,
+                    Json(json!({ "error": "Missing or invalid CSRF token" })),
+                )
+                    .into_response();
+            }
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({ "error": "Missing refresh token" })),
+                )
+                    .into_response();
+            }
+        },
+    };
+
+    let (username, session_id, refresh_token) =
+        match refresh_tokens::rotate(&state.db, &presented).await {
+            Ok(RotateOutcome::Rotated {
+                username,
+                session_id,
+                refresh_token,
+            }) => (username, session_id, refresh_token),
+            Ok(RotateOutcome::Invalid) | Ok(RotateOutcome::Reused) => {
+                return (
+                    StatusCode::UNAUTHORIZED,
+                    Json(json!({ "error": "Invalid refresh token" })),
+                )
+                    .into_response();
             }
             Err(e) => {
-                logging::log_auth_error("token generation", &e.to_string());
-                (
+                logging::log_db_error("rotate", &e.to_string());
+                return (
                     StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({ "error": "Token generation failed" })),
+                    Json(json!({ "error": "Internal server error" })),
                 )
+                    .into_response();
             }
-        }
-    } else {
-        // Log failed authentication attempt without exposing details
-        let failure_reason = if !username_match {
-            "invalid username"
-        } else {
-            "invalid password"
         };
-        logging::log_auth_failure(&req.username, failure_reason);
 
-        (
-            StatusCode::UNAUTHORIZED,
-            Json(json!({ "error": "Invalid credentials" })),
+    // Disabled accounts cannot keep renewing their sessions
+    let role = match users::find_by_username(&state.db, &username).await {
+        Ok(Some(user)) if user.is_active => user_role(&user),
+        Ok(_) => {
+            logging::log_auth_failure(&username, "refresh for missing or disabled account");
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(json!({ "error": "Invalid refresh token" })),
+            )
+                .into_response();
+        }
+        Err(e) => {
+            logging::log_db_error("select", &e.to_string());
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Internal server error" })),
+            )
+                .into_response();
+        }
+    };
+
+    if let Err(e) = sessions::touch(&state.db, &session_id, client_ip, &headers).await {
+        logging::log_db_error("update", &e.to_string());
+    }
+
+    match generate_token(&username, role, &session_id) {
+        Ok(token) => session_response(
+            StatusCode::OK,
+            json!({ "token": token, "refresh_token": refresh_token }),
+            use_cookie,
+        ),
+        Err(e) => {
+            logging::log_auth_error("token generation", &e.to_string());
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Token generation failed" })),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LogoutRequest {
+    refresh_token: Option<String>,
+}
+
+/// End the session used for this request: its access tokens and refresh token family,
+/// plus the family of a refresh token passed in the body or cookie
+pub async fn logout(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    SessionUser(auth): SessionUser,
+    body: Option<Json<LogoutRequest>>,
+) -> Response {
+    let exp = auth
+        .token
+        .expires_at
+        .map(|at| at.timestamp())
+        .unwrap_or_else(|| chrono::Utc::now().timestamp() + 3600);
+
+    if let Err(e) = state
+        .revocations
+        .revoke_token(&state.db, &auth.token.id, &auth.username, exp)
+        .await
+    {
+        logging::log_db_error("insert", &e.to_string());
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": "Logout failed" })),
         )
+            .into_response();
+    }
+
+    let refresh_token = body
+        .and_then(|Json(req)| req.refresh_token)
+        .or_else(|| cookies::get(&headers, cookies::REFRESH_COOKIE).map(str::to_string));
+    if let Some(refresh_token) = refresh_token
+        && let Err(e) = refresh_tokens::revoke(&state.db, &auth.username, &refresh_token).await
+    {
+        logging::log_db_error("update", &e.to_string());
+    }
+
+    if let Some(session_id) = &auth.token.session_id
+        && let Err(e) = state
+            .revocations
+            .revoke_session(&state.db, &auth.username, session_id)
+            .await
+    {
+        logging::log_db_error("update", &e.to_string());
     }
+
+    logging::log_security_event("logout", &auth.username);
+    let mut response = (
+        StatusCode::OK,
+        Json(json!({ "message": "Logged out successfully" })),
+    )
+        .into_response();
+    clear_cookies_if_present(&headers, &mut response);
+    response
+}
+
+/// Revoke every access and refresh token issued to the current user
+pub async fn logout_all(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    SessionUser(auth): SessionUser,
+) -> Response {
+    if let Err(e) = state
+        .revocations
+        .revoke_all_for_user(&state.db, &auth.username)
+        .await
+    {
+        logging::log_db_error("update", &e.to_string());
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": "Logout failed" })),
+        )
+            .into_response();
+    }
+
+    logging::log_security_event("logout_all", &auth.username);
+    let mut response = (
+        StatusCode::OK,
+        Json(json!({ "message": "Logged out of all sessions" })),
+    )
+        .into_response();
+    clear_cookies_if_present(&headers, &mut response);
+    response
 }
 
 #[cfg(test)]
@@ -132,6 +665,26 @@ mod tests {
         let login_req: LoginRequest = serde_json::from_str(json_data).unwrap();
         assert_eq!(login_req.username, "testuser");
         assert_eq!(login_req.password, "testpass");
+        assert!(!login_req.use_cookie);
+    }
+
+    #[test]
+    fn test_cookie_session_response() {
+        let tokens = json!({ "token": "jwt", "refresh_token": "refresh" });
+
+        let response = session_response(StatusCode::OK, tokens.clone(), false);
+        assert!(response.headers().get(header::SET_COOKIE).is_none());
+
+        let response = session_response(StatusCode::OK, tokens, true);
+        let cookies: Vec<_> = response
+            .headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .map(|v| v.to_str().unwrap().to_string())
+            .collect();
+        assert_eq!(cookies.len(), 3);
+        assert!(cookies[0].starts_with("notepad_session=jwt;"));
+        assert!(cookies[1].starts_with("notepad_refresh=refresh;"));
     }
 
     #[test]
@@ -141,4 +694,42 @@ mod tests {
         let admin_user = std::env::var("ADMIN_USER");
         assert!(admin_user.is_err() || admin_user.unwrap().is_empty());
     }
+
+    #[test]
+    fn test_register_request_deserialization() {
+        let json_data = r#"{"username":"newuser","password":"longenough"}"#;
+        let req: RegisterRequest = serde_json::from_str(json_data).unwrap();
+        assert_eq!(req.username, "newuser");
+        assert_eq!(req.invite_code, None);
+    }
+
+    #[test]
+    fn test_refresh_request_deserialization() {
+        let json_data = r#"{"refresh_token":"abc123"}"#;
+        let req: RefreshRequest = serde_json::from_str(json_data).unwrap();
+        assert_eq!(req.refresh_token.as_deref(), Some("abc123"));
+    }
+
+    #[test]
+    fn test_login_2fa_request_deserialization() {
+        let json_data = r#"{"mfa_token":"challenge","code":"123456"}"#;
+        let req: LoginTwoFactorRequest = serde_json::from_str(json_data).unwrap();
+        assert_eq!(req.mfa_token, "challenge");
+        assert_eq!(req.code, "123456");
+    }
+
+    #[test]
+    fn test_validate_username() {
+        assert!(validate_username("alice").is_ok());
+        assert!(validate_username("team.member-01").is_ok());
+        assert!(validate_username("ab").is_err());
+        assert!(validate_username("has space").is_err());
+        assert!(validate_username("semi;colon").is_err());
+    }
+
+    #[test]
+    fn test_validate_password() {
+        assert!(validate_password("longenough").is_ok());
+        assert!(validate_password("short").is_err());
+    }
 }
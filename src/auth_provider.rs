@@ -0,0 +1,135 @@
+use crate::{
+    ldap::{LdapConfig, LdapProvider},
+    logging,
+    models::User,
+    users,
+    utils::{hash_password, needs_rehash, verify_dummy_password, verify_password},
+};
+use sqlx::MySqlPool;
+use std::future::Future;
+
+/// Why a provider did not authenticate a login
+#[derive(Debug, PartialEq)]
+pub enum AuthFailure {
+    /// Wrong username or password, or a disabled account
+    Rejected(&'static str),
+    /// The backing store could not be reached; the reason is for logs only
+    Unavailable(String),
+}
+
+/// A source of truth for username/password logins
+pub trait AuthProvider {
+    fn name(&self) -> &'static str;
+
+    /// Check credentials and return the matching local account
+    fn authenticate(
+        &self,
+        db: &MySqlPool,
+        username: &str,
+        password: &str,
+    ) -> impl Future<Output = Result<User, AuthFailure>> + Send;
+}
+
+/// Password hashes stored in the users table (including the ADMIN_USER bootstrap account)
+pub struct LocalProvider;
+
+impl AuthProvider for LocalProvider {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    async fn authenticate(
+        &self,
+        db: &MySqlPool,
+        username: &str,
+        password: &str,
+    ) -> Result<User, AuthFailure> {
+        let user = users::find_by_username(db, username)
+            .await
+            .map_err(|e| AuthFailure::Unavailable(e.to_string()))?;
+
+        // Unknown and disabled accounts still pay for a hash check so response
+        // times do not reveal which usernames exist
+        match user {
+            None => {
+                verify_dummy_password(password);
+                Err(AuthFailure::Rejected("invalid username"))
+            }
+            Some(user) if !user.is_active => {
+                verify_dummy_password(password);
+                Err(AuthFailure::Rejected("account disabled"))
+            }
+            Some(user) if !verify_password(&user.password_hash, password) => {
+                Err(AuthFailure::Rejected("invalid password"))
+            }
+            Some(user) => {
+                upgrade_hash(db, &user, password).await;
+                Ok(user)
+            }
+        }
+    }
+}
+
+/// Re-hash a verified password if its stored hash uses bcrypt or outdated Argon2id
+/// parameters. Failures are logged; the login still succeeds with the old hash.
+async fn upgrade_hash(db: &MySqlPool, user: &User, password: &str) {
+    if !needs_rehash(&user.password_hash) {
+        return;
+    }
+
+    let result = match hash_password(password).map_err(|e| e.to_string()) {
+        Ok(hash) => users::set_password_hash(db, &user.username, &hash)
+            .await
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(_) => logging::log_security_event("password_rehashed", &user.username),
+        Err(e) => logging::log_db_error("update", &e),
+    }
+}
+
+/// Providers consulted by `auth::login`, in order: LDAP (if configured), then local accounts
+#[derive(Default)]
+pub struct AuthProviders {
+    ldap: Option<LdapProvider>,
+}
+
+impl AuthProviders {
+    /// Build the provider chain from LDAP_* environment variables
+    pub fn from_env() -> Result<Self, String> {
+        Ok(AuthProviders {
+            ldap: LdapConfig::from_env()?.map(LdapProvider::new),
+        })
+    }
+
+    pub fn with_ldap(ldap: LdapProvider) -> Self {
+        AuthProviders { ldap: Some(ldap) }
+    }
+
+    /// Try each provider until one accepts the credentials.
+    ///
+    /// Local accounts stay usable when the directory rejects a login or is down,
+    /// so the bootstrap admin can always sign in.
+    pub async fn authenticate(
+        &self,
+        db: &MySqlPool,
+        username: &str,
+        password: &str,
+    ) -> Result<User, AuthFailure> {
+        if let Some(ldap) = &self.ldap {
+            match ldap.authenticate(db, username, password).await {
+                Ok(user) => return Ok(user),
+                Err(AuthFailure::Rejected(reason)) => {
+                    logging::log_auth_failure(username, &format!("{} ({})", reason, ldap.name()));
+                }
+                Err(AuthFailure::Unavailable(reason)) => {
+                    logging::log_security_event("ldap_unavailable", &reason);
+                }
+            }
+        }
+
+        LocalProvider.authenticate(db, username, password).await
+    }
+}
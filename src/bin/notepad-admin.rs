@@ -0,0 +1,448 @@
+use backend::{
+    access_tokens,
+    auth::validate_password,
+    db,
+    models::Note,
+    rbac::Role,
+    revocation::{self, RevocationList},
+    tags, users,
+    utils::{hash_password, is_valid_password_hash, verify_password},
+};
+use clap::{Parser, Subcommand};
+use serde::Deserialize;
+use sqlx::MySqlPool;
+use std::{
+    collections::BTreeSet,
+    fs,
+    io::{self, Read, Write},
+    process::ExitCode,
+};
+
+type CliResult = Result<(), Box<dyn std::error::Error>>;
+
+/// Schema script applied by `notepad-admin migrate`
+const MIGRATION_SQL: &str = include_str!("../../migrate.sql");
+
+/// Marker in migrate.sql before the optional sample notes
+const SAMPLE_DATA_MARKER: &str = "-- Optional: Create sample data for testing";
+
+/// Administration tool for the notepad server.
+///
+/// Commands that touch the database connect to DATABASE_URL (read from the
+/// environment or .env). Passwords are always read from a hidden prompt.
+#[derive(Parser)]
+#[command(name = "notepad-admin", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Manage user accounts
+    #[command(subcommand)]
+    User(UserCommand),
+    /// Print an Argon2id hash for a password (e.g. for ADMIN_PASS_HASH)
+    HashPassword,
+    /// Check a password against a stored Argon2id or bcrypt hash
+    VerifyHash {
+        /// The stored hash
+        hash: String,
+    },
+    /// Manage personal access tokens
+    #[command(subcommand)]
+    Token(TokenCommand),
+    /// Recreate the schema from migrate.sql (drops every table and its data)
+    Migrate {
+        /// Confirm that existing tables and data may be dropped
+        #[arg(long)]
+        yes: bool,
+        /// Also insert the sample notes from migrate.sql
+        #[arg(long)]
+        sample_data: bool,
+    },
+    /// Export or import notes as JSON
+    #[command(subcommand)]
+    Notes(NotesCommand),
+}
+
+#[derive(Subcommand)]
+enum UserCommand {
+    /// Create an account
+    Create {
+        username: String,
+        /// viewer, editor or admin
+        #[arg(long, default_value = "editor")]
+        role: String,
+    },
+    /// Disable an account and end all of its sessions
+    Disable { username: String },
+    /// Re-enable a disabled account
+    Enable { username: String },
+    /// Set a new password and end all of the account's sessions
+    ResetPassword { username: String },
+}
+
+#[derive(Subcommand)]
+enum TokenCommand {
+    /// Mint a personal access token for a user; the token is printed once
+    Create {
+        username: String,
+        #[arg(long)]
+        name: String,
+        /// notes:read, notes:write or stats:read (repeatable)
+        #[arg(long = "scope", required = true)]
+        scopes: Vec<String>,
+        #[arg(long)]
+        expires_in_days: Option<u32>,
+    },
+}
+
+#[derive(Subcommand)]
+enum NotesCommand {
+    /// Write notes as a JSON array to a file or stdout
+    Export {
+        /// Only export notes owned by this user
+        #[arg(long)]
+        user: Option<String>,
+        /// Output file (default: stdout)
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Insert notes from a JSON array (as written by `export`) as new notes
+    Import {
+        /// Input file, or - for stdin
+        file: String,
+        /// Assign every imported note to this user instead of its `user` field
+        #[arg(long)]
+        user: Option<String>,
+    },
+}
+
+/// A note read by `notes import`; IDs are not kept, timestamps are when present
+#[derive(Deserialize)]
+struct ImportedNote {
+    title: String,
+    content: String,
+    user: Option<String>,
+    #[serde(default)]
+    is_public: bool,
+    created_at: Option<chrono::DateTime<chrono::Utc>>,
+    updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+fn prompt_password(prompt: &str) -> io::Result<String> {
+    rpassword::prompt_password(prompt)
+}
+
+/// Ask for a new password twice and check it against the server's password policy
+fn prompt_new_password() -> Result<String, Box<dyn std::error::Error>> {
+    let password = prompt_password("New password: ")?;
+    validate_password(&password)?;
+    if prompt_password("Repeat password: ")? != password {
+        return Err("Passwords do not match".into());
+    }
+    Ok(password)
+}
+
+async fn require_user(db: &MySqlPool, username: &str) -> CliResult {
+    match users::find_by_username(db, username).await? {
+        Some(_) => Ok(()),
+        None => Err(format!("User '{}' not found", username).into()),
+    }
+}
+
+async fn run_user(command: UserCommand) -> CliResult {
+    match command {
+        UserCommand::Create { username, role } => {
+            let role = Role::parse(&role)
+                .ok_or_else(|| format!("Unknown role '{}': use viewer, editor or admin", role))?;
+            let username = username.trim().to_string();
+            if username.is_empty() || username.len() > 100 {
+                return Err("Username must be between 1 and 100 characters".into());
+            }
+
+            let db = db::connect_db().await?;
+            if users::find_by_username(&db, &username).await?.is_some() {
+                return Err(format!("User '{}' already exists", username).into());
+            }
+
+            let password_hash = hash_password(&prompt_new_password()?)?;
+            users::create_user(&db, &username, &password_hash, role).await?;
+            println!("Created {} '{}'", role.as_str(), username);
+        }
+        UserCommand::Disable { username } => {
+            let db = db::connect_db().await?;
+            if !users::set_active(&db, &username, false).await? {
+                return Err(format!("User '{}' not found", username).into());
+            }
+            RevocationList::default()
+                .revoke_all_for_user(&db, &username)
+                .await?;
+            println!("Disabled '{}' and revoked its sessions", username);
+        }
+        UserCommand::Enable { username } => {
+            let db = db::connect_db().await?;
+            if !users::set_active(&db, &username, true).await? {
+                return Err(format!("User '{}' not found", username).into());
+            }
+            println!("Enabled '{}'", username);
+        }
+        UserCommand::ResetPassword { username } => {
+            let db = db::connect_db().await?;
+            require_user(&db, &username).await?;
+
+            let password_hash = hash_password(&prompt_new_password()?)?;
+            let mut tx = db.begin().await?;
+            users::set_password_hash(&mut *tx, &username, &password_hash).await?;
+            revocation::revoke_all_in(&mut tx, &username).await?;
+            access_tokens::revoke_all_in(&mut tx, &username).await?;
+            tx.commit().await?;
+            println!(
+                "Password for '{}' reset; existing sessions and access tokens revoked",
+                username
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn run_token(command: TokenCommand) -> CliResult {
+    match command {
+        TokenCommand::Create {
+            username,
+            name,
+            scopes,
+            expires_in_days,
+        } => {
+            let name = access_tokens::validate_name(&name)?;
+            let scopes = access_tokens::parse_scopes(&scopes)?;
+            let expires_in_days = access_tokens::validate_expiry(expires_in_days)?;
+
+            let db = db::connect_db().await?;
+            require_user(&db, &username).await?;
+
+            let (id, token) =
+                access_tokens::create(&db, &username, name, &scopes, expires_in_days).await?;
+            eprintln!(
+                "Created token {} for '{}'; it will not be shown again:",
+                id, username
+            );
+            println!("{}", token);
+        }
+    }
+    Ok(())
+}
+
+/// Split a SQL script into statements, dropping comment lines.
+///
+/// migrate.sql has no semicolons inside string literals, so splitting on `;` is enough.
+fn sql_statements(script: &str) -> Vec<String> {
+    script
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("--"))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .split(';')
+        .map(str::trim)
+        .filter(|statement| !statement.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Statements of migrate.sql to run: schema only, or schema plus sample notes.
+/// `DESCRIBE` statements are for interactive use and are skipped.
+fn migration_statements(sample_data: bool) -> Vec<String> {
+    let (schema, sample) = MIGRATION_SQL
+        .split_once(SAMPLE_DATA_MARKER)
+        .unwrap_or((MIGRATION_SQL, ""));
+
+    let mut statements = sql_statements(schema);
+    if sample_data {
+        statements.extend(sql_statements(sample));
+    }
+    statements.retain(|statement| !statement.to_uppercase().starts_with("DESCRIBE"));
+    statements
+}
+
+async fn run_migrate(yes: bool, sample_data: bool) -> CliResult {
+    if !yes {
+        return Err("migrate drops and recreates every table; re-run with --yes to confirm".into());
+    }
+
+    let db = db::connect_db().await?;
+    let statements = migration_statements(sample_data);
+    for statement in &statements {
+        sqlx::raw_sql(statement).execute(&db).await?;
+    }
+    println!("Applied {} statements from migrate.sql", statements.len());
+    Ok(())
+}
+
+async fn run_notes(command: NotesCommand) -> CliResult {
+    match command {
+        NotesCommand::Export { user, output } => {
+            let db = db::connect_db().await?;
+            let mut notes = sqlx::query_as::<_, Note>(
+                "SELECT id, title, content, user, is_public, notebook_id, version, created_at, updated_at
+                 FROM notes
+                 WHERE deleted_at IS NULL AND (? IS NULL OR user = ?)
+                 ORDER BY id",
+            )
+            .bind(&user)
+            .bind(&user)
+            .fetch_all(&db)
+            .await?;
+            tags::attach(&db, &mut notes).await?;
+
+            let json = serde_json::to_string_pretty(&notes)?;
+            match output {
+                Some(path) => fs::write(&path, json + "\n")?,
+                None => println!("{}", json),
+            }
+            eprintln!("Exported {} notes", notes.len());
+        }
+        NotesCommand::Import { file, user } => {
+            let mut input = String::new();
+            if file == "-" {
+                io::stdin().read_to_string(&mut input)?;
+            } else {
+                input = fs::read_to_string(&file)?;
+            }
+            let notes: Vec<ImportedNote> = serde_json::from_str(&input)?;
+            let note_tags = notes
+                .iter()
+                .enumerate()
+                .map(|(index, note)| {
+                    tags::normalize(&note.tags).map_err(|e| format!("Note {}: {}", index, e))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let owners = notes
+                .iter()
+                .enumerate()
+                .map(|(index, note)| {
+                    user.as_ref()
+                        .or(note.user.as_ref())
+                        .ok_or_else(|| format!("Note {} has no user; pass --user", index))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let db = db::connect_db().await?;
+            for owner in owners.iter().collect::<BTreeSet<_>>() {
+                require_user(&db, owner).await?;
+            }
+
+            // All or nothing, so a bad record does not leave a half-imported file
+            let mut tx = db.begin().await?;
+            for (index, note) in notes.iter().enumerate() {
+                let owner = owners[index];
+
+                let note_id = sqlx::query(
+                    "INSERT INTO notes (title, content, user, is_public, created_at, updated_at)
+                     VALUES (?, ?, ?, ?, COALESCE(?, NOW()), COALESCE(?, ?, NOW()))",
+                )
+                .bind(&note.title)
+                .bind(&note.content)
+                .bind(owner)
+                .bind(note.is_public)
+                .bind(note.created_at)
+                .bind(note.updated_at)
+                .bind(note.created_at)
+                .execute(&mut *tx)
+                .await?
+                .last_insert_id();
+
+                if !note_tags[index].is_empty() {
+                    tags::set_note_tags(&mut tx, owner, note_id as i64, &note_tags[index]).await?;
+                }
+            }
+            tx.commit().await?;
+            println!("Imported {} notes", notes.len());
+        }
+    }
+    Ok(())
+}
+
+async fn run(cli: Cli) -> CliResult {
+    match cli.command {
+        Command::User(command) => run_user(command).await,
+        Command::HashPassword => {
+            let password = prompt_new_password()?;
+            let hash = hash_password(&password)?;
+            println!("{}", hash);
+            eprintln!("Add this to your .env file as ADMIN_PASS_HASH");
+            Ok(())
+        }
+        Command::VerifyHash { hash } => {
+            if !is_valid_password_hash(&hash) {
+                return Err("Not an Argon2id or bcrypt hash".into());
+            }
+            if verify_password(&hash, &prompt_password("Password: ")?) {
+                println!("Password matches");
+                Ok(())
+            } else {
+                Err("Password does not match".into())
+            }
+        }
+        Command::Token(command) => run_token(command).await,
+        Command::Migrate { yes, sample_data } => run_migrate(yes, sample_data).await,
+        Command::Notes(command) => run_notes(command).await,
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    dotenv::dotenv().ok();
+    let cli = Cli::parse();
+
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            let _ = io::stdout().flush();
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_sql_statements_skip_comments() {
+        let script =
+            "-- drop it\nDROP TABLE IF EXISTS a;\n\nCREATE TABLE a (\n    id INT -- key\n);\n";
+        assert_eq!(
+            sql_statements(script),
+            vec![
+                "DROP TABLE IF EXISTS a",
+                "CREATE TABLE a (\n    id INT -- key\n)"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_migration_statements() {
+        let schema = migration_statements(false);
+        assert!(schema.iter().any(|s| s.starts_with("CREATE TABLE users")));
+        assert!(!schema.iter().any(|s| s.starts_with("INSERT")));
+        assert!(!schema.iter().any(|s| s.starts_with("DESCRIBE")));
+
+        let with_sample = migration_statements(true);
+        assert!(
+            with_sample
+                .iter()
+                .any(|s| s.starts_with("INSERT INTO notes"))
+        );
+    }
+}
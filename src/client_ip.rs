@@ -0,0 +1,176 @@
+use crate::{logging, state::AppState};
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{HeaderMap, StatusCode, request::Parts},
+};
+use std::{
+    env, fmt,
+    net::{IpAddr, SocketAddr},
+};
+
+/// Reverse proxies whose forwarding headers are believed, from TRUSTED_PROXIES
+#[derive(Clone, Debug, Default)]
+pub struct TrustedProxies(Vec<IpAddr>);
+
+impl TrustedProxies {
+    /// Parse TRUSTED_PROXIES, a comma-separated list of proxy IP addresses.
+    /// Unset or empty means no proxy is trusted and only the socket address counts.
+    pub fn from_env() -> Result<Self, String> {
+        let Ok(value) = env::var("TRUSTED_PROXIES") else {
+            return Ok(Self::default());
+        };
+
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                entry
+                    .parse()
+                    .map_err(|_| format!("'{}' is not an IP address", entry))
+            })
+            .collect::<Result<_, _>>()
+            .map(TrustedProxies)
+    }
+
+    fn contains(&self, ip: &IpAddr) -> bool {
+        self.0.contains(ip)
+    }
+
+    /// Address of the client behind `peer`.
+    ///
+    /// Forwarding headers are only read when `peer` is a trusted proxy. X-Forwarded-For
+    /// is walked from the right, skipping further trusted proxies, so entries the
+    /// client prepended itself are never used.
+    pub fn resolve(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        if !self.contains(&peer) {
+            return peer;
+        }
+
+        let forwarded: Vec<&str> = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .collect();
+        if !forwarded.is_empty() {
+            let mut client = peer;
+            for hop in forwarded.iter().rev() {
+                match hop.parse::<IpAddr>() {
+                    Ok(ip) => {
+                        client = ip;
+                        if !self.contains(&ip) {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+            return client;
+        }
+
+        headers
+            .get("x-real-ip")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+            .unwrap_or(peer)
+    }
+}
+
+/// Address of the client that sent a request: the TCP peer, or the address a
+/// trusted proxy reports for it.
+///
+/// Requires the server to run with
+/// `into_make_service_with_connect_info::<SocketAddr>()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+impl fmt::Display for ClientIp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromRequestParts<AppState> for ClientIp {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let Some(ConnectInfo(peer)) = parts.extensions.get::<ConnectInfo<SocketAddr>>() else {
+            logging::log_config_error("connect_info", "server is not serving connect info");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        };
+
+        Ok(ClientIp(
+            state.trusted_proxies.resolve(peer.ip(), &parts.headers),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_forwarding_headers_ignored_from_untrusted_peer() {
+        let proxies = TrustedProxies(vec![ip("10.0.0.1")]);
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "203.0.113.7".parse().unwrap());
+        headers.insert("x-real-ip", "203.0.113.8".parse().unwrap());
+
+        assert_eq!(
+            proxies.resolve(ip("198.51.100.2"), &headers),
+            ip("198.51.100.2")
+        );
+        assert_eq!(
+            TrustedProxies::default().resolve(ip("10.0.0.1"), &headers),
+            ip("10.0.0.1")
+        );
+    }
+
+    #[test]
+    fn test_forwarded_for_walked_from_the_right() {
+        let proxies = TrustedProxies(vec![ip("10.0.0.1"), ip("10.0.0.2")]);
+        let mut headers = HeaderMap::new();
+        // The client forged the first entry; 10.0.0.2 forwarded to 10.0.0.1
+        headers.insert(
+            "x-forwarded-for",
+            "1.2.3.4, 203.0.113.7, 10.0.0.2".parse().unwrap(),
+        );
+        assert_eq!(proxies.resolve(ip("10.0.0.1"), &headers), ip("203.0.113.7"));
+
+        headers.insert("x-forwarded-for", "garbage".parse().unwrap());
+        assert_eq!(proxies.resolve(ip("10.0.0.1"), &headers), ip("10.0.0.1"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-real-ip", "2001:db8::1".parse().unwrap());
+        assert_eq!(proxies.resolve(ip("10.0.0.1"), &headers), ip("2001:db8::1"));
+    }
+
+    #[test]
+    fn test_trusted_proxies_from_env() {
+        unsafe {
+            env::set_var("TRUSTED_PROXIES", "10.0.0.1, ::1");
+        }
+        let proxies = TrustedProxies::from_env().unwrap();
+        assert!(proxies.contains(&ip("::1")));
+        assert!(proxies.contains(&ip("10.0.0.1")));
+
+        unsafe {
+            env::set_var("TRUSTED_PROXIES", "10.0.0.0/8");
+        }
+        assert!(TrustedProxies::from_env().is_err());
+
+        unsafe {
+            env::remove_var("TRUSTED_PROXIES");
+        }
+        assert!(TrustedProxies::from_env().unwrap().0.is_empty());
+    }
+}
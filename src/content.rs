@@ -1,64 +1,151 @@
 use crate::{
+    etag,
+    extractors::AuthUser,
     logging,
+    markdown::FormatParams,
     models::{CreateNoteRequest, Note, UpdateNoteRequest},
+    notebooks,
+    pagination::{self, Cursor, ListParams, Page},
+    revisions,
     state::AppState,
-    utils::extract_user_from_token,
+    tags, trash,
 };
 use axum::{
-    extract::{Json, Path, State},
-    http::{HeaderMap, StatusCode},
+    extract::{Json, Path, Query, State},
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode, header},
     response::Json as ResponseJson,
 };
+use serde::Deserialize;
 use serde_json::json;
+use sqlx::{MySql, MySqlPool, QueryBuilder};
 // use uuid::Uuid; // Unused import removed
 
 // Public endpoints (no authentication required)
 
-/// Get all public notes
-pub async fn get_public_contents(
-    State(state): State<AppState>,
-) -> Result<ResponseJson<Vec<Note>>, StatusCode> {
-    logging::log_api_request("GET", "/contents", 200);
-
-    match sqlx::query_as::<_, Note>(
-        "SELECT id, title, content, user, is_public, created_at, updated_at
-         FROM notes
-         WHERE is_public = true
-         ORDER BY created_at DESC",
-    )
-    .fetch_all(&*state.db)
-    .await
-    {
-        Ok(notes) => {
-            logging::log_db_operation("select", "public_notes");
-            Ok(ResponseJson(notes))
+/// Run a paginated note listing. `scope` appends the condition deciding which
+/// notes the caller may see; the client's filters and cursor are added after it.
+async fn list_notes(
+    db: &MySqlPool,
+    params: &ListParams,
+    scope: impl Fn(&mut QueryBuilder<'_, MySql>),
+) -> Result<Page<Note>, StatusCode> {
+    let cursor = match &params.cursor {
+        Some(cursor) => {
+            Some(Cursor::decode(cursor, params.sort, params.order).ok_or(StatusCode::BAD_REQUEST)?)
         }
-        Err(e) => {
+        None => None,
+    };
+
+    let mut count = QueryBuilder::new("SELECT COUNT(*) FROM notes WHERE deleted_at IS NULL AND ");
+    scope(&mut count);
+    pagination::push_filters(&mut count, params);
+
+    let mut select = QueryBuilder::new(
+        "SELECT id, title, content, user, is_public, notebook_id, version, created_at, updated_at \
+         FROM notes WHERE deleted_at IS NULL AND ",
+    );
+    scope(&mut select);
+    pagination::push_filters(&mut select, params);
+    pagination::push_page(&mut select, params, cursor);
+
+    let total_count: i64 = count
+        .build_query_scalar()
+        .fetch_one(db)
+        .await
+        .map_err(|e| {
             logging::log_db_error("select", &e.to_string());
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let rows = select
+        .build_query_as::<Note>()
+        .fetch_all(db)
+        .await
+        .map_err(|e| {
+            logging::log_db_error("select", &e.to_string());
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let mut page = Page::from_rows(rows, params, total_count);
+    with_tags(db, &mut page.items).await?;
+    Ok(page)
+}
+
+/// A single note with its `ETag`
+type NoteResponse = ([(HeaderName, HeaderValue); 1], ResponseJson<Note>);
+
+/// Load the tags of notes about to be returned
+async fn with_tags(db: &MySqlPool, notes: &mut [Note]) -> Result<(), StatusCode> {
+    tags::attach(db, notes).await.map_err(|e| {
+        logging::log_db_error("select", &e.to_string());
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+/// Clear `notebook_id` on notes not owned by `viewer` (`None` for anonymous callers),
+/// since notebooks are private to their owner
+fn hide_notebooks(notes: &mut [Note], viewer: Option<&str>) {
+    for note in notes {
+        if viewer != Some(note.user.as_str()) {
+            note.notebook_id = None;
         }
     }
 }
 
+/// Get a page of public notes
+pub async fn get_public_contents(
+    State(state): State<AppState>,
+    Query(params): Query<ListParams>,
+    Query(format): Query<FormatParams>,
+) -> Result<ResponseJson<Page<Note>>, StatusCode> {
+    // Notebooks are private: filtering public notes by them would reveal how
+    // other users file their notes
+    if params.notebook_id.is_some() || params.include_descendants {
+        logging::log_api_request("GET", "/contents", 400);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    logging::log_api_request("GET", "/contents", 200);
+
+    let mut page = list_notes(&state.db, &params, |query| {
+        query.push("is_public = true");
+    })
+    .await?;
+    hide_notebooks(&mut page.items, None);
+    state.render_cache.apply(format.format, &mut page.items);
+
+    logging::log_db_operation("select", "public_notes");
+    Ok(ResponseJson(page))
+}
+
 /// Get a specific public note by ID
 pub async fn get_content_by_id(
     State(state): State<AppState>,
     Path(id): Path<i64>,
-) -> Result<ResponseJson<Note>, StatusCode> {
+    Query(format): Query<FormatParams>,
+) -> Result<NoteResponse, StatusCode> {
     logging::log_api_request("GET", &format!("/contents/{}", id), 200);
 
     match sqlx::query_as::<_, Note>(
-        "SELECT id, title, content, user, is_public, created_at, updated_at
+        "SELECT id, title, content, user, is_public, notebook_id, version, created_at, updated_at
          FROM notes
-         WHERE id = ? AND is_public = true",
+         WHERE id = ? AND is_public = true AND deleted_at IS NULL",
     )
     .bind(id)
     .fetch_optional(&*state.db)
     .await
     {
-        Ok(Some(note)) => {
+        Ok(Some(mut note)) => {
+            with_tags(&state.db, std::slice::from_mut(&mut note)).await?;
+            hide_notebooks(std::slice::from_mut(&mut note), None);
+            state
+                .render_cache
+                .apply(format.format, std::slice::from_mut(&mut note));
             logging::log_db_operation("select", "public_note");
-            Ok(ResponseJson(note))
+            Ok((
+                [(header::ETAG, etag::for_version(note.version))],
+                ResponseJson(note),
+            ))
         }
         Ok(None) => {
             logging::log_api_request("GET", &format!("/contents/{}", id), 404);
@@ -73,67 +160,82 @@ pub async fn get_content_by_id(
 
 // Admin/User endpoints (authentication required)
 
-/// Get all notes (both public and private) for authenticated user
+/// Get a page of the authenticated user's notes (both public and private)
 pub async fn get_all_contents(
     State(state): State<AppState>,
-    headers: HeaderMap,
-) -> Result<ResponseJson<Vec<Note>>, StatusCode> {
-    let username: String = match extract_user_from_token(&headers) {
-        Ok(user) => user,
-        Err(_) => return Err(StatusCode::UNAUTHORIZED),
-    };
+    auth: AuthUser,
+    Query(params): Query<ListParams>,
+    Query(format): Query<FormatParams>,
+) -> Result<ResponseJson<Page<Note>>, StatusCode> {
+    let username = auth.username;
 
     logging::log_api_request("GET", "/admin/contents", 200);
 
-    match sqlx::query_as::<_, Note>(
-        "SELECT id, title, content, user, is_public, created_at, updated_at
-         FROM notes
-         WHERE user = ?
-         ORDER BY created_at DESC",
-    )
-    .bind(&username)
-    .fetch_all(&*state.db)
+    let mut page = list_notes(&state.db, &params, |query| {
+        query.push("user = ").push_bind(username.clone());
+        pagination::push_notebook_filter(query, &params);
+    })
     .await
-    {
-        Ok(notes) => {
-            logging::log_db_operation("select", "user_notes");
-            logging::log_note_operation("retrieve", &username);
-            Ok(ResponseJson(notes))
-        }
-        Err(e) => {
-            logging::log_db_error("select", &e.to_string());
-            logging::log_note_error("retrieve", &username, &e.to_string());
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
-    }
+    .inspect_err(|_| logging::log_note_error("retrieve", &username, "listing failed"))?;
+    state.render_cache.apply(format.format, &mut page.items);
+
+    logging::log_db_operation("select", "user_notes");
+    logging::log_note_operation("retrieve", &username);
+    Ok(ResponseJson(page))
 }
 
 /// Create a new note
 pub async fn create_content(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    auth: AuthUser,
     Json(request): Json<CreateNoteRequest>,
 ) -> Result<(StatusCode, ResponseJson<serde_json::Value>), StatusCode> {
-    let username: String = match extract_user_from_token(&headers) {
-        Ok(user) => user,
-        Err(_) => return Err(StatusCode::UNAUTHORIZED),
+    let username = auth.username;
+
+    let note_tags = match tags::normalize(&request.tags) {
+        Ok(note_tags) => note_tags,
+        Err(message) => {
+            return Ok((
+                StatusCode::BAD_REQUEST,
+                ResponseJson(json!({ "error": message })),
+            ));
+        }
     };
 
     logging::log_note_operation("create", &username);
 
-    match sqlx::query(
-        "INSERT INTO notes (title, content, user, is_public, created_at, updated_at)
-         VALUES (?, ?, ?, ?, NOW(), NOW())",
-    )
-    .bind(&request.title)
-    .bind(&request.content)
-    .bind(&username)
-    .bind(request.is_public)
-    .execute(&*state.db)
-    .await
-    {
-        Ok(result) => {
-            let note_id = result.last_insert_id();
+    // The note and its tags are written together so a failure leaves neither behind
+    let result = async {
+        let mut tx = state.db.begin().await?;
+        if let Some(notebook_id) = request.notebook_id
+            && !notebooks::is_owned(&mut tx, &username, notebook_id).await?
+        {
+            return Ok(None);
+        }
+
+        let note_id = sqlx::query(
+            "INSERT INTO notes (title, content, user, is_public, notebook_id, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, NOW(), NOW())",
+        )
+        .bind(&request.title)
+        .bind(&request.content)
+        .bind(&username)
+        .bind(request.is_public)
+        .bind(request.notebook_id)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_id();
+
+        if !note_tags.is_empty() {
+            tags::set_note_tags(&mut tx, &username, note_id as i64, &note_tags).await?;
+        }
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(Some(note_id))
+    }
+    .await;
+
+    match result {
+        Ok(Some(note_id)) => {
             logging::log_db_operation("insert", "notes");
 
             let response = json!({
@@ -143,6 +245,10 @@ pub async fn create_content(
 
             Ok((StatusCode::CREATED, ResponseJson(response)))
         }
+        Ok(None) => Ok((
+            StatusCode::BAD_REQUEST,
+            ResponseJson(json!({"error": "Notebook not found"})),
+        )),
         Err(e) => {
             logging::log_db_error("insert", &e.to_string());
             logging::log_note_error("create", &username, &e.to_string());
@@ -154,29 +260,35 @@ pub async fn create_content(
 /// Get a specific note by ID (user can access their own notes, any user can access public notes)
 pub async fn get_content_by_id_admin(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    auth: AuthUser,
     Path(id): Path<i64>,
-) -> Result<ResponseJson<Note>, StatusCode> {
-    let username = match extract_user_from_token(&headers) {
-        Ok(user) => user,
-        Err(_) => return Err(StatusCode::UNAUTHORIZED),
-    };
+    Query(format): Query<FormatParams>,
+) -> Result<NoteResponse, StatusCode> {
+    let username = auth.username;
 
     logging::log_api_request("GET", &format!("/admin/contents/{}", id), 200);
 
     match sqlx::query_as::<_, Note>(
-        "SELECT id, title, content, user, is_public, created_at, updated_at
+        "SELECT id, title, content, user, is_public, notebook_id, version, created_at, updated_at
          FROM notes
-         WHERE id = ? AND (user = ? OR is_public = true)",
+         WHERE id = ? AND (user = ? OR is_public = true) AND deleted_at IS NULL",
     )
     .bind(id)
     .bind(&username)
     .fetch_optional(&*state.db)
     .await
     {
-        Ok(Some(note)) => {
+        Ok(Some(mut note)) => {
+            with_tags(&state.db, std::slice::from_mut(&mut note)).await?;
+            hide_notebooks(std::slice::from_mut(&mut note), Some(&username));
+            state
+                .render_cache
+                .apply(format.format, std::slice::from_mut(&mut note));
             logging::log_db_operation("select", "note");
-            Ok(ResponseJson(note))
+            Ok((
+                [(header::ETAG, etag::for_version(note.version))],
+                ResponseJson(note),
+            ))
         }
         Ok(None) => {
             logging::log_api_request("GET", &format!("/admin/contents/{}", id), 404);
@@ -189,122 +301,280 @@ pub async fn get_content_by_id_admin(
     }
 }
 
-/// Update a note
+/// Update a note.
+///
+/// The note is read, then changed by one UPDATE conditioned on the version that was
+/// read, in a transaction with the revision archiving what it replaced. If another
+/// edit got in first, or an `If-Match` header holds an older `ETag`, nothing changes
+/// and the response is 412, so a client editing a stale copy cannot overwrite
+/// someone else's change. Served for both PUT and PATCH.
 pub async fn update_content(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    auth: AuthUser,
     Path(id): Path<i64>,
+    headers: HeaderMap,
     Json(request): Json<UpdateNoteRequest>,
-) -> Result<(StatusCode, ResponseJson<serde_json::Value>), StatusCode> {
-    let username: String = match extract_user_from_token(&headers) {
-        Ok(user) => user,
-        Err(_) => return Err(StatusCode::UNAUTHORIZED),
-    };
-
-    // Check if note exists and belongs to user
-    let note_exists = sqlx::query("SELECT id FROM notes WHERE id = ? AND user = ?")
-        .bind(id)
-        .bind(&username)
-        .fetch_optional(&*state.db)
-        .await
-        .map_err(|e| {
-            logging::log_db_error("select", &e.to_string());
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-
-    if note_exists.is_none() {
-        logging::log_api_request("PUT", &format!("/admin/contents/{}", id), 404);
-        return Err(StatusCode::NOT_FOUND);
-    }
-
-    logging::log_note_operation("update", &username);
+) -> Result<(StatusCode, HeaderMap, ResponseJson<serde_json::Value>), StatusCode> {
+    let username = auth.username;
 
     // Check if any fields to update
-    if request.title.is_none() && request.content.is_none() && request.is_public.is_none() {
+    if request.title.is_none()
+        && request.content.is_none()
+        && request.is_public.is_none()
+        && request.tags.is_none()
+    {
         return Ok((
             StatusCode::BAD_REQUEST,
+            HeaderMap::new(),
             ResponseJson(json!({"error": "No fields to update"})),
         ));
     }
 
-    // Update title if provided
-    if let Some(title) = request.title {
-        sqlx::query("UPDATE notes SET title = ?, updated_at = NOW() WHERE id = ? AND user = ?")
-            .bind(&title)
-            .bind(id)
-            .bind(&username)
-            .execute(&*state.db)
-            .await
-            .map_err(|e| {
-                logging::log_db_error("update", &e.to_string());
-                logging::log_note_error("update", &username, &e.to_string());
-                StatusCode::INTERNAL_SERVER_ERROR
-            })?;
+    let note_tags = match request.tags.as_deref().map(tags::normalize).transpose() {
+        Ok(note_tags) => note_tags,
+        Err(message) => {
+            return Ok((
+                StatusCode::BAD_REQUEST,
+                HeaderMap::new(),
+                ResponseJson(json!({ "error": message })),
+            ));
+        }
+    };
+
+    logging::log_note_operation("update", &username);
+
+    let result = apply_update(
+        &state.db,
+        id,
+        &headers,
+        &request,
+        note_tags
+            .as_deref()
+            .map(|note_tags| (username.as_str(), note_tags)),
+        |query| {
+            query.push("user = ").push_bind(username.clone());
+        },
+    )
+    .await;
+
+    match result {
+        Ok(UpdateOutcome::Updated { version, .. }) => {
+            logging::log_db_operation("update", "notes");
+            let mut headers = HeaderMap::new();
+            headers.insert(header::ETAG, etag::for_version(version));
+            Ok((
+                StatusCode::OK,
+                headers,
+                ResponseJson(json!({
+                    "message": "Note updated successfully",
+                    "version": version
+                })),
+            ))
+        }
+        Ok(UpdateOutcome::Conflict(version)) => {
+            logging::log_api_request("PUT", &format!("/admin/contents/{}", id), 412);
+            Ok(precondition_failed(version))
+        }
+        Ok(UpdateOutcome::NotFound) => {
+            logging::log_api_request("PUT", &format!("/admin/contents/{}", id), 404);
+            Err(StatusCode::NOT_FOUND)
+        }
+        Err(e) => {
+            logging::log_db_error("update", &e.to_string());
+            logging::log_note_error("update", &username, &e.to_string());
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
     }
+}
 
-    // Update content if provided
-    if let Some(content) = request.content {
-        sqlx::query("UPDATE notes SET content = ?, updated_at = NOW() WHERE id = ? AND user = ?")
-            .bind(&content)
-            .bind(id)
-            .bind(&username)
-            .execute(&*state.db)
-            .await
-            .map_err(|e| {
-                logging::log_db_error("update", &e.to_string());
-                logging::log_note_error("update", &username, &e.to_string());
-                StatusCode::INTERNAL_SERVER_ERROR
-            })?;
+pub(crate) enum UpdateOutcome {
+    /// Applied; `archived` is the revision the replaced content was saved as, if any
+    Updated {
+        version: i32,
+        archived: Option<i32>,
+    },
+    /// `If-Match` did not match or another edit won; holds the current version
+    Conflict(i32),
+    NotFound,
+}
+
+/// 412 response carrying the note's current version
+pub(crate) fn precondition_failed(
+    version: i32,
+) -> (StatusCode, HeaderMap, ResponseJson<serde_json::Value>) {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::ETAG, etag::for_version(version));
+    (
+        StatusCode::PRECONDITION_FAILED,
+        headers,
+        ResponseJson(json!({
+            "error": "The note was changed since it was loaded",
+            "version": version
+        })),
+    )
+}
+
+/// Apply `request` to note `id` with optimistic concurrency. `scope` appends the
+/// condition deciding which notes the caller may change; `note_tags` holds the
+/// owner and the normalized tags to set, when the request replaces them.
+pub(crate) async fn apply_update(
+    db: &MySqlPool,
+    id: i64,
+    headers: &HeaderMap,
+    request: &UpdateNoteRequest,
+    note_tags: Option<(&str, &[String])>,
+    scope: impl Fn(&mut QueryBuilder<'_, MySql>),
+) -> Result<UpdateOutcome, sqlx::Error> {
+    let mut select = QueryBuilder::new(
+        "SELECT version, title, content, updated_at FROM notes WHERE deleted_at IS NULL AND id = ",
+    );
+    select.push_bind(id).push(" AND ");
+    scope(&mut select);
+    let Some(current) = select
+        .build_query_as::<revisions::Snapshot>()
+        .fetch_optional(db)
+        .await?
+    else {
+        return Ok(UpdateOutcome::NotFound);
+    };
+    if !etag::if_match_allows(headers, current.version) {
+        return Ok(UpdateOutcome::Conflict(current.version));
     }
 
-    // Update is_public if provided
-    if let Some(is_public) = request.is_public {
-        sqlx::query("UPDATE notes SET is_public = ?, updated_at = NOW() WHERE id = ? AND user = ?")
-            .bind(is_public)
-            .bind(id)
-            .bind(&username)
-            .execute(&*state.db)
-            .await
-            .map_err(|e| {
-                logging::log_db_error("update", &e.to_string());
-                logging::log_note_error("update", &username, &e.to_string());
-                StatusCode::INTERNAL_SERVER_ERROR
-            })?;
+    let mut tx = db.begin().await?;
+    let mut update = QueryBuilder::new("UPDATE notes SET title = COALESCE(");
+    update
+        .push_bind(request.title.clone())
+        .push(", title), content = COALESCE(")
+        .push_bind(request.content.clone())
+        .push(", content), is_public = COALESCE(")
+        .push_bind(request.is_public)
+        .push(
+            ", is_public), version = version + 1, updated_at = NOW() \
+             WHERE deleted_at IS NULL AND id = ",
+        )
+        .push_bind(id)
+        .push(" AND version = ")
+        .push_bind(current.version)
+        .push(" AND ");
+    scope(&mut update);
+    let updated = update.build().execute(&mut *tx).await?.rows_affected();
+
+    if updated == 0 {
+        // Edited or deleted since it was read
+        drop(tx);
+        let mut select =
+            QueryBuilder::new("SELECT version FROM notes WHERE deleted_at IS NULL AND id = ");
+        select.push_bind(id).push(" AND ");
+        scope(&mut select);
+        let version: Option<i32> = select.build_query_scalar().fetch_optional(db).await?;
+        return Ok(version.map_or(UpdateOutcome::NotFound, UpdateOutcome::Conflict));
     }
 
-    logging::log_db_operation("update", "notes");
+    let archived = if request.title.is_some() || request.content.is_some() {
+        Some(revisions::archive(&mut tx, id, &current).await?)
+    } else {
+        None
+    };
+    if let Some((owner, note_tags)) = note_tags {
+        tags::set_note_tags(&mut tx, owner, id, note_tags).await?;
+    }
 
-    Ok((
-        StatusCode::OK,
-        ResponseJson(json!({"message": "Note updated successfully"})),
-    ))
+    tx.commit().await?;
+    Ok(UpdateOutcome::Updated {
+        version: current.version + 1,
+        archived,
+    })
 }
 
-/// Delete a note
-pub async fn delete_content(
+#[derive(Deserialize)]
+pub struct MoveNoteRequest {
+    /// Destination notebook; `null` leaves the note unfiled
+    pub notebook_id: Option<i64>,
+}
+
+/// Move a note into another notebook. The notebook's ownership is checked in the
+/// same transaction as the move.
+pub async fn move_content(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    auth: AuthUser,
     Path(id): Path<i64>,
+    Json(request): Json<MoveNoteRequest>,
 ) -> Result<(StatusCode, ResponseJson<serde_json::Value>), StatusCode> {
-    let username: String = match extract_user_from_token(&headers) {
-        Ok(user) => user,
-        Err(_) => return Err(StatusCode::UNAUTHORIZED),
-    };
+    let username = auth.username;
 
-    logging::log_note_operation("delete", &username);
+    let result = async {
+        let mut tx = state.db.begin().await?;
+        if let Some(notebook_id) = request.notebook_id
+            && !notebooks::is_owned(&mut tx, &username, notebook_id).await?
+        {
+            return Ok(None);
+        }
 
-    match sqlx::query("DELETE FROM notes WHERE id = ? AND user = ?")
+        let moved = sqlx::query(
+            "UPDATE notes SET notebook_id = ?, version = version + 1, updated_at = NOW()
+             WHERE id = ? AND user = ? AND deleted_at IS NULL",
+        )
+        .bind(request.notebook_id)
         .bind(id)
         .bind(&username)
-        .execute(&*state.db)
-        .await
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(Some(moved))
+    }
+    .await
+    .map_err(|e| {
+        logging::log_db_error("update", &e.to_string());
+        logging::log_note_error("move", &username, &e.to_string());
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    match result {
+        Some(0) => {
+            logging::log_api_request("POST", &format!("/admin/contents/{}/move", id), 404);
+            Err(StatusCode::NOT_FOUND)
+        }
+        Some(_) => {
+            logging::log_db_operation("update", "notes");
+            Ok((
+                StatusCode::OK,
+                ResponseJson(json!({"message": "Note moved successfully"})),
+            ))
+        }
+        None => Ok((
+            StatusCode::BAD_REQUEST,
+            ResponseJson(json!({"error": "Notebook not found"})),
+        )),
+    }
+}
+
+/// Move a note to the trash (see `trash` for restoring or purging it)
+pub async fn delete_content(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<(StatusCode, ResponseJson<serde_json::Value>), StatusCode> {
+    let username = auth.username;
+
+    logging::log_note_operation("delete", &username);
+
+    match sqlx::query(
+        "UPDATE notes SET deleted_at = NOW(), updated_at = updated_at
+         WHERE id = ? AND user = ? AND deleted_at IS NULL",
+    )
+    .bind(id)
+    .bind(&username)
+    .execute(&*state.db)
+    .await
     {
         Ok(result) => {
             if result.rows_affected() > 0 {
-                logging::log_db_operation("delete", "notes");
+                logging::log_db_operation("trash", "notes");
                 Ok((
                     StatusCode::OK,
-                    ResponseJson(json!({"message": "Note deleted successfully"})),
+                    ResponseJson(json!({"message": "Note moved to trash"})),
                 ))
             } else {
                 logging::log_api_request("DELETE", &format!("/admin/contents/{}", id), 404);
@@ -322,20 +592,19 @@ pub async fn delete_content(
 /// Get statistics about notes
 pub async fn get_stats(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    auth: AuthUser,
 ) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
-    let username = match extract_user_from_token(&headers) {
-        Ok(user) => user,
-        Err(_) => return Err(StatusCode::UNAUTHORIZED),
-    };
+    let username = auth.username;
 
     logging::log_api_request("GET", "/admin/stats", 200);
 
     // Get total notes for user
-    let total_notes: i64 = match sqlx::query_scalar("SELECT COUNT(*) FROM notes WHERE user = ?")
-        .bind(&username)
-        .fetch_one(&*state.db)
-        .await
+    let total_notes: i64 = match sqlx::query_scalar(
+        "SELECT COUNT(*) FROM notes WHERE user = ? AND deleted_at IS NULL",
+    )
+    .bind(&username)
+    .fetch_one(&*state.db)
+    .await
     {
         Ok(count) => count,
         Err(e) => {
@@ -345,18 +614,19 @@ pub async fn get_stats(
     };
 
     // Get public notes count for user
-    let public_notes: i64 =
-        match sqlx::query_scalar("SELECT COUNT(*) FROM notes WHERE user = ? AND is_public = true")
-            .bind(&username)
-            .fetch_one(&*state.db)
-            .await
-        {
-            Ok(count) => count,
-            Err(e) => {
-                logging::log_db_error("select", &e.to_string());
-                return Err(StatusCode::INTERNAL_SERVER_ERROR);
-            }
-        };
+    let public_notes: i64 = match sqlx::query_scalar(
+        "SELECT COUNT(*) FROM notes WHERE user = ? AND is_public = true AND deleted_at IS NULL",
+    )
+    .bind(&username)
+    .fetch_one(&*state.db)
+    .await
+    {
+        Ok(count) => count,
+        Err(e) => {
+            logging::log_db_error("select", &e.to_string());
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
 
     // Get private notes count for user
     let private_notes = total_notes - public_notes;
@@ -371,6 +641,155 @@ pub async fn get_stats(
     Ok(ResponseJson(stats))
 }
 
+/// Update any user's public note (admin moderation). Honours `If-Match` like
+/// `update_content`.
+pub async fn moderate_content(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<i64>,
+    headers: HeaderMap,
+    Json(request): Json<UpdateNoteRequest>,
+) -> Result<(StatusCode, HeaderMap, ResponseJson<serde_json::Value>), StatusCode> {
+    if request.title.is_none() && request.content.is_none() && request.is_public.is_none() {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            HeaderMap::new(),
+            ResponseJson(json!({"error": "No fields to update"})),
+        ));
+    }
+
+    let result = apply_update(&state.db, id, &headers, &request, None, |query| {
+        query.push("is_public = true");
+    })
+    .await
+    .map_err(|e| {
+        logging::log_db_error("update", &e.to_string());
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let version = match result {
+        UpdateOutcome::Updated { version, .. } => version,
+        UpdateOutcome::Conflict(version) => {
+            logging::log_api_request("PUT", &format!("/admin/moderation/contents/{}", id), 412);
+            return Ok(precondition_failed(version));
+        }
+        UpdateOutcome::NotFound => {
+            logging::log_api_request("PUT", &format!("/admin/moderation/contents/{}", id), 404);
+            return Err(StatusCode::NOT_FOUND);
+        }
+    };
+
+    logging::log_security_event(
+        "note_moderated",
+        &format!("admin {} updated public note {}", auth.username, id),
+    );
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::ETAG, etag::for_version(version));
+    Ok((
+        StatusCode::OK,
+        headers,
+        ResponseJson(json!({
+            "message": "Note updated successfully",
+            "version": version
+        })),
+    ))
+}
+
+/// Delete any user's public note (admin moderation)
+pub async fn moderate_delete_content(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<(StatusCode, ResponseJson<serde_json::Value>), StatusCode> {
+    let result = async {
+        let mut tx = state.db.begin().await?;
+        let owner: Option<String> = sqlx::query_scalar(
+            "SELECT user FROM notes WHERE id = ? AND is_public = true AND deleted_at IS NULL FOR UPDATE",
+        )
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        // Moderation removes the note outright rather than leaving it in the owner's trash
+        if let Some(owner) = &owner {
+            trash::purge_note(&mut tx, owner, id).await?;
+        }
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(owner)
+    }
+    .await
+    .map_err(|e| {
+        logging::log_db_error("delete", &e.to_string());
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    if result.is_none() {
+        logging::log_api_request("DELETE", &format!("/admin/moderation/contents/{}", id), 404);
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    logging::log_security_event(
+        "note_moderated",
+        &format!("admin {} deleted public note {}", auth.username, id),
+    );
+
+    Ok((
+        StatusCode::OK,
+        ResponseJson(json!({"message": "Note deleted successfully"})),
+    ))
+}
+
+/// Get statistics across all users (admin only)
+pub async fn get_global_stats(
+    State(state): State<AppState>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    logging::log_api_request("GET", "/admin/moderation/stats", 200);
+
+    let (total_users, active_users): (i64, i64) =
+        sqlx::query_as("SELECT COUNT(*), CAST(COALESCE(SUM(is_active), 0) AS SIGNED) FROM users")
+            .fetch_one(&*state.db)
+            .await
+            .map_err(|e| {
+                logging::log_db_error("select", &e.to_string());
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+
+    let per_user: Vec<(String, i64, i64)> = sqlx::query_as(
+        "SELECT user, COUNT(*), CAST(SUM(is_public) AS SIGNED)
+         FROM notes
+         WHERE deleted_at IS NULL
+         GROUP BY user
+         ORDER BY COUNT(*) DESC, user",
+    )
+    .fetch_all(&*state.db)
+    .await
+    .map_err(|e| {
+        logging::log_db_error("select", &e.to_string());
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let total_notes: i64 = per_user.iter().map(|(_, total, _)| total).sum();
+    let public_notes: i64 = per_user.iter().map(|(_, _, public)| public).sum();
+
+    let stats = json!({
+        "total_users": total_users,
+        "active_users": active_users,
+        "total_notes": total_notes,
+        "public_notes": public_notes,
+        "private_notes": total_notes - public_notes,
+        "notes_by_user": per_user
+            .iter()
+            .map(|(user, total, public)| json!({
+                "user": user,
+                "total_notes": total,
+                "public_notes": public,
+            }))
+            .collect::<Vec<_>>(),
+    });
+
+    Ok(ResponseJson(stats))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -385,7 +804,7 @@ mod tests {
         let request: CreateNoteRequest = serde_json::from_str(json_data).unwrap();
         assert_eq!(request.title, "Test Note");
         assert_eq!(request.content, "This is test content");
-        assert_eq!(request.is_public, true);
+        assert!(request.is_public);
     }
 
     #[test]
@@ -398,5 +817,25 @@ mod tests {
         assert_eq!(request.title, Some("Updated Title".to_string()));
         assert_eq!(request.content, None);
         assert_eq!(request.is_public, Some(false));
+        assert_eq!(request.tags, None);
+    }
+
+    #[test]
+    fn test_note_request_tags() {
+        let create: CreateNoteRequest =
+            serde_json::from_str(r#"{"title": "t", "content": "c", "is_public": false}"#).unwrap();
+        assert!(create.tags.is_empty());
+
+        let update: UpdateNoteRequest = serde_json::from_str(r#"{"tags": []}"#).unwrap();
+        assert_eq!(update.tags, Some(Vec::new()));
+    }
+
+    #[test]
+    fn test_move_note_request_deserialization() {
+        let request: MoveNoteRequest = serde_json::from_str(r#"{"notebook_id": 7}"#).unwrap();
+        assert_eq!(request.notebook_id, Some(7));
+
+        let request: MoveNoteRequest = serde_json::from_str(r#"{"notebook_id": null}"#).unwrap();
+        assert_eq!(request.notebook_id, None);
     }
 }
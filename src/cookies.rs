@@ -0,0 +1,159 @@
+use axum::http::{HeaderMap, HeaderValue, Method, header};
+use std::env;
+
+/// HttpOnly cookie holding the access token in cookie session mode
+pub const SESSION_COOKIE: &str = "notepad_session";
+/// HttpOnly cookie holding the refresh token in cookie session mode
+pub const REFRESH_COOKIE: &str = "notepad_refresh";
+/// Script-readable cookie with the double-submit CSRF token
+pub const CSRF_COOKIE: &str = "notepad_csrf";
+/// Header that must echo the CSRF cookie on state-changing requests
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+/// Access token lifetime, matching the `exp` set by `utils::generate_token`
+const SESSION_MAX_AGE_SECS: i64 = 3600;
+
+/// Value of a cookie from the request's `Cookie` headers
+pub fn get<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+        .filter(|value| !value.is_empty())
+}
+
+/// Attributes shared by every session cookie.
+///
+/// COOKIE_SECURE=false drops `Secure` for plain-HTTP development; COOKIE_SAMESITE
+/// (Strict, Lax or None) defaults to Strict.
+fn attributes() -> String {
+    let same_site = match env::var("COOKIE_SAMESITE").as_deref() {
+        Ok("Lax") | Ok("lax") => "Lax",
+        Ok("None") | Ok("none") => "None",
+        _ => "Strict",
+    };
+    // Browsers reject SameSite=None without Secure
+    let secure = same_site == "None" || env::var("COOKIE_SECURE").map_or(true, |v| v != "false");
+
+    format!(
+        "Path=/; SameSite={}{}",
+        same_site,
+        if secure { "; Secure" } else { "" }
+    )
+}
+
+fn set_cookie(name: &str, value: &str, max_age: i64, http_only: bool) -> HeaderValue {
+    let cookie = format!(
+        "{}={}; Max-Age={}; {}{}",
+        name,
+        value,
+        max_age,
+        attributes(),
+        if http_only { "; HttpOnly" } else { "" }
+    );
+    HeaderValue::from_str(&cookie).expect("cookie values are URL-safe tokens")
+}
+
+/// `Set-Cookie` values that start a cookie session
+pub fn session_cookies(
+    access_token: &str,
+    refresh_token: &str,
+    csrf_token: &str,
+) -> [HeaderValue; 3] {
+    let refresh_max_age = crate::refresh_tokens::ttl_days() * 24 * 3600;
+    [
+        set_cookie(SESSION_COOKIE, access_token, SESSION_MAX_AGE_SECS, true),
+        set_cookie(REFRESH_COOKIE, refresh_token, refresh_max_age, true),
+        set_cookie(CSRF_COOKIE, csrf_token, refresh_max_age, false),
+    ]
+}
+
+/// `Set-Cookie` values that end a cookie session
+pub fn clear_session_cookies() -> [HeaderValue; 3] {
+    [
+        set_cookie(SESSION_COOKIE, "", 0, true),
+        set_cookie(REFRESH_COOKIE, "", 0, true),
+        set_cookie(CSRF_COOKIE, "", 0, false),
+    ]
+}
+
+/// Methods that must not change state and so skip the CSRF check
+pub fn is_safe_method(method: &Method) -> bool {
+    method == Method::GET || method == Method::HEAD || method == Method::OPTIONS
+}
+
+/// Double-submit check: the CSRF header must match the CSRF cookie
+pub fn verify_csrf(headers: &HeaderMap) -> bool {
+    let (Some(cookie), Some(header)) = (
+        get(headers, CSRF_COOKIE),
+        headers.get(CSRF_HEADER).and_then(|v| v.to_str().ok()),
+    ) else {
+        return false;
+    };
+
+    // Compare without an early exit so timing does not reveal the matching prefix
+    cookie.len() == header.len()
+        && cookie
+            .bytes()
+            .zip(header.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(
+                header::HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                HeaderValue::from_str(value).unwrap(),
+            );
+        }
+        headers
+    }
+
+    #[test]
+    fn test_get_cookie() {
+        let headers = headers(&[("cookie", "a=1; notepad_session=abc.def; b=2")]);
+        assert_eq!(get(&headers, SESSION_COOKIE), Some("abc.def"));
+        assert_eq!(get(&headers, REFRESH_COOKIE), None);
+    }
+
+    #[test]
+    fn test_verify_csrf() {
+        let ok = headers(&[
+            ("cookie", "notepad_csrf=tok123"),
+            ("x-csrf-token", "tok123"),
+        ]);
+        assert!(verify_csrf(&ok));
+
+        let mismatch = headers(&[
+            ("cookie", "notepad_csrf=tok123"),
+            ("x-csrf-token", "tok124"),
+        ]);
+        assert!(!verify_csrf(&mismatch));
+
+        let missing_header = headers(&[("cookie", "notepad_csrf=tok123")]);
+        assert!(!verify_csrf(&missing_header));
+
+        let empty = headers(&[("cookie", "notepad_csrf="), ("x-csrf-token", "")]);
+        assert!(!verify_csrf(&empty));
+    }
+
+    #[test]
+    fn test_session_cookie_attributes() {
+        let [session, refresh, csrf] = session_cookies("jwt", "refresh", "csrf");
+        let session = session.to_str().unwrap();
+        assert!(session.starts_with("notepad_session=jwt; Max-Age=3600; Path=/; SameSite="));
+        assert!(session.ends_with("; HttpOnly"));
+        assert!(refresh.to_str().unwrap().contains("HttpOnly"));
+        assert!(!csrf.to_str().unwrap().contains("HttpOnly"));
+    }
+}
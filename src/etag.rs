@@ -0,0 +1,52 @@
+use axum::http::{HeaderMap, HeaderValue, header};
+
+/// Strong entity tag for a note at `version`
+pub fn for_version(version: i32) -> HeaderValue {
+    HeaderValue::from_str(&format!("\"{}\"", version)).expect("digits are a valid header value")
+}
+
+/// Whether the request's `If-Match` header permits changing a note at `version`.
+///
+/// Requests without `If-Match` always pass. `*` matches any existing note. Weak
+/// tags never match, since `If-Match` uses strong comparison (RFC 9110).
+pub fn if_match_allows(headers: &HeaderMap, version: i32) -> bool {
+    let mut values = headers.get_all(header::IF_MATCH).iter().peekable();
+    if values.peek().is_none() {
+        return true;
+    }
+
+    let current = format!("\"{}\"", version);
+    values
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag == current)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn if_match(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_MATCH, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_for_version() {
+        assert_eq!(for_version(7), "\"7\"");
+    }
+
+    #[test]
+    fn test_if_match_allows() {
+        assert!(if_match_allows(&HeaderMap::new(), 3));
+        assert!(if_match_allows(&if_match("\"3\""), 3));
+        assert!(if_match_allows(&if_match("\"1\", \"3\""), 3));
+        assert!(if_match_allows(&if_match("*"), 3));
+
+        assert!(!if_match_allows(&if_match("\"2\""), 3));
+        assert!(!if_match_allows(&if_match("W/\"3\""), 3));
+        assert!(!if_match_allows(&if_match("3"), 3));
+    }
+}
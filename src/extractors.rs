@@ -0,0 +1,448 @@
+use crate::{
+    access_tokens::{self, Scope},
+    client_ip::ClientIp,
+    cookies, logging,
+    rbac::{self, Role},
+    sessions,
+    state::AppState,
+    utils::{bearer_token, decode_token},
+};
+use axum::{
+    Json,
+    extract::{FromRequestParts, Request, State},
+    http::{HeaderValue, Method, StatusCode, header, request::Parts},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+
+/// How the request was authenticated
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenKind {
+    /// JWT issued by an interactive login
+    Session,
+    /// Personal access token created for scripts and CI
+    PersonalAccessToken,
+}
+
+/// Metadata about the credential that authenticated the request
+#[derive(Clone, Debug)]
+pub struct TokenInfo {
+    pub kind: TokenKind,
+    /// JWT ID for sessions, database ID for personal access tokens
+    pub id: String,
+    /// Login session the JWT belongs to (see `sessions`); `None` for personal access tokens
+    pub session_id: Option<String>,
+    pub scopes: Vec<Scope>,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// The authenticated principal for a request
+#[derive(Clone, Debug)]
+pub struct AuthUser {
+    pub username: String,
+    pub roles: Vec<String>,
+    pub token: TokenInfo,
+}
+
+impl AuthUser {
+    /// Effective role: the most privileged of the roles granted to the account
+    pub fn role(&self) -> Role {
+        Role::highest(&self.roles)
+    }
+
+    /// Reject accounts whose role is below `role`
+    pub fn require_role(&self, role: Role) -> Result<(), AuthError> {
+        if self.role() >= role {
+            return Ok(());
+        }
+
+        logging::log_security_event(
+            "role_denied",
+            &format!(
+                "user {} with role {} needs {}",
+                self.username,
+                self.role().as_str(),
+                role.as_str()
+            ),
+        );
+        Err(AuthError::forbidden(
+            "insufficient_role",
+            &format!("This endpoint requires the {} role", role.as_str()),
+        ))
+    }
+
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.token.scopes.contains(&scope)
+    }
+
+    /// Reject credentials that lack a scope
+    pub fn require_scope(&self, scope: Scope) -> Result<(), AuthError> {
+        if self.has_scope(scope) {
+            return Ok(());
+        }
+
+        logging::log_security_event(
+            "token_scope_denied",
+            &format!(
+                "user {} token {} lacks {}",
+                self.username,
+                self.token.id,
+                scope.as_str()
+            ),
+        );
+        Err(AuthError::insufficient_scope(scope))
+    }
+
+    /// Reject personal access tokens for account-management routes
+    pub fn require_session(&self) -> Result<(), AuthError> {
+        match self.token.kind {
+            TokenKind::Session => Ok(()),
+            TokenKind::PersonalAccessToken => Err(AuthError::forbidden(
+                "session_required",
+                "This endpoint requires an interactive login",
+            )),
+        }
+    }
+}
+
+/// Authentication or authorization failure, rendered as JSON with a
+/// `WWW-Authenticate` challenge (RFC 6750)
+#[derive(Debug)]
+pub struct AuthError {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+    challenge: String,
+}
+
+impl AuthError {
+    pub fn unauthorized(message: &str) -> Self {
+        AuthError {
+            status: StatusCode::UNAUTHORIZED,
+            code: "unauthorized",
+            message: message.to_string(),
+            challenge: format!(
+                "Bearer realm=\"notepad\", error=\"invalid_token\", error_description=\"{}\"",
+                message
+            ),
+        }
+    }
+
+    pub fn insufficient_scope(scope: Scope) -> Self {
+        AuthError {
+            status: StatusCode::FORBIDDEN,
+            code: "insufficient_scope",
+            message: format!("Token is missing the '{}' scope", scope.as_str()),
+            challenge: format!(
+                "Bearer realm=\"notepad\", error=\"insufficient_scope\", scope=\"{}\"",
+                scope.as_str()
+            ),
+        }
+    }
+
+    pub fn forbidden(code: &'static str, message: &str) -> Self {
+        AuthError {
+            status: StatusCode::FORBIDDEN,
+            code,
+            message: message.to_string(),
+            challenge: "Bearer realm=\"notepad\", error=\"insufficient_scope\"".to_string(),
+        }
+    }
+
+    fn internal() -> Self {
+        AuthError {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            code: "internal_error",
+            message: "Internal server error".to_string(),
+            challenge: String::new(),
+        }
+    }
+}
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        let mut response = (
+            self.status,
+            Json(json!({ "error": self.code, "message": self.message })),
+        )
+            .into_response();
+
+        if !self.challenge.is_empty()
+            && let Ok(value) = HeaderValue::from_str(&self.challenge)
+        {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, value);
+        }
+
+        response
+    }
+}
+
+impl FromRequestParts<AppState> for AuthUser {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        // Already authenticated by the `require_auth` layer
+        if let Some(user) = parts.extensions.get::<AuthUser>() {
+            return Ok(user.clone());
+        }
+
+        // Bearer header first; browsers in cookie session mode send the HttpOnly cookie instead
+        let token = match bearer_token(&parts.headers) {
+            Ok(token) => token,
+            Err(e) => {
+                let token = cookies::get(&parts.headers, cookies::SESSION_COOKIE)
+                    .ok_or_else(|| AuthError::unauthorized(&e.to_string()))?;
+
+                // Cookies are sent automatically, so writes must prove same-origin intent
+                if !cookies::is_safe_method(&parts.method) && !cookies::verify_csrf(&parts.headers)
+                {
+                    logging::log_security_event(
+                        "csrf_rejected",
+                        &format!("{} {}", parts.method, parts.uri.path()),
+                    );
+                    return Err(AuthError::forbidden(
+                        "csrf_failed",
+                        "Missing or invalid CSRF token",
+                    ));
+                }
+                token
+            }
+        };
+
+        if token.starts_with(access_tokens::TOKEN_PREFIX) {
+            return match access_tokens::lookup(&state.db, token).await {
+                Ok(Some(user)) => Ok(user),
+                Ok(None) => Err(AuthError::unauthorized("Invalid access token")),
+                Err(e) => {
+                    logging::log_db_error("select", &e.to_string());
+                    Err(AuthError::internal())
+                }
+            };
+        }
+
+        let claims = decode_token(token, &state.revocations)
+            .map_err(|_| AuthError::unauthorized("Invalid or expired token"))?;
+
+        let client_ip = ClientIp::from_request_parts(parts, state)
+            .await
+            .map_err(|_| AuthError::internal())?;
+        if let Some(sid) = &claims.sid
+            && let Err(e) = sessions::touch(&state.db, sid, client_ip, &parts.headers).await
+        {
+            logging::log_db_error("update", &e.to_string());
+        }
+
+        Ok(AuthUser {
+            username: claims.sub,
+            roles: claims.roles,
+            token: TokenInfo {
+                kind: TokenKind::Session,
+                id: claims.jti,
+                session_id: claims.sid,
+                scopes: Scope::ALL.to_vec(),
+                expires_at: chrono::DateTime::from_timestamp(claims.exp as i64, 0),
+            },
+        })
+    }
+}
+
+/// An `AuthUser` that logged in interactively; personal access tokens are rejected.
+///
+/// Used for account-management routes so a leaked script token cannot mint more
+/// tokens, change 2FA settings or end the owner's sessions.
+pub struct SessionUser(pub AuthUser);
+
+impl FromRequestParts<AppState> for SessionUser {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let user = AuthUser::from_request_parts(parts, state).await?;
+        user.require_session()?;
+        Ok(SessionUser(user))
+    }
+}
+
+/// Scope an `/admin` route needs: stats are read-only, otherwise the method decides
+fn required_scope(method: &Method, path: &str) -> Scope {
+    if path.ends_with("/stats") {
+        Scope::StatsRead
+    } else if method == Method::GET || method == Method::HEAD {
+        Scope::NotesRead
+    } else {
+        Scope::NotesWrite
+    }
+}
+
+/// Middleware for the `/admin` router: authenticate once, enforce token scopes and
+/// make the principal available to handlers through the `AuthUser` extractor
+pub async fn require_auth(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let (mut parts, body) = request.into_parts();
+
+    let user = match AuthUser::from_request_parts(&mut parts, &state).await {
+        Ok(user) => user,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    if let Err(rejection) = user.require_scope(required_scope(&parts.method, parts.uri.path())) {
+        return rejection.into_response();
+    }
+
+    if let Err(rejection) = user.require_role(rbac::required_role(&parts.method, parts.uri.path()))
+    {
+        return rejection.into_response();
+    }
+
+    parts.extensions.insert(user);
+    next.run(Request::from_parts(parts, body)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_required_scope() {
+        assert_eq!(required_scope(&Method::GET, "/contents"), Scope::NotesRead);
+        assert_eq!(
+            required_scope(&Method::POST, "/contents"),
+            Scope::NotesWrite
+        );
+        assert_eq!(
+            required_scope(&Method::PUT, "/contents/1"),
+            Scope::NotesWrite
+        );
+        assert_eq!(
+            required_scope(&Method::DELETE, "/contents/1"),
+            Scope::NotesWrite
+        );
+        assert_eq!(required_scope(&Method::GET, "/stats"), Scope::StatsRead);
+    }
+
+    #[test]
+    fn test_auth_error_response_has_challenge() {
+        let response = AuthError::unauthorized("Missing authorization header").into_response();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let challenge = response.headers()[header::WWW_AUTHENTICATE]
+            .to_str()
+            .unwrap();
+        assert!(challenge.starts_with("Bearer realm=\"notepad\", error=\"invalid_token\""));
+
+        let response = AuthError::insufficient_scope(Scope::NotesWrite).into_response();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let challenge = response.headers()[header::WWW_AUTHENTICATE]
+            .to_str()
+            .unwrap();
+        assert!(challenge.contains("scope=\"notes:write\""));
+    }
+
+    /// State for router tests; the pool connects lazily and is never used
+    fn test_state() -> AppState {
+        AppState {
+            db: std::sync::Arc::new(
+                sqlx::MySqlPool::connect_lazy("mysql://test@localhost/test").unwrap(),
+            ),
+            revocations: Default::default(),
+            login_throttle: Default::default(),
+            mfa_challenges: Default::default(),
+            render_cache: Default::default(),
+            auth_providers: Default::default(),
+            oidc: None,
+            trusted_proxies: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_require_auth_rejects_missing_token() {
+        use axum::{Router, body::Body, middleware::from_fn_with_state, routing::get};
+        use tower::util::ServiceExt;
+
+        let state = test_state();
+        let app = Router::new()
+            .route("/contents", get(|| async { "ok" }))
+            .route_layer(from_fn_with_state(state.clone(), require_auth))
+            .with_state(state);
+
+        let request = Request::builder()
+            .uri("/contents")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(response.headers().contains_key(header::WWW_AUTHENTICATE));
+    }
+
+    #[tokio::test]
+    async fn test_cookie_session_write_requires_csrf() {
+        use axum::{Router, body::Body, middleware::from_fn_with_state, routing::post};
+        use tower::util::ServiceExt;
+
+        let state = test_state();
+        let app = Router::new()
+            .route("/contents", post(|| async { "ok" }))
+            .route_layer(from_fn_with_state(state.clone(), require_auth))
+            .with_state(state);
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/contents")
+            .header(header::COOKIE, "notepad_session=abc; notepad_csrf=tok")
+            .header(cookies::CSRF_HEADER, "other")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[test]
+    fn test_pat_cannot_manage_account() {
+        let user = AuthUser {
+            username: "ci".to_string(),
+            roles: vec![],
+            token: TokenInfo {
+                kind: TokenKind::PersonalAccessToken,
+                id: "1".to_string(),
+                session_id: None,
+                scopes: vec![Scope::NotesRead],
+                expires_at: None,
+            },
+        };
+        assert!(user.require_session().is_err());
+        assert!(user.require_scope(Scope::NotesRead).is_ok());
+        assert!(user.require_scope(Scope::NotesWrite).is_err());
+    }
+
+    #[test]
+    fn test_require_role_uses_highest_role() {
+        let user = AuthUser {
+            username: "alice".to_string(),
+            roles: vec!["viewer".to_string()],
+            token: TokenInfo {
+                kind: TokenKind::Session,
+                id: "jti".to_string(),
+                session_id: Some("session".to_string()),
+                scopes: Scope::ALL.to_vec(),
+                expires_at: None,
+            },
+        };
+        assert!(user.require_role(Role::Viewer).is_ok());
+        assert!(user.require_role(Role::Editor).is_err());
+
+        let admin = AuthUser {
+            roles: vec!["viewer".to_string(), "admin".to_string()],
+            ..user
+        };
+        assert_eq!(admin.role(), Role::Admin);
+        assert!(admin.require_role(Role::Admin).is_ok());
+    }
+}
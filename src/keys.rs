@@ -0,0 +1,322 @@
+use crate::logging;
+use axum::response::Json;
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey};
+use rsa::{
+    RsaPrivateKey, RsaPublicKey,
+    pkcs1::{DecodeRsaPrivateKey, DecodeRsaPublicKey},
+    pkcs8::{DecodePrivateKey, DecodePublicKey},
+    traits::PublicKeyParts,
+};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use std::{env, fs, sync::OnceLock};
+
+/// Asymmetric key material for signing and verifying access tokens.
+///
+/// Configured through JWT_SIGNING_KEY_PATH (Ed25519 or RSA private key PEM) and
+/// JWT_VERIFY_KEY_PATHS (comma-separated public key PEMs of retired keys that are
+/// still accepted during a rotation). Without a signing key the server keeps using
+/// HS256 with JWT_SECRET.
+pub struct JwtKeys {
+    signing: SigningKey,
+    verification: Vec<VerificationKey>,
+}
+
+struct SigningKey {
+    kid: String,
+    alg: Algorithm,
+    key: EncodingKey,
+}
+
+struct VerificationKey {
+    kid: String,
+    alg: Algorithm,
+    key: DecodingKey,
+    jwk: Value,
+}
+
+/// Public half of a key, in the form needed for both verification and the JWKS document
+enum PublicKey {
+    Ed25519 { x: Vec<u8> },
+    Rsa { n: Vec<u8>, e: Vec<u8> },
+}
+
+impl PublicKey {
+    fn algorithm(&self) -> Algorithm {
+        match self {
+            PublicKey::Ed25519 { .. } => Algorithm::EdDSA,
+            PublicKey::Rsa { .. } => Algorithm::RS256,
+        }
+    }
+
+    /// RFC 7638 JWK thumbprint, used as the key ID
+    fn thumbprint(&self) -> String {
+        let canonical = match self {
+            PublicKey::Ed25519 { x } => format!(
+                r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#,
+                URL_SAFE_NO_PAD.encode(x)
+            ),
+            PublicKey::Rsa { n, e } => format!(
+                r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#,
+                URL_SAFE_NO_PAD.encode(e),
+                URL_SAFE_NO_PAD.encode(n)
+            ),
+        };
+        URL_SAFE_NO_PAD.encode(Sha256::digest(canonical.as_bytes()))
+    }
+
+    fn into_verification_key(self) -> Result<VerificationKey, Box<dyn std::error::Error>> {
+        let kid = self.thumbprint();
+        let alg = self.algorithm();
+
+        let (key, jwk) = match &self {
+            PublicKey::Ed25519 { x } => {
+                let x = URL_SAFE_NO_PAD.encode(x);
+                let key = DecodingKey::from_ed_components(&x)?;
+                let jwk = json!({
+                    "kty": "OKP",
+                    "crv": "Ed25519",
+                    "use": "sig",
+                    "alg": "EdDSA",
+                    "kid": kid,
+                    "x": x
+                });
+                (key, jwk)
+            }
+            PublicKey::Rsa { n, e } => {
+                let n = URL_SAFE_NO_PAD.encode(n);
+                let e = URL_SAFE_NO_PAD.encode(e);
+                let key = DecodingKey::from_rsa_components(&n, &e)?;
+                let jwk = json!({
+                    "kty": "RSA",
+                    "use": "sig",
+                    "alg": "RS256",
+                    "kid": kid,
+                    "n": n,
+                    "e": e
+                });
+                (key, jwk)
+            }
+        };
+
+        Ok(VerificationKey { kid, alg, key, jwk })
+    }
+}
+
+fn rsa_public_key(key: &RsaPublicKey) -> PublicKey {
+    PublicKey::Rsa {
+        n: key.n().to_bytes_be(),
+        e: key.e().to_bytes_be(),
+    }
+}
+
+/// Parse a private key PEM (PKCS#8 Ed25519, or PKCS#8/PKCS#1 RSA)
+fn parse_private_key(pem: &str) -> Result<(EncodingKey, PublicKey), Box<dyn std::error::Error>> {
+    if let Ok(key) = ed25519_dalek::SigningKey::from_pkcs8_pem(pem) {
+        let public = PublicKey::Ed25519 {
+            x: key.verifying_key().to_bytes().to_vec(),
+        };
+        return Ok((EncodingKey::from_ed_pem(pem.as_bytes())?, public));
+    }
+
+    let key = RsaPrivateKey::from_pkcs8_pem(pem)
+        .or_else(|_| RsaPrivateKey::from_pkcs1_pem(pem))
+        .map_err(|_| "unsupported private key: expected an Ed25519 or RSA PEM")?;
+    let public = rsa_public_key(&key.to_public_key());
+
+    Ok((EncodingKey::from_rsa_pem(pem.as_bytes())?, public))
+}
+
+/// Parse a public key PEM (SPKI Ed25519, or SPKI/PKCS#1 RSA)
+fn parse_public_key(pem: &str) -> Result<PublicKey, Box<dyn std::error::Error>> {
+    if let Ok(key) = ed25519_dalek::VerifyingKey::from_public_key_pem(pem) {
+        return Ok(PublicKey::Ed25519 {
+            x: key.to_bytes().to_vec(),
+        });
+    }
+
+    let key = RsaPublicKey::from_public_key_pem(pem)
+        .or_else(|_| RsaPublicKey::from_pkcs1_pem(pem))
+        .map_err(|_| "unsupported public key: expected an Ed25519 or RSA PEM")?;
+
+    Ok(rsa_public_key(&key))
+}
+
+impl JwtKeys {
+    /// Build the key set from a signing key PEM and the PEMs of retired public keys
+    pub fn from_pems(
+        signing_pem: &str,
+        retired_pems: &[String],
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let (encoding_key, public) = parse_private_key(signing_pem)?;
+        let signing_verification = public.into_verification_key()?;
+
+        let signing = SigningKey {
+            kid: signing_verification.kid.clone(),
+            alg: signing_verification.alg,
+            key: encoding_key,
+        };
+
+        let mut verification = vec![signing_verification];
+        for pem in retired_pems {
+            let key = parse_public_key(pem)?.into_verification_key()?;
+            if !verification.iter().any(|k| k.kid == key.kid) {
+                verification.push(key);
+            }
+        }
+
+        Ok(JwtKeys {
+            signing,
+            verification,
+        })
+    }
+
+    /// Load keys from JWT_SIGNING_KEY_PATH and JWT_VERIFY_KEY_PATHS; `None` means HS256 mode
+    pub fn from_env() -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        let Ok(signing_path) = env::var("JWT_SIGNING_KEY_PATH") else {
+            return Ok(None);
+        };
+
+        let signing_pem = fs::read_to_string(&signing_path)
+            .map_err(|e| format!("cannot read {}: {}", signing_path, e))?;
+
+        let retired_pems = env::var("JWT_VERIFY_KEY_PATHS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+            .map(|path| {
+                fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Self::from_pems(&signing_pem, &retired_pems).map(Some)
+    }
+
+    /// Key ID, algorithm and key used to sign new tokens
+    pub fn signing_key(&self) -> (&str, Algorithm, &EncodingKey) {
+        (&self.signing.kid, self.signing.alg, &self.signing.key)
+    }
+
+    /// Verification key for a token's `kid` header
+    pub fn verification_key(&self, kid: &str) -> Option<(Algorithm, &DecodingKey)> {
+        self.verification
+            .iter()
+            .find(|k| k.kid == kid)
+            .map(|k| (k.alg, &k.key))
+    }
+
+    /// Public keys as a JWK Set document
+    pub fn jwks(&self) -> Value {
+        json!({ "keys": self.verification.iter().map(|k| &k.jwk).collect::<Vec<_>>() })
+    }
+}
+
+static KEYS: OnceLock<Option<JwtKeys>> = OnceLock::new();
+
+/// Load the configured keys once at startup
+pub fn init() -> Result<(), Box<dyn std::error::Error>> {
+    let keys = JwtKeys::from_env()?;
+    if let Some(keys) = &keys {
+        logging::log_security_event(
+            "jwt_keys_loaded",
+            &format!(
+                "signing kid {}, {} verification key(s)",
+                keys.signing.kid,
+                keys.verification.len()
+            ),
+        );
+    }
+    let _ = KEYS.set(keys);
+    Ok(())
+}
+
+/// Asymmetric keys, if configured; `None` means tokens use HS256 with JWT_SECRET
+pub fn current() -> Option<&'static JwtKeys> {
+    KEYS.get().and_then(Option::as_ref)
+}
+
+/// GET /.well-known/jwks.json - public keys other services can verify tokens with
+pub async fn jwks() -> Json<Value> {
+    Json(current().map_or_else(|| json!({ "keys": [] }), JwtKeys::jwks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Claims;
+    use ed25519_dalek::pkcs8::{EncodePrivateKey, EncodePublicKey, spki::der::pem::LineEnding};
+    use jsonwebtoken::{Header, Validation, decode, decode_header, encode};
+
+    fn ed25519_pems(seed: u8) -> (String, String) {
+        let key = ed25519_dalek::SigningKey::from_bytes(&[seed; 32]);
+        let private = key.to_pkcs8_pem(LineEnding::LF).unwrap().to_string();
+        let public = key
+            .verifying_key()
+            .to_public_key_pem(LineEnding::LF)
+            .unwrap();
+        (private, public)
+    }
+
+    fn sign(keys: &JwtKeys) -> String {
+        let (kid, alg, key) = keys.signing_key();
+        let mut header = Header::new(alg);
+        header.kid = Some(kid.to_string());
+        let claims = Claims {
+            sub: "alice".to_string(),
+            exp: (chrono::Utc::now().timestamp() + 60) as usize,
+            iat: chrono::Utc::now().timestamp() as usize,
+            iat_ms: None,
+            jti: "test".to_string(),
+            sid: None,
+            roles: vec![],
+        };
+        encode(&header, &claims, key).unwrap()
+    }
+
+    fn verify(keys: &JwtKeys, token: &str) -> bool {
+        let kid = decode_header(token).unwrap().kid.unwrap();
+        let Some((alg, key)) = keys.verification_key(&kid) else {
+            return false;
+        };
+        decode::<Claims>(token, key, &Validation::new(alg)).is_ok()
+    }
+
+    #[test]
+    fn test_ed25519_sign_and_verify() {
+        let (private, _) = ed25519_pems(1);
+        let keys = JwtKeys::from_pems(&private, &[]).unwrap();
+
+        let token = sign(&keys);
+        assert!(verify(&keys, &token));
+
+        let jwks = keys.jwks();
+        assert_eq!(jwks["keys"].as_array().unwrap().len(), 1);
+        assert_eq!(jwks["keys"][0]["kty"], "OKP");
+        assert_eq!(jwks["keys"][0]["kid"], keys.signing_key().0);
+    }
+
+    #[test]
+    fn test_rotation_keeps_retired_key_valid() {
+        let (old_private, old_public) = ed25519_pems(1);
+        let (new_private, _) = ed25519_pems(2);
+
+        let old_keys = JwtKeys::from_pems(&old_private, &[]).unwrap();
+        let old_token = sign(&old_keys);
+
+        // Before the old key is listed, its tokens are rejected
+        let rotated = JwtKeys::from_pems(&new_private, &[]).unwrap();
+        assert!(!verify(&rotated, &old_token));
+
+        let rotated = JwtKeys::from_pems(&new_private, &[old_public]).unwrap();
+        assert!(verify(&rotated, &old_token));
+        assert!(verify(&rotated, &sign(&rotated)));
+        assert_eq!(rotated.jwks()["keys"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_rejects_invalid_pem() {
+        assert!(JwtKeys::from_pems("not a key", &[]).is_err());
+    }
+}
@@ -0,0 +1,489 @@
+use crate::{
+    auth_provider::{AuthFailure, AuthProvider},
+    logging,
+    models::User,
+    rbac::Role,
+    users,
+};
+use ldap3::{LdapConnAsync, LdapConnSettings, Scope, dn_escape, ldap_escape};
+use sqlx::MySqlPool;
+use std::{env, time::Duration};
+
+/// LDAP result code for a failed simple bind (RFC 4511)
+const INVALID_CREDENTIALS: u32 = 49;
+
+/// Password hash stored for accounts created by LDAP login; never matches a password
+const NO_PASSWORD_HASH: &str = "!ldap";
+
+/// Directory settings from LDAP_* environment variables
+#[derive(Debug, Clone)]
+pub struct LdapConfig {
+    /// e.g. `ldap://ldap.example.com:389` or `ldaps://...`
+    pub url: String,
+    /// DN to bind as, with `{username}` replaced, e.g. `uid={username},ou=people,dc=example,dc=org`
+    pub bind_dn_template: String,
+    /// Base DN for the admin group search
+    pub search_base: Option<String>,
+    /// Filter matching when the user is an admin; `{username}` and `{dn}` are replaced,
+    /// e.g. `(&(cn=notepad-admins)(member={dn}))`
+    pub admin_group_filter: Option<String>,
+    pub timeout: Duration,
+}
+
+impl LdapConfig {
+    /// `None` when LDAP_URL is unset; an error when it is set without a bind DN template
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Some(url) = env::var("LDAP_URL").ok().filter(|v| !v.is_empty()) else {
+            return Ok(None);
+        };
+
+        let bind_dn_template = env::var("LDAP_BIND_DN_TEMPLATE")
+            .ok()
+            .filter(|v| v.contains("{username}"))
+            .ok_or("LDAP_BIND_DN_TEMPLATE must be set and contain {username}")?;
+
+        let search_base = env::var("LDAP_SEARCH_BASE").ok().filter(|v| !v.is_empty());
+        let admin_group_filter = env::var("LDAP_ADMIN_GROUP_FILTER")
+            .ok()
+            .filter(|v| !v.is_empty());
+        if admin_group_filter.is_some() && search_base.is_none() {
+            return Err("LDAP_ADMIN_GROUP_FILTER requires LDAP_SEARCH_BASE".to_string());
+        }
+
+        let timeout = env::var("LDAP_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|secs| *secs > 0)
+            .unwrap_or(5);
+
+        Ok(Some(LdapConfig {
+            url,
+            bind_dn_template,
+            search_base,
+            admin_group_filter,
+            timeout: Duration::from_secs(timeout),
+        }))
+    }
+}
+
+/// Role after an LDAP login: group membership grants or removes admin, other roles are kept
+pub fn synced_role(stored: Role, is_admin: Option<bool>) -> Role {
+    match is_admin {
+        Some(true) => Role::Admin,
+        Some(false) if stored == Role::Admin => Role::Editor,
+        _ => stored,
+    }
+}
+
+/// Authenticates with an LDAP simple bind as the user
+pub struct LdapProvider {
+    config: LdapConfig,
+}
+
+impl LdapProvider {
+    pub fn new(config: LdapConfig) -> Self {
+        LdapProvider { config }
+    }
+
+    /// DN for a username, escaped so it cannot add RDNs
+    pub fn bind_dn(&self, username: &str) -> String {
+        self.config
+            .bind_dn_template
+            .replace("{username}", &dn_escape(username))
+    }
+
+    /// Bind as the user and, if configured, check admin group membership.
+    ///
+    /// Returns `Some(is_admin)` when an admin group filter is configured.
+    pub async fn check(&self, username: &str, password: &str) -> Result<Option<bool>, AuthFailure> {
+        // An empty password would be an unauthenticated bind, which servers accept
+        if username.is_empty() || password.is_empty() {
+            return Err(AuthFailure::Rejected("empty ldap credentials"));
+        }
+
+        let unavailable = |e: ldap3::LdapError| AuthFailure::Unavailable(e.to_string());
+
+        let settings = LdapConnSettings::new().set_conn_timeout(self.config.timeout);
+        let (conn, mut ldap) = LdapConnAsync::with_settings(settings, &self.config.url)
+            .await
+            .map_err(unavailable)?;
+        ldap3::drive!(conn);
+
+        let dn = self.bind_dn(username);
+        let bind = ldap
+            .with_timeout(self.config.timeout)
+            .simple_bind(&dn, password)
+            .await
+            .map_err(unavailable)?;
+        match bind.rc {
+            0 => {}
+            INVALID_CREDENTIALS => return Err(AuthFailure::Rejected("invalid ldap credentials")),
+            rc => {
+                return Err(AuthFailure::Unavailable(format!(
+                    "bind returned code {}: {}",
+                    rc, bind.text
+                )));
+            }
+        }
+
+        let is_admin = match (&self.config.search_base, &self.config.admin_group_filter) {
+            (Some(base), Some(template)) => {
+                let filter = template
+                    .replace("{username}", &ldap_escape(username))
+                    .replace("{dn}", &ldap_escape(&dn));
+                let (entries, _) = ldap
+                    .with_timeout(self.config.timeout)
+                    .search(base, Scope::Subtree, &filter, vec!["1.1"])
+                    .await
+                    .and_then(|result| result.success())
+                    .map_err(unavailable)?;
+                Some(!entries.is_empty())
+            }
+            _ => None,
+        };
+
+        let _ = ldap.unbind().await;
+        Ok(is_admin)
+    }
+}
+
+impl AuthProvider for LdapProvider {
+    fn name(&self) -> &'static str {
+        "ldap"
+    }
+
+    /// Bind, then create or update the matching local account.
+    ///
+    /// Only accounts LDAP created are managed here. Other local accounts, such as the
+    /// bootstrap admin, are left to `LocalProvider`: their password is never sent to
+    /// the directory and their role is never synced.
+    async fn authenticate(
+        &self,
+        db: &MySqlPool,
+        username: &str,
+        password: &str,
+    ) -> Result<User, AuthFailure> {
+        let db_error = |e: sqlx::Error| AuthFailure::Unavailable(e.to_string());
+
+        let existing = users::find_by_username(db, username)
+            .await
+            .map_err(db_error)?;
+        if existing
+            .as_ref()
+            .is_some_and(|user| user.password_hash != NO_PASSWORD_HASH)
+        {
+            return Err(AuthFailure::Rejected("local account not managed by ldap"));
+        }
+
+        let is_admin = self.check(username, password).await?;
+
+        match existing {
+            Some(user) => {
+                let stored = Role::parse(&user.role).unwrap_or_default();
+                let role = synced_role(stored, is_admin);
+                if role != stored {
+                    users::set_role(db, username, role)
+                        .await
+                        .map_err(db_error)?;
+                    logging::log_security_event(
+                        "ldap_role_synced",
+                        &format!("{} is now {}", username, role.as_str()),
+                    );
+                }
+            }
+            None => {
+                let role = synced_role(Role::Editor, is_admin);
+                users::create_user(db, username, NO_PASSWORD_HASH, role)
+                    .await
+                    .map_err(db_error)?;
+                logging::log_security_event(
+                    "ldap_account_created",
+                    &format!("{} as {}", username, role.as_str()),
+                );
+            }
+        }
+
+        match users::find_by_username(db, username)
+            .await
+            .map_err(db_error)?
+        {
+            Some(user) if user.is_active => Ok(user),
+            Some(_) => Err(AuthFailure::Rejected("account disabled")),
+            None => Err(AuthFailure::Unavailable("account vanished".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ldap3::asn1::{PL, StructureTag, parse_tag};
+    use std::{collections::HashMap, sync::Arc};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    /// Directory contents served by the stand-in server
+    struct Directory {
+        passwords: HashMap<String, String>,
+        /// (dn, attributes) pairs searched by filters
+        entries: Vec<(String, Vec<(String, String)>)>,
+    }
+
+    fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut out = vec![tag];
+        let len = content.len();
+        if len < 0x80 {
+            out.push(len as u8);
+        } else {
+            let bytes: Vec<u8> = len
+                .to_be_bytes()
+                .into_iter()
+                .skip_while(|b| *b == 0)
+                .collect();
+            out.push(0x80 | bytes.len() as u8);
+            out.extend(bytes);
+        }
+        out.extend_from_slice(content);
+        out
+    }
+
+    fn integer(n: u64) -> Vec<u8> {
+        let mut bytes: Vec<u8> = n
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|b| *b == 0)
+            .collect();
+        if bytes.first().is_none_or(|b| b & 0x80 != 0) {
+            bytes.insert(0, 0);
+        }
+        bytes
+    }
+
+    /// LDAPResult body: resultCode, matchedDN, diagnosticMessage
+    fn ldap_result(tag: u8, rc: u8) -> Vec<u8> {
+        let mut body = tlv(0x0a, &[rc]);
+        body.extend(tlv(0x04, b""));
+        body.extend(tlv(0x04, b""));
+        tlv(tag, &body)
+    }
+
+    fn message(id: u64, op: Vec<u8>) -> Vec<u8> {
+        let mut body = tlv(0x02, &integer(id));
+        body.extend(op);
+        tlv(0x30, &body)
+    }
+
+    fn children(tag: StructureTag) -> Vec<StructureTag> {
+        match tag.payload {
+            PL::C(children) => children,
+            PL::P(_) => Vec::new(),
+        }
+    }
+
+    fn bytes(tag: &StructureTag) -> Vec<u8> {
+        match &tag.payload {
+            PL::P(bytes) => bytes.clone(),
+            PL::C(_) => Vec::new(),
+        }
+    }
+
+    fn text(tag: &StructureTag) -> String {
+        String::from_utf8_lossy(&bytes(tag)).to_lowercase()
+    }
+
+    /// Evaluate and/or/not/equality/present filters (RFC 4511 section 4.5.1.7)
+    fn matches(filter: &StructureTag, attrs: &[(String, String)]) -> bool {
+        match filter.id {
+            0 => children(filter.clone()).iter().all(|f| matches(f, attrs)),
+            1 => children(filter.clone()).iter().any(|f| matches(f, attrs)),
+            2 => !children(filter.clone()).iter().any(|f| matches(f, attrs)),
+            3 => {
+                let parts = children(filter.clone());
+                let (name, value) = (text(&parts[0]), text(&parts[1]));
+                attrs
+                    .iter()
+                    .any(|(n, v)| n.to_lowercase() == name && v.to_lowercase() == value)
+            }
+            7 => {
+                let name = text(filter);
+                attrs.iter().any(|(n, _)| n.to_lowercase() == name)
+            }
+            _ => false,
+        }
+    }
+
+    fn respond(directory: &Directory, request: StructureTag) -> Option<Vec<u8>> {
+        let mut parts = children(request).into_iter();
+        let id = bytes(&parts.next()?)
+            .iter()
+            .fold(0u64, |n, b| (n << 8) | u64::from(*b));
+        let op = parts.next()?;
+
+        match op.id {
+            // BindRequest: version, name, simple [0] password
+            0 => {
+                let fields = children(op);
+                let dn = String::from_utf8_lossy(&bytes(&fields[1])).into_owned();
+                let password = String::from_utf8_lossy(&bytes(&fields[2])).into_owned();
+                let ok = (dn.is_empty() && password.is_empty())
+                    || directory.passwords.get(&dn) == Some(&password);
+                Some(message(id, ldap_result(0x61, if ok { 0 } else { 49 })))
+            }
+            // SearchRequest: base, scope, deref, size, time, typesOnly, filter, attributes
+            3 => {
+                let fields = children(op);
+                let base = text(&fields[0]);
+                let mut out = Vec::new();
+                for (dn, attrs) in &directory.entries {
+                    if dn.to_lowercase().ends_with(&base) && matches(&fields[6], attrs) {
+                        let mut entry = tlv(0x04, dn.as_bytes());
+                        entry.extend(tlv(0x30, b""));
+                        out.extend(message(id, tlv(0x64, &entry)));
+                    }
+                }
+                out.extend(message(id, ldap_result(0x65, 0)));
+                Some(out)
+            }
+            // UnbindRequest or anything unsupported: close the connection
+            _ => None,
+        }
+    }
+
+    /// OpenLDAP-compatible stand-in speaking just enough LDAPv3 for bind and search
+    async fn start_directory(directory: Directory) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ldap://{}", listener.local_addr().unwrap());
+        let directory = Arc::new(directory);
+
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let directory = directory.clone();
+                tokio::spawn(async move {
+                    let mut buf = Vec::new();
+                    let mut chunk = [0u8; 4096];
+                    loop {
+                        while let Ok((rest, request)) = parse_tag(&buf) {
+                            let consumed = buf.len() - rest.len();
+                            let Some(reply) = respond(&directory, request) else {
+                                return;
+                            };
+                            buf.drain(..consumed);
+                            if socket.write_all(&reply).await.is_err() {
+                                return;
+                            }
+                        }
+                        match socket.read(&mut chunk).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+                        }
+                    }
+                });
+            }
+        });
+
+        url
+    }
+
+    fn directory() -> Directory {
+        Directory {
+            passwords: HashMap::from([
+                (
+                    "uid=alice,ou=people,dc=example,dc=org".to_string(),
+                    "alice-pass".to_string(),
+                ),
+                (
+                    "uid=bob,ou=people,dc=example,dc=org".to_string(),
+                    "bob-pass".to_string(),
+                ),
+            ]),
+            entries: vec![(
+                "cn=notepad-admins,ou=groups,dc=example,dc=org".to_string(),
+                vec![
+                    ("objectClass".to_string(), "groupOfNames".to_string()),
+                    ("cn".to_string(), "notepad-admins".to_string()),
+                    (
+                        "member".to_string(),
+                        "uid=alice,ou=people,dc=example,dc=org".to_string(),
+                    ),
+                ],
+            )],
+        }
+    }
+
+    fn provider(url: &str, admin_group_filter: Option<&str>) -> LdapProvider {
+        LdapProvider::new(LdapConfig {
+            url: url.to_string(),
+            bind_dn_template: "uid={username},ou=people,dc=example,dc=org".to_string(),
+            search_base: Some("ou=groups,dc=example,dc=org".to_string()),
+            admin_group_filter: admin_group_filter.map(str::to_string),
+            timeout: Duration::from_secs(2),
+        })
+    }
+
+    const ADMIN_FILTER: &str = "(&(objectClass=groupOfNames)(cn=notepad-admins)(member={dn}))";
+
+    #[tokio::test]
+    async fn test_bind_and_admin_group_mapping() {
+        let url = start_directory(directory()).await;
+        let ldap = provider(&url, Some(ADMIN_FILTER));
+
+        assert_eq!(ldap.check("alice", "alice-pass").await, Ok(Some(true)));
+        assert_eq!(ldap.check("bob", "bob-pass").await, Ok(Some(false)));
+        assert_eq!(
+            ldap.check("bob", "wrong").await,
+            Err(AuthFailure::Rejected("invalid ldap credentials"))
+        );
+        assert_eq!(
+            ldap.check("mallory", "anything").await,
+            Err(AuthFailure::Rejected("invalid ldap credentials"))
+        );
+
+        let without_groups = provider(&url, None);
+        assert_eq!(without_groups.check("alice", "alice-pass").await, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn test_empty_password_is_not_an_anonymous_bind() {
+        let url = start_directory(directory()).await;
+        let ldap = provider(&url, None);
+
+        assert_eq!(
+            ldap.check("alice", "").await,
+            Err(AuthFailure::Rejected("empty ldap credentials"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_server_is_unavailable() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ldap://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let result = provider(&url, None).check("alice", "alice-pass").await;
+        assert!(matches!(result, Err(AuthFailure::Unavailable(_))));
+    }
+
+    #[test]
+    fn test_bind_dn_escapes_username() {
+        let ldap = provider("ldap://localhost", None);
+        assert_eq!(
+            ldap.bind_dn("alice"),
+            "uid=alice,ou=people,dc=example,dc=org"
+        );
+        assert_eq!(
+            ldap.bind_dn("eve,ou=admins"),
+            "uid=eve\\2cou\\3dadmins,ou=people,dc=example,dc=org"
+        );
+    }
+
+    #[test]
+    fn test_synced_role() {
+        assert_eq!(synced_role(Role::Viewer, Some(true)), Role::Admin);
+        assert_eq!(synced_role(Role::Admin, Some(false)), Role::Editor);
+        assert_eq!(synced_role(Role::Viewer, Some(false)), Role::Viewer);
+        assert_eq!(synced_role(Role::Admin, None), Role::Admin);
+    }
+}
@@ -1,7 +1,32 @@
+pub mod access_tokens;
 pub mod auth;
+pub mod auth_provider;
+pub mod client_ip;
 pub mod content;
+pub mod cookies;
 pub mod db;
+pub mod etag;
+pub mod extractors;
+pub mod keys;
+pub mod ldap;
 pub mod logging;
+pub mod markdown;
 pub mod models;
+pub mod notebooks;
+pub mod oidc;
+pub mod pagination;
+pub mod passwords;
+pub mod rbac;
+pub mod refresh_tokens;
+pub mod revisions;
+pub mod revocation;
+pub mod search;
+pub mod sessions;
 pub mod state;
+pub mod tags;
+pub mod throttle;
+pub mod totp;
+pub mod trash;
+pub mod two_factor;
+pub mod users;
 pub mod utils;
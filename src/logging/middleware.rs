@@ -1,6 +1,7 @@
+use crate::client_ip::ClientIp;
 use axum::{
     extract::Request,
-    http::{HeaderMap, HeaderName, StatusCode},
+    http::{HeaderMap, StatusCode},
     middleware::Next,
     response::Response,
 };
@@ -10,6 +11,7 @@ use uuid::Uuid;
 
 /// Request logging middleware
 pub async fn request_logging_middleware(
+    client_ip: ClientIp,
     request: Request,
     next: Next,
 ) -> Result<Response, StatusCode> {
@@ -19,15 +21,7 @@ pub async fn request_logging_middleware(
     let request_id = Uuid::new_v4().to_string();
 
     // Extract user agent if available
-    let user_agent = request
-        .headers()
-        .get("user-agent")
-        .and_then(|h| h.to_str().ok())
-        .unwrap_or("unknown")
-        .to_string();
-
-    // Extract client IP
-    let client_ip = extract_client_ip(&request.headers());
+    let user_agent = extract_user_agent(request.headers());
 
     info!(
         "Request started - ID: {}, Method: {}, Path: {}, IP: {}, User-Agent: {}",
@@ -62,32 +56,13 @@ pub async fn request_logging_middleware(
     Ok(response)
 }
 
-/// Extract client IP from headers
-fn extract_client_ip(headers: &HeaderMap) -> String {
-    // Try various headers in order of preference
-    let ip_headers = [
-        "x-forwarded-for",
-        "x-real-ip",
-        "cf-connecting-ip",
-        "x-client-ip",
-        "x-forwarded",
-        "forwarded-for",
-        "forwarded",
-    ];
-
-    for header_name in ip_headers.iter().map(|s| HeaderName::from_static(s)) {
-        if let Some(header_value) = headers.get(&header_name) {
-            if let Ok(ip_str) = header_value.to_str() {
-                // X-Forwarded-For can contain multiple IPs, take the first one
-                let ip = ip_str.split(',').next().unwrap_or("").trim();
-                if !ip.is_empty() {
-                    return ip.to_string();
-                }
-            }
-        }
-    }
-
-    "unknown".to_string()
+/// Extract the client's user agent from headers
+pub fn extract_user_agent(headers: &HeaderMap) -> String {
+    headers
+        .get("user-agent")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("unknown")
+        .to_string()
 }
 
 /// Error logging middleware for handling internal server errors
@@ -1,19 +1,16 @@
-mod auth;
-mod content;
-mod db;
-mod logging;
-mod models;
-mod state;
-mod utils;
-
 use axum::{
     Router,
     http::{HeaderValue, Method},
-    middleware::from_fn,
+    middleware::{from_fn, from_fn_with_state},
     response::Json,
-    routing::{delete, get, post, put},
+    routing::{delete, get, patch, post, put},
+};
+use backend::{
+    access_tokens, auth, auth_provider::AuthProviders, client_ip::TrustedProxies, content, cookies,
+    db, extractors, keys, logging, notebooks, oidc, passwords, revisions,
+    revocation::RevocationList, search, sessions, state, tags, trash, two_factor, users,
 };
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 use tower_http::cors::CorsLayer;
 
 #[tokio::main]
@@ -24,6 +21,33 @@ async fn main() {
     // Load environment variables
     dotenv::dotenv().ok();
 
+    // Load asymmetric JWT keys if configured (falls back to HS256 with JWT_SECRET)
+    if let Err(e) = keys::init() {
+        logging::log_config_error("JWT_SIGNING_KEY_PATH", &e.to_string());
+        eprintln!("Failed to load JWT keys: {}", e);
+        return;
+    }
+
+    // Password login backends: LDAP when LDAP_URL is set, local accounts always
+    let auth_providers = match AuthProviders::from_env() {
+        Ok(providers) => Arc::new(providers),
+        Err(e) => {
+            logging::log_config_error("LDAP_URL", &e);
+            eprintln!("Invalid LDAP configuration: {}", e);
+            return;
+        }
+    };
+
+    // Reverse proxies whose X-Forwarded-For is believed; none by default
+    let trusted_proxies = match TrustedProxies::from_env() {
+        Ok(proxies) => Arc::new(proxies),
+        Err(e) => {
+            logging::log_config_error("TRUSTED_PROXIES", &e);
+            eprintln!("Invalid TRUSTED_PROXIES: {}", e);
+            return;
+        }
+    };
+
     // Connect to database
     let db = match db::connect_db().await {
         Ok(pool) => {
@@ -37,7 +61,29 @@ async fn main() {
         }
     };
 
-    let state = state::AppState { db };
+    // Seed the initial account from ADMIN_USER/ADMIN_PASS_HASH on first start
+    users::bootstrap_admin_from_env(&db).await;
+
+    // Load revoked tokens into memory and keep the cache in sync with the database
+    let revocations = RevocationList::default();
+    if let Err(e) = revocations.reload(&db).await {
+        logging::log_db_error("select", &e.to_string());
+    }
+    revocations.spawn_refresh_task(db.clone(), std::time::Duration::from_secs(60));
+
+    // Purge notes that have been in the trash longer than TRASH_RETENTION_DAYS
+    trash::spawn_purge_task(db.clone());
+
+    let state = state::AppState {
+        db,
+        revocations,
+        login_throttle: Default::default(),
+        mfa_challenges: Default::default(),
+        render_cache: Default::default(),
+        auth_providers,
+        oidc: oidc::OidcClient::from_env(),
+        trusted_proxies,
+    };
 
     // Get server configuration
     let host = std::env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
@@ -47,8 +93,7 @@ async fn main() {
         .unwrap_or(3000);
 
     // Get CORS configuration
-    let frontend_url = std::env::var("FRONTEND_URL")
-        .unwrap_or_else(|_| "https://www.terradominus.life".to_string());
+    let frontend_url = backend::utils::frontend_url();
 
     // Parse frontend URL with error handling
     let frontend_origin = frontend_url.parse::<HeaderValue>().unwrap_or_else(|_| {
@@ -64,26 +109,96 @@ async fn main() {
         frontend_origin
     );
 
-    // Build admin routes (authentication handled in each handler)
+    // Build admin routes (authentication and token scopes enforced by the require_auth layer)
     let admin_router = Router::new()
         .route("/contents", get(content::get_all_contents))
         .route("/contents", post(content::create_content))
+        .route("/contents/search", get(search::search_user_contents))
         .route("/contents/{id}", get(content::get_content_by_id_admin))
         .route("/contents/{id}", put(content::update_content))
+        .route("/contents/{id}", patch(content::update_content))
         .route("/contents/{id}", delete(content::delete_content))
-        .route("/stats", get(content::get_stats));
+        .route("/contents/{id}/move", post(content::move_content))
+        .route("/contents/{id}/revisions", get(revisions::list_revisions))
+        .route(
+            "/contents/{id}/revisions/diff",
+            get(revisions::diff_revisions),
+        )
+        .route(
+            "/contents/{id}/revisions/{revision}",
+            get(revisions::get_revision),
+        )
+        .route(
+            "/contents/{id}/revisions/{revision}/restore",
+            post(revisions::restore_revision),
+        )
+        .route(
+            "/notebooks",
+            get(notebooks::list_notebooks).post(notebooks::create_notebook),
+        )
+        .route(
+            "/notebooks/{id}",
+            get(notebooks::get_notebook)
+                .put(notebooks::rename_notebook)
+                .delete(notebooks::delete_notebook),
+        )
+        .route("/notebooks/{id}/move", post(notebooks::move_notebook))
+        .route("/trash", get(trash::list_trash).delete(trash::empty_trash))
+        .route("/trash/{id}", delete(trash::delete_from_trash))
+        .route("/trash/{id}/restore", post(trash::restore_from_trash))
+        .route("/stats", get(content::get_stats))
+        .route("/tags", get(tags::list_tags))
+        .route("/tags/merge", post(tags::merge_tags))
+        .route("/tags/{name}", put(tags::rename_tag))
+        // Admin-only routes (see rbac::required_role)
+        .route(
+            "/moderation/contents/{id}",
+            put(content::moderate_content).delete(content::moderate_delete_content),
+        )
+        .route("/moderation/stats", get(content::get_global_stats))
+        .route("/users/{username}/role", put(users::update_role))
+        .route(
+            "/users/{username}/password-reset",
+            post(passwords::create_reset_token),
+        )
+        .route_layer(from_fn_with_state(state.clone(), extractors::require_auth));
 
     // Build the application with routes
     let app = Router::new()
         // Public routes (no authentication required)
+        .route("/", get(root_handler))
         .route("/health", get(health_check))
+        .route("/.well-known/jwks.json", get(keys::jwks))
         .route("/contents", get(content::get_public_contents))
+        .route("/contents/search", get(search::search_public_contents))
         .route("/contents/{id}", get(content::get_content_by_id))
         // Authentication route
         .route("/login", post(auth::login))
+        .route("/login/2fa", post(auth::login_2fa))
+        .route("/login/oidc", get(oidc::start))
+        .route("/login/oidc/callback", get(oidc::callback))
+        .route("/register", post(auth::register))
+        .route("/token/refresh", post(auth::refresh))
+        .route("/password/reset", post(passwords::reset_password))
+        .route("/logout", post(auth::logout))
+        .route("/logout/all", post(auth::logout_all))
+        // Account security routes (authenticated through the SessionUser extractor)
+        .route("/account/2fa/setup", post(two_factor::setup))
+        .route("/account/2fa/confirm", post(two_factor::confirm))
+        .route("/account/2fa/disable", post(two_factor::disable))
+        .route(
+            "/account/2fa/recovery-codes",
+            post(two_factor::regenerate_recovery_codes),
+        )
+        .route("/account/password", post(passwords::change_password))
+        .route("/account/tokens", get(access_tokens::list_tokens))
+        .route("/account/tokens", post(access_tokens::create_token))
+        .route("/account/tokens/{id}", delete(access_tokens::revoke_token))
+        .route("/account/sessions", get(sessions::list_sessions))
+        .route("/account/sessions/{id}", delete(sessions::revoke_session))
         // Nest admin routes under /admin
         .nest("/admin", admin_router)
-        .with_state(state)
+        .with_state(state.clone())
         // Apply CORS middleware to allow frontend access
         .layer(
             CorsLayer::new()
@@ -99,10 +214,13 @@ async fn main() {
                     Method::DELETE,
                     Method::OPTIONS,
                 ])
+                .expose_headers([axum::http::header::RETRY_AFTER, axum::http::header::ETAG])
                 .allow_headers([
                     axum::http::header::AUTHORIZATION,
                     axum::http::header::ACCEPT,
                     axum::http::header::CONTENT_TYPE,
+                    axum::http::header::IF_MATCH,
+                    axum::http::HeaderName::from_static(cookies::CSRF_HEADER),
                 ])
                 .allow_credentials(true),
         )
@@ -123,7 +241,10 @@ async fn main() {
             },
         ))
         // Apply request logging middleware to all routes
-        .layer(from_fn(logging::middleware::request_logging_middleware))
+        .layer(from_fn_with_state(
+            state.clone(),
+            logging::middleware::request_logging_middleware,
+        ))
         .layer(from_fn(logging::middleware::error_logging_middleware));
 
     // Log application startup
@@ -131,7 +252,13 @@ async fn main() {
     println!("🚀 Notepad API server starting on {}:{}", host, port);
 
     let listener = tokio::net::TcpListener::bind((host, port)).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    // Connect info gives handlers the peer address for throttling and session records
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
 
 // Root handler (API endpoint)
@@ -140,14 +267,37 @@ async fn root_handler() -> Json<serde_json::Value> {
         "message": "Welcome to Notepad Content Management API",
         "version": "2.0.0",
         "features": {
-            "public_notes": "GET /contents - Get all public notes",
+            "public_notes": "GET /contents - List public notes (paginated, filterable by tag)",
+            "public_search": "GET /contents/search?q= - Full-text search of public notes",
             "public_note_by_id": "GET /contents/:id - Get specific public note",
-            "authentication": "POST /login - Admin login",
-            "user_notes": "GET /admin/contents - Get all user notes (auth required)",
+            "authentication": "POST /login - Log in with a local or LDAP account",
+            "jwks": "GET /.well-known/jwks.json - Public keys for verifying access tokens",
+            "oidc_login": "GET /login/oidc - Sign in with the configured OpenID Connect provider",
+            "two_factor_login": "POST /login/2fa - Complete login with a TOTP or recovery code",
+            "registration": "POST /register - Create an account (if signup is enabled)",
+            "token_refresh": "POST /token/refresh - Rotate a refresh token for a new access token",
+            "logout": "POST /logout - Revoke the current token (auth required)",
+            "logout_all": "POST /logout/all - Revoke all tokens for the current user (auth required)",
+            "two_factor": "POST /account/2fa/{setup,confirm,disable,recovery-codes} - Manage TOTP 2FA (auth required)",
+            "access_tokens": "GET/POST /account/tokens, DELETE /account/tokens/:id - Manage personal access tokens (auth required)",
+            "sessions": "GET /account/sessions, DELETE /account/sessions/:id - List and revoke active logins (auth required)",
+            "markdown": "?format=html on /contents and /admin/contents endpoints - Adds sanitized HTML rendered from Markdown as content_html",
+            "user_notes": "GET /admin/contents - List user notes (paginated, filterable by tag and notebook, auth required)",
+            "search_notes": "GET /admin/contents/search?q= - Full-text search of user notes (auth required)",
             "create_note": "POST /admin/contents - Create new note (auth required)",
-            "update_note": "PUT /admin/contents/:id - Update note (auth required)",
-            "delete_note": "DELETE /admin/contents/:id - Delete note (auth required)",
-            "stats": "GET /admin/stats - Get user statistics (auth required)"
+            "update_note": "PUT/PATCH /admin/contents/:id - Update the given fields; 412 if the note changed or If-Match is stale (auth required)",
+            "delete_note": "DELETE /admin/contents/:id - Move note to the trash (auth required)",
+            "trash": "GET/DELETE /admin/trash, POST /admin/trash/:id/restore, DELETE /admin/trash/:id - Manage trashed notes (auth required)",
+            "move_note": "POST /admin/contents/:id/move - Move a note into a notebook (auth required)",
+            "revisions": "GET /admin/contents/:id/revisions[/:revision], GET .../revisions/diff?from=&to=, POST .../revisions/:revision/restore - Note history (auth required)",
+            "notebooks": "GET/POST /admin/notebooks, GET/PUT/DELETE /admin/notebooks/:id, POST /admin/notebooks/:id/move - Manage nested notebooks (auth required)",
+            "tags": "GET /admin/tags, PUT /admin/tags/:name, POST /admin/tags/merge - List, rename and merge tags (auth required)",
+            "stats": "GET /admin/stats - Get user statistics (auth required)",
+            "moderation": "PUT/DELETE /admin/moderation/contents/:id - Moderate any public note (admin role)",
+            "global_stats": "GET /admin/moderation/stats - Statistics across all users (admin role)",
+            "user_roles": "PUT /admin/users/:username/role - Set a user's role (admin role)",
+            "password_change": "POST /account/password - Change password with the current one (auth required)",
+            "password_reset": "POST /admin/users/:username/password-reset issues a token; POST /password/reset redeems it"
         }
     }))
 }
@@ -0,0 +1,266 @@
+use crate::models::Note;
+use ammonia::Builder;
+use pulldown_cmark::{CowStr, Event, Options, Parser, html};
+use serde::Deserialize;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    sync::{Arc, LazyLock, Mutex},
+};
+
+/// Rendered notes kept in memory; the cache is cleared once it grows past this
+const CACHE_CAPACITY: usize = 5_000;
+
+/// Sanitizer for rendered Markdown: ammonia's defaults plus what the GFM
+/// extensions emit (task list checkboxes, table alignment, code languages)
+static SANITIZER: LazyLock<Builder<'static>> = LazyLock::new(|| {
+    let mut builder = Builder::default();
+    builder
+        .add_tags(["input"])
+        .add_tag_attributes("input", ["type", "checked", "disabled"])
+        .add_tag_attributes("code", ["class"])
+        .add_tag_attributes("th", ["style"])
+        .add_tag_attributes("td", ["style"])
+        .attribute_filter(|element, attribute, value| match (element, attribute) {
+            ("input", "type") => (value == "checkbox").then_some(Cow::Borrowed(value)),
+            ("code", "class") => is_language_class(value).then_some(Cow::Borrowed(value)),
+            (_, "style") => matches!(
+                value,
+                "text-align: left" | "text-align: center" | "text-align: right"
+            )
+            .then_some(Cow::Borrowed(value)),
+            _ => Some(Cow::Borrowed(value)),
+        });
+    builder
+});
+
+/// `language-<name>` as emitted for fenced code blocks, for client-side highlighters
+fn is_language_class(value: &str) -> bool {
+    value.strip_prefix("language-").is_some_and(|language| {
+        !language.is_empty()
+            && language
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "_+#.-".contains(c))
+    })
+}
+
+/// Turn `<input` tags in raw HTML written by the author into text, so the only
+/// inputs left are the disabled checkboxes pulldown-cmark emits for task lists
+fn escape_raw_inputs(raw: CowStr<'_>) -> CowStr<'_> {
+    let lower = raw.to_ascii_lowercase();
+    if !lower.contains("<input") {
+        return raw;
+    }
+
+    let mut escaped = String::with_capacity(raw.len() + 8);
+    let mut position = 0;
+    for (start, _) in lower.match_indices("<input") {
+        escaped.push_str(&raw[position..start]);
+        escaped.push_str("&lt;");
+        position = start + 1;
+    }
+    escaped.push_str(&raw[position..]);
+    escaped.into()
+}
+
+/// Render note content (CommonMark with GFM tables, task lists and
+/// strikethrough) to HTML that is safe to insert into a page
+pub fn render(content: &str) -> String {
+    let options =
+        Options::ENABLE_TABLES | Options::ENABLE_TASKLISTS | Options::ENABLE_STRIKETHROUGH;
+    let events = Parser::new_ext(content, options).map(|event| match event {
+        Event::Html(raw) => Event::Html(escape_raw_inputs(raw)),
+        Event::InlineHtml(raw) => Event::InlineHtml(escape_raw_inputs(raw)),
+        event => event,
+    });
+    let mut unsafe_html = String::with_capacity(content.len() * 3 / 2);
+    html::push_html(&mut unsafe_html, events);
+    SANITIZER.clean(&unsafe_html).to_string()
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentFormat {
+    /// Only the raw Markdown in `content`
+    #[default]
+    Markdown,
+    /// Also `content_html`, the rendered and sanitized content
+    Html,
+}
+
+/// `?format=` accepted by the endpoints that return notes
+#[derive(Debug, Default, Deserialize)]
+pub struct FormatParams {
+    #[serde(default)]
+    pub format: ContentFormat,
+}
+
+#[derive(Clone)]
+struct CachedRender {
+    updated_at: chrono::DateTime<chrono::Utc>,
+    version: i32,
+    html: Arc<str>,
+}
+
+/// Rendered HTML per note, valid while the note's `updated_at` and `version`
+/// are unchanged. The version guards against two edits in the same second.
+#[derive(Clone, Default)]
+pub struct RenderCache {
+    entries: Arc<Mutex<HashMap<i64, CachedRender>>>,
+}
+
+impl RenderCache {
+    /// Rendered content of `note`, from the cache when it is still current
+    pub fn html_for(&self, note: &Note) -> Arc<str> {
+        {
+            let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(cached) = entries.get(&note.id)
+                && cached.updated_at == note.updated_at
+                && cached.version == note.version
+            {
+                return cached.html.clone();
+            }
+        }
+
+        let html: Arc<str> = render(&note.content).into();
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() >= CACHE_CAPACITY && !entries.contains_key(&note.id) {
+            entries.clear();
+        }
+        entries.insert(
+            note.id,
+            CachedRender {
+                updated_at: note.updated_at,
+                version: note.version,
+                html: html.clone(),
+            },
+        );
+        html
+    }
+
+    /// Fill in `content_html` on each note when HTML was requested
+    pub fn apply(&self, format: ContentFormat, notes: &mut [Note]) {
+        if format != ContentFormat::Html {
+            return;
+        }
+        for note in notes {
+            note.content_html = Some(self.html_for(note).to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::Query;
+
+    fn note(id: i64, content: &str, version: i32) -> Note {
+        let now = chrono::Utc::now();
+        Note {
+            id,
+            title: "Note".to_string(),
+            content: content.to_string(),
+            user: "alice".to_string(),
+            is_public: false,
+            notebook_id: None,
+            version,
+            created_at: now,
+            updated_at: now,
+            tags: Vec::new(),
+            content_html: None,
+        }
+    }
+
+    #[test]
+    fn test_render_commonmark_and_gfm() {
+        assert_eq!(
+            render("# Title\n\n**bold**"),
+            "<h1>Title</h1>\n<p><strong>bold</strong></p>\n"
+        );
+        assert_eq!(render("~~gone~~"), "<p><del>gone</del></p>\n");
+
+        let table = render("| a | b |\n|:-|-:|\n| 1 | 2 |\n");
+        assert!(table.contains("<table>"));
+        assert!(table.contains("<th style=\"text-align: left\">a</th>"));
+        assert!(table.contains("<td style=\"text-align: right\">2</td>"));
+
+        let tasks = render("- [x] done\n- [ ] todo\n");
+        assert!(tasks.contains("<input disabled=\"\" type=\"checkbox\" checked=\"\">"));
+        assert!(tasks.contains("<input disabled=\"\" type=\"checkbox\">"));
+    }
+
+    #[test]
+    fn test_render_code_language_class() {
+        assert_eq!(
+            render("```rust\nfn main() {}\n```\n"),
+            "<pre><code class=\"language-rust\">fn main() {}\n</code></pre>\n"
+        );
+        assert_eq!(
+            render("```c++\nint x;\n```\n"),
+            "<pre><code class=\"language-c++\">int x;\n</code></pre>\n"
+        );
+
+        assert!(is_language_class("language-objective-c"));
+        assert!(!is_language_class("language-"));
+        assert!(!is_language_class("language-a\"b"));
+        assert!(!is_language_class("hljs"));
+    }
+
+    #[test]
+    fn test_render_strips_unsafe_html() {
+        let html = render(
+            "<script>alert(1)</script>\n\n[x](javascript:alert(1)) <img src=x onerror=alert(1)>",
+        );
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("javascript:"));
+        assert!(!html.contains("onerror"));
+
+        let html = render(
+            "<input type=\"text\" value=\"x\"> <INPUT> <td style=\"color: red\">\n\n\
+             <div><input type=\"checkbox\"></div>\n\n- [ ] todo\n",
+        );
+        assert!(!html.contains("color"));
+        let inputs: Vec<&str> = html
+            .match_indices("<input")
+            .map(|(start, _)| &html[start..start + html[start..].find('>').unwrap()])
+            .collect();
+        assert_eq!(inputs, vec!["<input disabled=\"\" type=\"checkbox\""]);
+    }
+
+    #[test]
+    fn test_render_cache_follows_updates() {
+        let cache = RenderCache::default();
+        let mut first = note(1, "*one*", 1);
+        assert_eq!(&*cache.html_for(&first), "<p><em>one</em></p>\n");
+
+        // Same stamp: served from the cache even though the content differs
+        first.content = "*changed*".to_string();
+        assert_eq!(&*cache.html_for(&first), "<p><em>one</em></p>\n");
+
+        first.version = 2;
+        assert_eq!(&*cache.html_for(&first), "<p><em>changed</em></p>\n");
+
+        let mut notes = [note(2, "`x`", 1)];
+        cache.apply(ContentFormat::Markdown, &mut notes);
+        assert_eq!(notes[0].content_html, None);
+        cache.apply(ContentFormat::Html, &mut notes);
+        assert_eq!(
+            notes[0].content_html.as_deref(),
+            Some("<p><code>x</code></p>\n")
+        );
+    }
+
+    #[test]
+    fn test_format_params_from_query() {
+        let uri = "/contents/1?format=html".parse().unwrap();
+        let Query(params) = Query::<FormatParams>::try_from_uri(&uri).unwrap();
+        assert_eq!(params.format, ContentFormat::Html);
+
+        let uri = "/contents?limit=5".parse().unwrap();
+        let Query(params) = Query::<FormatParams>::try_from_uri(&uri).unwrap();
+        assert_eq!(params.format, ContentFormat::Markdown);
+
+        let uri = "/contents?format=pdf".parse().unwrap();
+        assert!(Query::<FormatParams>::try_from_uri(&uri).is_err());
+    }
+}
@@ -8,8 +8,37 @@ pub struct Note {
     pub content: String,
     pub user: String,
     pub is_public: bool,
+    pub notebook_id: Option<i64>,
+    /// Incremented on every change; the note's `ETag`
+    pub version: i32,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
+    /// Filled in from `note_tags` after the row is loaded
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Rendered `content`, only present when requested with `?format=html`
+    #[sqlx(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_html: Option<String>,
+}
+
+impl crate::pagination::Paginated for Note {
+    fn id(&self) -> i64 {
+        self.id
+    }
+
+    fn created_at(&self) -> chrono::DateTime<chrono::Utc> {
+        self.created_at
+    }
+
+    fn updated_at(&self) -> chrono::DateTime<chrono::Utc> {
+        self.updated_at
+    }
+
+    fn title(&self) -> &str {
+        &self.title
+    }
 }
 
 #[derive(Deserialize)]
@@ -17,6 +46,10 @@ pub struct CreateNoteRequest {
     pub title: String,
     pub content: String,
     pub is_public: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Notebook to file the note in; unfiled when absent
+    pub notebook_id: Option<i64>,
 }
 
 #[derive(Deserialize)]
@@ -24,4 +57,97 @@ pub struct UpdateNoteRequest {
     pub title: Option<String>,
     pub content: Option<String>,
     pub is_public: Option<bool>,
+    /// Replaces the note's tags when present; `[]` removes them all
+    pub tags: Option<Vec<String>>,
+}
+
+#[derive(FromRow, Serialize)]
+pub struct Notebook {
+    pub id: i64,
+    pub name: String,
+    /// `None` for a top-level notebook
+    pub parent_id: Option<i64>,
+    /// Notes filed directly in this notebook (not in its descendants)
+    pub note_count: i64,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A note in the trash
+#[derive(FromRow, Serialize)]
+pub struct TrashedNote {
+    pub id: i64,
+    pub title: String,
+    pub content: String,
+    pub is_public: bool,
+    pub notebook_id: Option<i64>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+    pub deleted_at: chrono::DateTime<chrono::Utc>,
+    /// When the note will be purged; `None` if automatic purging is disabled
+    #[sqlx(skip)]
+    pub purge_after: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// A past version of a note, archived when the note was edited
+#[derive(FromRow, Serialize)]
+pub struct NoteRevision {
+    /// Per-note sequence number, starting at 1
+    pub revision: i32,
+    pub title: String,
+    pub content: String,
+    /// When this version was written
+    pub edited_at: chrono::DateTime<chrono::Utc>,
+    /// When it was replaced and archived
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(FromRow, Serialize)]
+pub struct RevisionSummary {
+    pub revision: i32,
+    pub title: String,
+    /// Length of the archived content in characters
+    pub content_length: i64,
+    pub edited_at: chrono::DateTime<chrono::Utc>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(FromRow, Serialize)]
+pub struct User {
+    pub id: i64,
+    pub username: String,
+    #[serde(skip_serializing)]
+    pub password_hash: String,
+    pub is_active: bool,
+    pub role: String,
+    #[serde(skip_serializing)]
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
+    #[serde(skip_serializing)]
+    pub totp_last_step: Option<i64>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(FromRow, Serialize)]
+pub struct PersonalAccessToken {
+    pub id: i64,
+    pub name: String,
+    pub token_prefix: String,
+    pub scopes: String,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_used_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(FromRow, Serialize)]
+pub struct Session {
+    pub id: String,
+    pub ip_address: String,
+    pub user_agent: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub last_used_at: chrono::DateTime<chrono::Utc>,
+    /// Whether this is the session making the request
+    #[sqlx(skip)]
+    pub current: bool,
 }
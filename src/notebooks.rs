@@ -0,0 +1,377 @@
+use crate::{extractors::AuthUser, logging, models::Notebook, state::AppState};
+use axum::{
+    extract::{Json, Path, State},
+    http::StatusCode,
+    response::Json as ResponseJson,
+};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::{MySqlConnection, MySqlPool};
+use std::collections::HashMap;
+
+/// Longest notebook name accepted
+pub const MAX_NAME_LEN: usize = 100;
+
+const NOTEBOOK_COLUMNS: &str = "SELECT b.id, b.name, b.parent_id, \
+     (SELECT COUNT(*) FROM notes n WHERE n.notebook_id = b.id AND n.deleted_at IS NULL) \
+     AS note_count, \
+     b.created_at, b.updated_at FROM notebooks b";
+
+#[derive(Deserialize)]
+pub struct CreateNotebookRequest {
+    pub name: String,
+    /// Parent notebook; top level when absent
+    pub parent_id: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct RenameNotebookRequest {
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct MoveNotebookRequest {
+    /// New parent; `null` moves the notebook to the top level
+    pub parent_id: Option<i64>,
+}
+
+fn validate_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+        return Err(format!(
+            "Notebook names must be between 1 and {} characters",
+            MAX_NAME_LEN
+        ));
+    }
+    Ok(name.to_string())
+}
+
+fn bad_request(message: &str) -> (StatusCode, ResponseJson<serde_json::Value>) {
+    (
+        StatusCode::BAD_REQUEST,
+        ResponseJson(json!({ "error": message })),
+    )
+}
+
+/// Whether `notebook_id` is `ancestor_id` or lies somewhere below it.
+///
+/// `parents` maps each of the user's notebooks to its parent. The walk is
+/// bounded by the number of notebooks so a corrupted cycle cannot loop forever.
+fn is_within(parents: &HashMap<i64, Option<i64>>, notebook_id: i64, ancestor_id: i64) -> bool {
+    let mut current = Some(notebook_id);
+    for _ in 0..=parents.len() {
+        match current {
+            Some(id) if id == ancestor_id => return true,
+            Some(id) => current = parents.get(&id).copied().flatten(),
+            None => return false,
+        }
+    }
+    false
+}
+
+/// Whether `notebook_id` exists and belongs to `username`.
+///
+/// Inside a transaction the notebook stays share-locked until it ends, so it cannot
+/// be deleted before a note filed into it is written.
+pub async fn is_owned(
+    conn: &mut MySqlConnection,
+    username: &str,
+    notebook_id: i64,
+) -> Result<bool, sqlx::Error> {
+    let found: Option<i64> =
+        sqlx::query_scalar("SELECT id FROM notebooks WHERE id = ? AND user = ? LOCK IN SHARE MODE")
+            .bind(notebook_id)
+            .bind(username)
+            .fetch_optional(conn)
+            .await?;
+    Ok(found.is_some())
+}
+
+async fn fetch_notebook(
+    db: &MySqlPool,
+    username: &str,
+    id: i64,
+) -> Result<Option<Notebook>, sqlx::Error> {
+    sqlx::query_as::<_, Notebook>(&format!(
+        "{} WHERE b.id = ? AND b.user = ?",
+        NOTEBOOK_COLUMNS
+    ))
+    .bind(id)
+    .bind(username)
+    .fetch_optional(db)
+    .await
+}
+
+/// List the user's notebooks as a flat list; `parent_id` links them into a tree
+pub async fn list_notebooks(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<ResponseJson<Vec<Notebook>>, StatusCode> {
+    logging::log_api_request("GET", "/admin/notebooks", 200);
+
+    sqlx::query_as::<_, Notebook>(&format!(
+        "{} WHERE b.user = ? ORDER BY b.name, b.id",
+        NOTEBOOK_COLUMNS
+    ))
+    .bind(&auth.username)
+    .fetch_all(&*state.db)
+    .await
+    .map(ResponseJson)
+    .map_err(|e| {
+        logging::log_db_error("select", &e.to_string());
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+/// Get one of the user's notebooks
+pub async fn get_notebook(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<ResponseJson<Notebook>, StatusCode> {
+    match fetch_notebook(&state.db, &auth.username, id).await {
+        Ok(Some(notebook)) => Ok(ResponseJson(notebook)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            logging::log_db_error("select", &e.to_string());
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Create a notebook, optionally inside another one
+pub async fn create_notebook(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(request): Json<CreateNotebookRequest>,
+) -> Result<(StatusCode, ResponseJson<serde_json::Value>), StatusCode> {
+    let username = auth.username;
+    let name = match validate_name(&request.name) {
+        Ok(name) => name,
+        Err(message) => return Ok(bad_request(&message)),
+    };
+
+    let result = async {
+        let mut conn = state.db.acquire().await?;
+        if let Some(parent_id) = request.parent_id
+            && !is_owned(&mut conn, &username, parent_id).await?
+        {
+            return Ok(None);
+        }
+
+        let id = sqlx::query(
+            "INSERT INTO notebooks (user, name, parent_id, created_at, updated_at)
+             VALUES (?, ?, ?, NOW(), NOW())",
+        )
+        .bind(&username)
+        .bind(&name)
+        .bind(request.parent_id)
+        .execute(&mut *conn)
+        .await?
+        .last_insert_id();
+        Ok::<_, sqlx::Error>(Some(id))
+    }
+    .await;
+
+    match result {
+        Ok(Some(id)) => {
+            logging::log_db_operation("insert", "notebooks");
+            Ok((
+                StatusCode::CREATED,
+                ResponseJson(json!({ "message": "Notebook created successfully", "id": id })),
+            ))
+        }
+        Ok(None) => Ok(bad_request("Parent notebook not found")),
+        Err(e) => {
+            logging::log_db_error("insert", &e.to_string());
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Rename a notebook
+pub async fn rename_notebook(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<i64>,
+    Json(request): Json<RenameNotebookRequest>,
+) -> Result<(StatusCode, ResponseJson<serde_json::Value>), StatusCode> {
+    let name = match validate_name(&request.name) {
+        Ok(name) => name,
+        Err(message) => return Ok(bad_request(&message)),
+    };
+
+    let result =
+        sqlx::query("UPDATE notebooks SET name = ?, updated_at = NOW() WHERE id = ? AND user = ?")
+            .bind(&name)
+            .bind(id)
+            .bind(&auth.username)
+            .execute(&*state.db)
+            .await
+            .map_err(|e| {
+                logging::log_db_error("update", &e.to_string());
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+
+    if result.rows_affected() == 0 {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    logging::log_db_operation("update", "notebooks");
+    Ok((
+        StatusCode::OK,
+        ResponseJson(json!({ "message": "Notebook updated successfully" })),
+    ))
+}
+
+/// Move a notebook, with everything below it, under another parent
+pub async fn move_notebook(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<i64>,
+    Json(request): Json<MoveNotebookRequest>,
+) -> Result<(StatusCode, ResponseJson<serde_json::Value>), StatusCode> {
+    let username = auth.username;
+
+    let result = async {
+        let mut tx = state.db.begin().await?;
+
+        // Lock the user's tree so concurrent moves cannot combine into a cycle
+        let parents: HashMap<i64, Option<i64>> = sqlx::query_as::<_, (i64, Option<i64>)>(
+            "SELECT id, parent_id FROM notebooks WHERE user = ? FOR UPDATE",
+        )
+        .bind(&username)
+        .fetch_all(&mut *tx)
+        .await?
+        .into_iter()
+        .collect();
+
+        if !parents.contains_key(&id) {
+            return Ok(Err(StatusCode::NOT_FOUND));
+        }
+        if let Some(parent_id) = request.parent_id {
+            if !parents.contains_key(&parent_id) {
+                return Ok(Ok(bad_request("Parent notebook not found")));
+            }
+            if is_within(&parents, parent_id, id) {
+                return Ok(Ok(bad_request(
+                    "A notebook cannot be moved into itself or one of its descendants",
+                )));
+            }
+        }
+
+        sqlx::query("UPDATE notebooks SET parent_id = ?, updated_at = NOW() WHERE id = ?")
+            .bind(request.parent_id)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(Ok((
+            StatusCode::OK,
+            ResponseJson(json!({ "message": "Notebook moved successfully" })),
+        )))
+    }
+    .await;
+
+    match result {
+        Ok(response) => {
+            if response.is_ok() {
+                logging::log_db_operation("update", "notebooks");
+            }
+            response
+        }
+        Err(e) => {
+            logging::log_db_error("update", &e.to_string());
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Delete a notebook. Its notes and child notebooks move up to its parent, so
+/// nothing inside is lost.
+pub async fn delete_notebook(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<(StatusCode, ResponseJson<serde_json::Value>), StatusCode> {
+    let username = auth.username;
+
+    let result = async {
+        let mut tx = state.db.begin().await?;
+        let parent_id: Option<Option<i64>> = sqlx::query_scalar(
+            "SELECT parent_id FROM notebooks WHERE id = ? AND user = ? FOR UPDATE",
+        )
+        .bind(id)
+        .bind(&username)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(parent_id) = parent_id else {
+            return Ok(false);
+        };
+
+        sqlx::query("UPDATE notebooks SET parent_id = ? WHERE parent_id = ? AND user = ?")
+            .bind(parent_id)
+            .bind(id)
+            .bind(&username)
+            .execute(&mut *tx)
+            .await?;
+        // A changed notebook is a change to the note, so stale ETags must stop matching
+        sqlx::query(
+            "UPDATE notes SET notebook_id = ?, version = version + 1
+             WHERE notebook_id = ? AND user = ?",
+        )
+        .bind(parent_id)
+        .bind(id)
+        .bind(&username)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM notebooks WHERE id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(true)
+    }
+    .await;
+
+    match result {
+        Ok(true) => {
+            logging::log_db_operation("delete", "notebooks");
+            Ok((
+                StatusCode::OK,
+                ResponseJson(json!({ "message": "Notebook deleted successfully" })),
+            ))
+        }
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            logging::log_db_error("delete", &e.to_string());
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_within() {
+        // 1 -> 2 -> 3, and 4 on its own
+        let parents = HashMap::from([(1, None), (2, Some(1)), (3, Some(2)), (4, None)]);
+
+        assert!(is_within(&parents, 3, 1));
+        assert!(is_within(&parents, 2, 2));
+        assert!(!is_within(&parents, 1, 3));
+        assert!(!is_within(&parents, 4, 1));
+
+        let cyclic = HashMap::from([(1, Some(2)), (2, Some(1))]);
+        assert!(!is_within(&cyclic, 1, 3));
+    }
+
+    #[test]
+    fn test_validate_name() {
+        assert_eq!(validate_name("  Projects ").unwrap(), "Projects");
+        assert!(validate_name(" ").is_err());
+        assert!(validate_name(&"x".repeat(MAX_NAME_LEN + 1)).is_err());
+    }
+}
@@ -0,0 +1,706 @@
+use crate::{
+    auth::{issue_session, set_session_cookies, user_role},
+    client_ip::ClientIp,
+    logging,
+    rbac::Role,
+    state::AppState,
+    users,
+    utils::{frontend_url, generate_opaque_token, hash_token},
+};
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode, decode_header, jwk::JwkSet};
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    env,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::OnceCell;
+
+/// How long a user has to finish signing in at the identity provider
+const PENDING_LOGIN_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Password hash stored for accounts created by OIDC login; never matches a password
+const NO_PASSWORD_HASH: &str = "!oidc";
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Which ID token claim becomes the local username (`notes.user`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UsernameClaim {
+    Sub,
+    Email,
+}
+
+/// Identity provider settings from OIDC_* environment variables
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub redirect_url: String,
+    pub scopes: String,
+    pub username_claim: UsernameClaim,
+    /// Create a local editor account on first login instead of rejecting unknown users
+    pub auto_create: bool,
+}
+
+impl OidcConfig {
+    /// `None` unless OIDC_ISSUER_URL, OIDC_CLIENT_ID and OIDC_REDIRECT_URL are all set
+    pub fn from_env() -> Option<Self> {
+        let issuer = env::var("OIDC_ISSUER_URL").ok().filter(|v| !v.is_empty())?;
+        let client_id = env::var("OIDC_CLIENT_ID").ok().filter(|v| !v.is_empty())?;
+        let redirect_url = env::var("OIDC_REDIRECT_URL")
+            .ok()
+            .filter(|v| !v.is_empty())?;
+
+        let username_claim = match env::var("OIDC_USERNAME_CLAIM").as_deref() {
+            Ok("email") => UsernameClaim::Email,
+            _ => UsernameClaim::Sub,
+        };
+
+        Some(OidcConfig {
+            issuer: issuer.trim_end_matches('/').to_string(),
+            client_id,
+            client_secret: env::var("OIDC_CLIENT_SECRET")
+                .ok()
+                .filter(|v| !v.is_empty()),
+            redirect_url,
+            scopes: env::var("OIDC_SCOPES").unwrap_or_else(|_| "openid email profile".to_string()),
+            username_claim,
+            auto_create: env::var("OIDC_AUTO_CREATE").is_ok_and(|v| v == "true"),
+        })
+    }
+}
+
+/// The parts of the discovery document we use
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+/// Claims read from a validated ID token
+#[derive(Debug, Deserialize)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub nonce: Option<String>,
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
+}
+
+impl IdTokenClaims {
+    /// Local username for these claims
+    pub fn username(&self, claim: UsernameClaim) -> Result<String, &'static str> {
+        match claim {
+            UsernameClaim::Sub => Ok(self.sub.clone()),
+            UsernameClaim::Email => {
+                // A missing claim is no proof the address belongs to this user
+                if self.email_verified != Some(true) {
+                    return Err("email address is not verified");
+                }
+                self.email
+                    .clone()
+                    .filter(|email| !email.is_empty())
+                    .ok_or("ID token has no email claim")
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+struct PendingLogin {
+    code_verifier: String,
+    nonce: String,
+    expires_at: Instant,
+}
+
+/// S256 PKCE challenge for a code verifier (RFC 7636)
+pub fn pkce_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+/// OIDC relying party: discovery, authorization requests and ID token validation
+pub struct OidcClient {
+    config: OidcConfig,
+    http: reqwest::Client,
+    metadata: OnceCell<ProviderMetadata>,
+    pending: Mutex<HashMap<String, PendingLogin>>,
+}
+
+impl OidcClient {
+    pub fn new(config: OidcConfig) -> Self {
+        OidcClient {
+            config,
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+            metadata: OnceCell::new(),
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Client configured from the environment, if OIDC is enabled
+    pub fn from_env() -> Option<Arc<Self>> {
+        OidcConfig::from_env().map(|config| Arc::new(Self::new(config)))
+    }
+
+    pub fn config(&self) -> &OidcConfig {
+        &self.config
+    }
+
+    /// Fetch and cache the provider's discovery document
+    pub async fn metadata(&self) -> Result<&ProviderMetadata, BoxError> {
+        self.metadata
+            .get_or_try_init(|| async {
+                let url = format!("{}/.well-known/openid-configuration", self.config.issuer);
+                let metadata: ProviderMetadata = self
+                    .http
+                    .get(&url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+
+                if metadata.issuer.trim_end_matches('/') != self.config.issuer {
+                    return Err(format!("discovery issuer mismatch: {}", metadata.issuer).into());
+                }
+                Ok(metadata)
+            })
+            .await
+    }
+
+    /// Start a login: remember PKCE verifier and nonce under a fresh `state`
+    /// and return the provider's authorization URL
+    pub async fn authorization_url(&self) -> Result<String, BoxError> {
+        let metadata = self.metadata().await?;
+
+        let state = generate_opaque_token();
+        let nonce = generate_opaque_token();
+        let code_verifier = generate_opaque_token();
+
+        let url = reqwest::Url::parse_with_params(
+            &metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", self.config.client_id.as_str()),
+                ("redirect_uri", self.config.redirect_url.as_str()),
+                ("scope", self.config.scopes.as_str()),
+                ("state", state.as_str()),
+                ("nonce", nonce.as_str()),
+                ("code_challenge", pkce_challenge(&code_verifier).as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )?;
+
+        let now = Instant::now();
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.retain(|_, login| login.expires_at > now);
+        pending.insert(
+            hash_token(&state),
+            PendingLogin {
+                code_verifier,
+                nonce,
+                expires_at: now + PENDING_LOGIN_TTL,
+            },
+        );
+
+        Ok(url.into())
+    }
+
+    /// Finish a login: exchange the code and validate the returned ID token.
+    ///
+    /// Each `state` can be used once.
+    pub async fn complete(&self, state: &str, code: &str) -> Result<IdTokenClaims, BoxError> {
+        let login = self
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&hash_token(state))
+            .filter(|login| login.expires_at > Instant::now())
+            .ok_or("unknown or expired state")?;
+
+        let metadata = self.metadata().await?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.config.redirect_url.as_str()),
+            ("client_id", self.config.client_id.as_str()),
+            ("code_verifier", login.code_verifier.as_str()),
+        ];
+        if let Some(secret) = &self.config.client_secret {
+            form.push(("client_secret", secret.as_str()));
+        }
+
+        let tokens: TokenResponse = self
+            .http
+            .post(&metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let jwks: JwkSet = self
+            .http
+            .get(&metadata.jwks_uri)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        self.validate_id_token(&tokens.id_token, &jwks, &metadata.issuer, &login.nonce)
+    }
+
+    /// Check signature, issuer, audience, expiry and nonce of an ID token
+    pub fn validate_id_token(
+        &self,
+        id_token: &str,
+        jwks: &JwkSet,
+        issuer: &str,
+        nonce: &str,
+    ) -> Result<IdTokenClaims, BoxError> {
+        let header = decode_header(id_token)?;
+
+        // Only asymmetric algorithms; an HMAC "key" from a JWKS would be public
+        if matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        ) {
+            return Err("ID token uses a symmetric algorithm".into());
+        }
+
+        let jwk = match &header.kid {
+            Some(kid) => jwks.find(kid),
+            None if jwks.keys.len() == 1 => jwks.keys.first(),
+            None => None,
+        }
+        .ok_or("no matching key in provider JWKS")?;
+        let key = DecodingKey::from_jwk(jwk)?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[issuer]);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+        let claims = decode::<IdTokenClaims>(id_token, &key, &validation)?.claims;
+
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err("ID token nonce mismatch".into());
+        }
+        Ok(claims)
+    }
+}
+
+fn not_configured() -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({ "error": "OIDC login is not configured" })),
+    )
+        .into_response()
+}
+
+fn provider_error() -> Response {
+    (
+        StatusCode::BAD_GATEWAY,
+        Json(json!({ "error": "Identity provider unavailable" })),
+    )
+        .into_response()
+}
+
+/// GET /login/oidc - redirect to the identity provider
+pub async fn start(State(state): State<AppState>) -> Response {
+    let Some(oidc) = &state.oidc else {
+        return not_configured();
+    };
+
+    match oidc.authorization_url().await {
+        Ok(url) => Redirect::to(&url).into_response(),
+        Err(e) => {
+            logging::log_security_event("oidc_discovery_failed", &e.to_string());
+            provider_error()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CallbackParams {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+}
+
+/// GET /login/oidc/callback - exchange the code and issue our own tokens
+pub async fn callback(
+    State(state): State<AppState>,
+    client_ip: ClientIp,
+    headers: HeaderMap,
+    Query(params): Query<CallbackParams>,
+) -> Response {
+    let Some(oidc) = &state.oidc else {
+        return not_configured();
+    };
+
+    let (Some(code), Some(login_state)) = (&params.code, &params.state) else {
+        logging::log_security_event(
+            "oidc_login_failed",
+            &format!(
+                "provider returned {} for {}",
+                params.error.as_deref().unwrap_or("no code"),
+                client_ip
+            ),
+        );
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(json!({ "error": "Login was not completed" })),
+        )
+            .into_response();
+    };
+
+    let claims = match oidc.complete(login_state, code).await {
+        Ok(claims) => claims,
+        Err(e) => {
+            logging::log_security_event("oidc_login_failed", &format!("{} from {}", e, client_ip));
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(json!({ "error": "Invalid OIDC login" })),
+            )
+                .into_response();
+        }
+    };
+
+    let username = match claims.username(oidc.config().username_claim) {
+        Ok(username) if username.len() <= 100 => username,
+        Ok(_) => {
+            logging::log_security_event("oidc_login_failed", "username longer than 100 characters");
+            return (
+                StatusCode::FORBIDDEN,
+                Json(json!({ "error": "Account cannot be mapped" })),
+            )
+                .into_response();
+        }
+        Err(reason) => {
+            logging::log_security_event("oidc_login_failed", reason);
+            return (
+                StatusCode::FORBIDDEN,
+                Json(json!({ "error": "Account cannot be mapped" })),
+            )
+                .into_response();
+        }
+    };
+
+    let user = match users::find_by_username(&state.db, &username).await {
+        Ok(user) => user,
+        Err(e) => {
+            logging::log_db_error("select", &e.to_string());
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Internal server error" })),
+            )
+                .into_response();
+        }
+    };
+
+    let (role, totp_enabled) = match user {
+        // Only accounts created by OIDC login are linked. Any other account with this
+        // name is protected by a password, which the identity does not prove.
+        Some(user) if user.password_hash != NO_PASSWORD_HASH => {
+            logging::log_security_event(
+                "oidc_login_rejected",
+                &format!("{} matches an account not created by OIDC", username),
+            );
+            return (
+                StatusCode::FORBIDDEN,
+                Json(json!({ "error": "Account cannot be mapped" })),
+            )
+                .into_response();
+        }
+        Some(user) if user.is_active => (user_role(&user), user.totp_enabled),
+        Some(_) => {
+            logging::log_auth_failure(&username, "account disabled (oidc)");
+            return (
+                StatusCode::FORBIDDEN,
+                Json(json!({ "error": "Account is disabled" })),
+            )
+                .into_response();
+        }
+        None if oidc.config().auto_create => {
+            if let Err(e) =
+                users::create_user(&state.db, &username, NO_PASSWORD_HASH, Role::Editor).await
+            {
+                logging::log_db_error("insert", &e.to_string());
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(json!({ "error": "Failed to create account" })),
+                )
+                    .into_response();
+            }
+            logging::log_security_event("oidc_account_created", &username);
+            (Role::Editor, false)
+        }
+        None => {
+            logging::log_auth_failure(&username, "no local account (oidc)");
+            return (
+                StatusCode::FORBIDDEN,
+                Json(json!({ "error": "No account for this identity" })),
+            )
+                .into_response();
+        }
+    };
+
+    // 2FA applies here as on /login: the frontend asks for the code and finishes
+    // the login through /login/2fa
+    if totp_enabled {
+        let mfa_token = state.mfa_challenges.create(&username);
+        return frontend_redirect(&[("mfa_token", &mfa_token), ("user", &username)]);
+    }
+
+    logging::log_security_event("oidc_login", &format!("{} from {}", username, client_ip));
+
+    // The callback is a top-level browser navigation, so start a cookie session and
+    // send the browser back to the frontend
+    match issue_session(&state, &username, role, client_ip, &headers).await {
+        Ok(tokens) => {
+            let csrf_token = generate_opaque_token();
+            let mut response =
+                frontend_redirect(&[("csrf_token", &csrf_token), ("user", &username)]);
+            set_session_cookies(&mut response, &tokens, &csrf_token);
+            response
+        }
+        Err(response) => response.into_response(),
+    }
+}
+
+/// Redirect to FRONTEND_URL with `params` in the fragment, which browsers never send
+/// to a server
+fn frontend_redirect(params: &[(&str, &str)]) -> Response {
+    let fragment = reqwest::Url::parse_with_params("about:blank", params)
+        .ok()
+        .and_then(|url| url.query().map(str::to_string))
+        .unwrap_or_default();
+
+    match reqwest::Url::parse(&frontend_url()) {
+        Ok(mut url) => {
+            url.set_fragment(Some(&fragment));
+            Redirect::to(url.as_str()).into_response()
+        }
+        Err(e) => {
+            logging::log_config_error("FRONTEND_URL", &e.to_string());
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Internal server error" })),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::JwtKeys;
+    use axum::{Form, Router, routing::get, routing::post};
+    use ed25519_dalek::pkcs8::{EncodePrivateKey, spki::der::pem::LineEnding};
+    use jsonwebtoken::{Header, encode};
+
+    /// Minimal identity provider: discovery, JWKS and a token endpoint that
+    /// checks the PKCE verifier before returning a signed ID token
+    async fn mock_issuer(nonce: Arc<Mutex<String>>, challenge: Arc<Mutex<String>>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+
+        let pem = ed25519_dalek::SigningKey::from_bytes(&[9; 32])
+            .to_pkcs8_pem(LineEnding::LF)
+            .unwrap()
+            .to_string();
+        let keys = Arc::new(JwtKeys::from_pems(&pem, &[]).unwrap());
+
+        let discovery = json!({
+            "issuer": issuer,
+            "authorization_endpoint": format!("{issuer}/authorize"),
+            "token_endpoint": format!("{issuer}/token"),
+            "jwks_uri": format!("{issuer}/jwks"),
+        });
+        let jwks = keys.jwks();
+        let token_issuer = issuer.clone();
+
+        let app = Router::new()
+            .route(
+                "/.well-known/openid-configuration",
+                get(move || async move { Json(discovery) }),
+            )
+            .route("/jwks", get(move || async move { Json(jwks) }))
+            .route(
+                "/token",
+                post(
+                    move |Form(form): Form<HashMap<String, String>>| async move {
+                        let verifier = form.get("code_verifier").cloned().unwrap_or_default();
+                        if form.get("code").map(String::as_str) != Some("good-code")
+                            || pkce_challenge(&verifier) != *challenge.lock().unwrap()
+                        {
+                            return (
+                                StatusCode::BAD_REQUEST,
+                                Json(json!({"error": "invalid_grant"})),
+                            );
+                        }
+
+                        let (kid, alg, key) = keys.signing_key();
+                        let mut header = Header::new(alg);
+                        header.kid = Some(kid.to_string());
+                        let now = chrono::Utc::now().timestamp();
+                        let claims = json!({
+                            "iss": token_issuer,
+                            "aud": "notepad",
+                            "sub": "user-123",
+                            "email": "alice@example.com",
+                            "email_verified": true,
+                            "nonce": *nonce.lock().unwrap(),
+                            "iat": now,
+                            "exp": now + 300,
+                        });
+                        let id_token = encode(&header, &claims, key).unwrap();
+                        (StatusCode::OK, Json(json!({ "id_token": id_token })))
+                    },
+                ),
+            );
+
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        issuer
+    }
+
+    fn client(issuer: &str, username_claim: UsernameClaim) -> OidcClient {
+        OidcClient::new(OidcConfig {
+            issuer: issuer.to_string(),
+            client_id: "notepad".to_string(),
+            client_secret: None,
+            redirect_url: "http://localhost:8000/login/oidc/callback".to_string(),
+            scopes: "openid email".to_string(),
+            username_claim,
+            auto_create: false,
+        })
+    }
+
+    fn query_param(url: &str, name: &str) -> String {
+        reqwest::Url::parse(url)
+            .unwrap()
+            .query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_pkce_challenge_rfc7636_vector() {
+        assert_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_login_against_mock_issuer() {
+        let nonce = Arc::new(Mutex::new(String::new()));
+        let challenge = Arc::new(Mutex::new(String::new()));
+        let issuer = mock_issuer(nonce.clone(), challenge.clone()).await;
+        let oidc = client(&issuer, UsernameClaim::Email);
+
+        let url = oidc.authorization_url().await.unwrap();
+        assert!(url.starts_with(&format!("{issuer}/authorize?")));
+        assert_eq!(query_param(&url, "code_challenge_method"), "S256");
+        *nonce.lock().unwrap() = query_param(&url, "nonce");
+        *challenge.lock().unwrap() = query_param(&url, "code_challenge");
+
+        let state = query_param(&url, "state");
+        let claims = oidc.complete(&state, "good-code").await.unwrap();
+        assert_eq!(claims.sub, "user-123");
+        assert_eq!(
+            claims.username(UsernameClaim::Email).unwrap(),
+            "alice@example.com"
+        );
+
+        // The state is single-use
+        assert!(oidc.complete(&state, "good-code").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_wrong_nonce_and_code() {
+        let nonce = Arc::new(Mutex::new("someone-else".to_string()));
+        let challenge = Arc::new(Mutex::new(String::new()));
+        let issuer = mock_issuer(nonce, challenge.clone()).await;
+        let oidc = client(&issuer, UsernameClaim::Sub);
+
+        let url = oidc.authorization_url().await.unwrap();
+        *challenge.lock().unwrap() = query_param(&url, "code_challenge");
+        assert!(
+            oidc.complete(&query_param(&url, "state"), "good-code")
+                .await
+                .is_err()
+        );
+
+        let url = oidc.authorization_url().await.unwrap();
+        assert!(
+            oidc.complete(&query_param(&url, "state"), "bad-code")
+                .await
+                .is_err()
+        );
+        assert!(oidc.complete("unknown-state", "good-code").await.is_err());
+    }
+
+    #[test]
+    fn test_unverified_email_is_rejected() {
+        let mut claims = IdTokenClaims {
+            sub: "user-123".to_string(),
+            nonce: None,
+            email: Some("alice@example.com".to_string()),
+            email_verified: Some(false),
+        };
+        assert_eq!(claims.username(UsernameClaim::Sub).unwrap(), "user-123");
+        assert!(claims.username(UsernameClaim::Email).is_err());
+
+        claims.email_verified = None;
+        assert!(claims.username(UsernameClaim::Email).is_err());
+
+        claims.email_verified = Some(true);
+        assert_eq!(
+            claims.username(UsernameClaim::Email).unwrap(),
+            "alice@example.com"
+        );
+    }
+
+    #[test]
+    fn test_frontend_redirect_puts_params_in_fragment() {
+        unsafe {
+            env::set_var("FRONTEND_URL", "https://notes.example.com");
+        }
+        let response = frontend_redirect(&[("csrf_token", "abc"), ("user", "a b@example.com")]);
+        unsafe {
+            env::remove_var("FRONTEND_URL");
+        }
+
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            response.headers()[axum::http::header::LOCATION],
+            "https://notes.example.com/#csrf_token=abc&user=a+b%40example.com"
+        );
+    }
+}
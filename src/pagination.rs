@@ -0,0 +1,474 @@
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{MySql, QueryBuilder};
+
+/// Page size when `limit` is not given
+pub const DEFAULT_LIMIT: u32 = 20;
+/// Largest page size a client can ask for
+pub const MAX_LIMIT: u32 = 100;
+
+/// Column a note listing is ordered by; `id` always breaks ties
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    #[default]
+    CreatedAt,
+    UpdatedAt,
+    Title,
+}
+
+impl SortField {
+    fn column(&self) -> &'static str {
+        match self {
+            SortField::CreatedAt => "created_at",
+            SortField::UpdatedAt => "updated_at",
+            SortField::Title => "title",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    Public,
+    Private,
+}
+
+/// How the `tags` filter combines several tags
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TagMode {
+    /// Notes carrying every listed tag
+    #[default]
+    All,
+    /// Notes carrying at least one listed tag
+    Any,
+}
+
+/// Query parameters accepted by the note listing endpoints
+#[derive(Debug, Default, Deserialize)]
+pub struct ListParams {
+    pub limit: Option<u32>,
+    /// `next_cursor` from the previous page
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub sort: SortField,
+    #[serde(default)]
+    pub order: SortOrder,
+    pub visibility: Option<Visibility>,
+    pub owner: Option<String>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub updated_after: Option<DateTime<Utc>>,
+    pub updated_before: Option<DateTime<Utc>>,
+    /// Comma-separated tag names
+    pub tags: Option<String>,
+    #[serde(default)]
+    pub tag_mode: TagMode,
+    pub notebook_id: Option<i64>,
+    /// With `notebook_id`, also include notes in notebooks nested below it
+    #[serde(default)]
+    pub include_descendants: bool,
+}
+
+impl ListParams {
+    /// Requested page size, clamped to 1..=MAX_LIMIT
+    pub fn limit(&self) -> u32 {
+        self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+    }
+
+    /// Tag names from the `tags` filter, trimmed and without case-insensitive duplicates
+    pub fn tag_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for name in self.tags.iter().flat_map(|tags| tags.split(',')) {
+            let name = name.trim();
+            if !name.is_empty() && !names.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+                names.push(name.to_string());
+            }
+        }
+        names
+    }
+}
+
+/// Position after the last row of a page: its sort value and ID.
+///
+/// The sort field and order are included so a cursor cannot be replayed against a
+/// listing ordered differently.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Cursor {
+    sort: SortField,
+    order: SortOrder,
+    value: CursorValue,
+    id: i64,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum CursorValue {
+    Time(DateTime<Utc>),
+    Text(String),
+}
+
+impl Cursor {
+    /// Opaque, URL-safe form returned to clients
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    /// Parse a cursor, rejecting malformed ones and ones from a different ordering
+    pub fn decode(cursor: &str, sort: SortField, order: SortOrder) -> Option<Cursor> {
+        let bytes = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+        let cursor: Cursor = serde_json::from_slice(&bytes).ok()?;
+
+        let value_matches = matches!(
+            (&cursor.value, sort),
+            (CursorValue::Text(_), SortField::Title)
+                | (
+                    CursorValue::Time(_),
+                    SortField::CreatedAt | SortField::UpdatedAt
+                )
+        );
+        (cursor.sort == sort && cursor.order == order && value_matches).then_some(cursor)
+    }
+}
+
+/// Rows a listing can be paginated over
+pub trait Paginated {
+    fn id(&self) -> i64;
+    fn created_at(&self) -> DateTime<Utc>;
+    fn updated_at(&self) -> DateTime<Utc>;
+    fn title(&self) -> &str;
+
+    fn cursor(&self, sort: SortField, order: SortOrder) -> Cursor {
+        let value = match sort {
+            SortField::CreatedAt => CursorValue::Time(self.created_at()),
+            SortField::UpdatedAt => CursorValue::Time(self.updated_at()),
+            SortField::Title => CursorValue::Text(self.title().to_string()),
+        };
+        Cursor {
+            sort,
+            order,
+            value,
+            id: self.id(),
+        }
+    }
+}
+
+/// Response envelope for paginated listings
+#[derive(Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Pass as `cursor` to fetch the next page; `null` on the last page
+    pub next_cursor: Option<String>,
+    /// Rows matching the filters across all pages
+    pub total_count: i64,
+}
+
+impl<T: Paginated> Page<T> {
+    /// Build a page from up to `limit + 1` rows; the extra row only signals that more follow
+    pub fn from_rows(mut rows: Vec<T>, params: &ListParams, total_count: i64) -> Self {
+        let limit = params.limit() as usize;
+        let next_cursor = if rows.len() > limit {
+            rows.truncate(limit);
+            rows.last()
+                .map(|row| row.cursor(params.sort, params.order).encode())
+        } else {
+            None
+        };
+
+        Page {
+            items: rows,
+            next_cursor,
+            total_count,
+        }
+    }
+}
+
+/// Append `AND ...` conditions for the visibility, owner, date range and tag filters
+pub fn push_filters(query: &mut QueryBuilder<'_, MySql>, params: &ListParams) {
+    match params.visibility {
+        Some(Visibility::Public) => {
+            query.push(" AND is_public = true");
+        }
+        Some(Visibility::Private) => {
+            query.push(" AND is_public = false");
+        }
+        None => {}
+    }
+
+    if let Some(owner) = &params.owner {
+        query.push(" AND user = ").push_bind(owner.clone());
+    }
+
+    let ranges = [
+        ("created_at >= ", params.created_after),
+        ("created_at < ", params.created_before),
+        ("updated_at >= ", params.updated_after),
+        ("updated_at < ", params.updated_before),
+    ];
+    for (condition, value) in ranges {
+        if let Some(value) = value {
+            query.push(" AND ").push(condition).push_bind(value);
+        }
+    }
+
+    let tags = params.tag_names();
+    if !tags.is_empty() {
+        query.push(
+            " AND id IN (SELECT nt.note_id FROM note_tags nt \
+             JOIN tags t ON t.id = nt.tag_id WHERE t.name IN (",
+        );
+        let count = tags.len() as i64;
+        let mut names = query.separated(", ");
+        for tag in tags {
+            names.push_bind(tag);
+        }
+        query.push(")");
+        if params.tag_mode == TagMode::All {
+            // Tags are unique per owner, so each name matches at most once per note
+            query
+                .push(" GROUP BY nt.note_id HAVING COUNT(*) = ")
+                .push_bind(count);
+        }
+        query.push(")");
+    }
+}
+
+/// Append the `AND ...` condition for the notebook filter. Notebooks are private,
+/// so only listings scoped to the notebooks' owner may use it.
+pub fn push_notebook_filter(query: &mut QueryBuilder<'_, MySql>, params: &ListParams) {
+    if let Some(notebook_id) = params.notebook_id {
+        if params.include_descendants {
+            query
+                .push(
+                    " AND notebook_id IN (WITH RECURSIVE tree (id) AS (\
+                     SELECT id FROM notebooks WHERE id = ",
+                )
+                .push_bind(notebook_id)
+                .push(
+                    " UNION ALL SELECT b.id FROM notebooks b JOIN tree ON b.parent_id = tree.id) \
+                     SELECT id FROM tree)",
+                );
+        } else {
+            query.push(" AND notebook_id = ").push_bind(notebook_id);
+        }
+    }
+}
+
+/// Append the keyset condition for `cursor`, the ORDER BY clause and the LIMIT
+/// (one extra row to detect a following page)
+pub fn push_page(query: &mut QueryBuilder<'_, MySql>, params: &ListParams, cursor: Option<Cursor>) {
+    let column = params.sort.column();
+    let (comparison, direction) = match params.order {
+        SortOrder::Asc => (" > ", "ASC"),
+        SortOrder::Desc => (" < ", "DESC"),
+    };
+
+    if let Some(cursor) = cursor {
+        // (column, id) strictly after the cursor in the requested order
+        query.push(" AND (").push(column).push(comparison);
+        push_cursor_value(query, &cursor.value);
+        query.push(" OR (").push(column).push(" = ");
+        push_cursor_value(query, &cursor.value);
+        query
+            .push(" AND id")
+            .push(comparison)
+            .push_bind(cursor.id)
+            .push("))");
+    }
+
+    query.push(format!(
+        " ORDER BY {} {}, id {} LIMIT ",
+        column, direction, direction
+    ));
+    query.push_bind(params.limit() + 1);
+}
+
+fn push_cursor_value(query: &mut QueryBuilder<'_, MySql>, value: &CursorValue) {
+    match value {
+        CursorValue::Time(time) => query.push_bind(*time),
+        CursorValue::Text(text) => query.push_bind(text.clone()),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::Query;
+
+    struct Row {
+        id: i64,
+        at: DateTime<Utc>,
+    }
+
+    impl Paginated for Row {
+        fn id(&self) -> i64 {
+            self.id
+        }
+        fn created_at(&self) -> DateTime<Utc> {
+            self.at
+        }
+        fn updated_at(&self) -> DateTime<Utc> {
+            self.at
+        }
+        fn title(&self) -> &str {
+            "title"
+        }
+    }
+
+    fn rows(count: i64) -> Vec<Row> {
+        (0..count)
+            .map(|id| Row {
+                id,
+                at: DateTime::from_timestamp(1_700_000_000 - id, 0).unwrap(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_list_params_from_query() {
+        let uri = "/contents?limit=500&sort=updated_at&order=asc&visibility=private\
+                   &created_after=2024-01-01T00:00:00Z"
+            .parse()
+            .unwrap();
+        let Query(params) = Query::<ListParams>::try_from_uri(&uri).unwrap();
+        assert_eq!(params.limit(), MAX_LIMIT);
+        assert_eq!(params.sort, SortField::UpdatedAt);
+        assert_eq!(params.order, SortOrder::Asc);
+        assert_eq!(params.visibility, Some(Visibility::Private));
+        assert!(params.created_after.is_some());
+        assert!(params.tag_names().is_empty());
+
+        let defaults = ListParams::default();
+        assert_eq!(defaults.limit(), DEFAULT_LIMIT);
+        assert_eq!(defaults.sort, SortField::CreatedAt);
+        assert_eq!(defaults.order, SortOrder::Desc);
+    }
+
+    #[test]
+    fn test_cursor_round_trip_and_ordering_check() {
+        let row = &rows(1)[0];
+        let encoded = row.cursor(SortField::CreatedAt, SortOrder::Desc).encode();
+
+        let decoded = Cursor::decode(&encoded, SortField::CreatedAt, SortOrder::Desc).unwrap();
+        assert_eq!(decoded, row.cursor(SortField::CreatedAt, SortOrder::Desc));
+
+        assert!(Cursor::decode(&encoded, SortField::CreatedAt, SortOrder::Asc).is_none());
+        assert!(Cursor::decode(&encoded, SortField::Title, SortOrder::Desc).is_none());
+        assert!(Cursor::decode("not-a-cursor", SortField::CreatedAt, SortOrder::Desc).is_none());
+    }
+
+    #[test]
+    fn test_page_from_rows() {
+        let params = ListParams {
+            limit: Some(2),
+            ..Default::default()
+        };
+
+        let page = Page::from_rows(rows(3), &params, 7);
+        assert_eq!(page.items.len(), 2);
+        assert_eq!(page.total_count, 7);
+        let cursor = Cursor::decode(
+            page.next_cursor.as_deref().unwrap(),
+            SortField::CreatedAt,
+            SortOrder::Desc,
+        )
+        .unwrap();
+        assert_eq!(cursor.id, 1);
+
+        let last = Page::from_rows(rows(2), &params, 2);
+        assert_eq!(last.items.len(), 2);
+        assert!(last.next_cursor.is_none());
+    }
+
+    #[test]
+    fn test_query_building() {
+        let params = ListParams {
+            limit: Some(10),
+            order: SortOrder::Asc,
+            visibility: Some(Visibility::Public),
+            owner: Some("alice".to_string()),
+            created_after: DateTime::from_timestamp(0, 0),
+            ..Default::default()
+        };
+        let cursor = rows(1)[0].cursor(SortField::CreatedAt, SortOrder::Asc);
+
+        let mut query = QueryBuilder::<MySql>::new("SELECT id FROM notes WHERE 1 = 1");
+        push_filters(&mut query, &params);
+        push_page(&mut query, &params, Some(cursor));
+
+        assert_eq!(
+            query.sql(),
+            "SELECT id FROM notes WHERE 1 = 1 AND is_public = true AND user = ? \
+             AND created_at >= ? AND (created_at > ? OR (created_at = ? AND id > ?)) \
+             ORDER BY created_at ASC, id ASC LIMIT ?"
+        );
+    }
+
+    #[test]
+    fn test_tag_filter() {
+        let mut params = ListParams {
+            tags: Some(" work, ideas,,Work ".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(params.tag_names(), vec!["work", "ideas"]);
+
+        let mut query = QueryBuilder::<MySql>::new("SELECT id FROM notes WHERE 1 = 1");
+        push_filters(&mut query, &params);
+        assert_eq!(
+            query.sql(),
+            "SELECT id FROM notes WHERE 1 = 1 AND id IN (SELECT nt.note_id FROM note_tags nt \
+             JOIN tags t ON t.id = nt.tag_id WHERE t.name IN (?, ?) \
+             GROUP BY nt.note_id HAVING COUNT(*) = ?)"
+        );
+
+        params.tag_mode = TagMode::Any;
+        let mut query = QueryBuilder::<MySql>::new("SELECT id FROM notes WHERE 1 = 1");
+        push_filters(&mut query, &params);
+        assert_eq!(
+            query.sql(),
+            "SELECT id FROM notes WHERE 1 = 1 AND id IN (SELECT nt.note_id FROM note_tags nt \
+             JOIN tags t ON t.id = nt.tag_id WHERE t.name IN (?, ?))"
+        );
+    }
+
+    #[test]
+    fn test_notebook_filter() {
+        let uri = "/admin/contents?notebook_id=3&include_descendants=true"
+            .parse()
+            .unwrap();
+        let Query(mut params) = Query::<ListParams>::try_from_uri(&uri).unwrap();
+        assert_eq!(params.notebook_id, Some(3));
+        assert!(params.include_descendants);
+
+        let mut query = QueryBuilder::<MySql>::new("SELECT id FROM notes WHERE 1 = 1");
+        push_filters(&mut query, &params);
+        assert_eq!(query.sql(), "SELECT id FROM notes WHERE 1 = 1");
+
+        let mut query = QueryBuilder::<MySql>::new("SELECT id FROM notes WHERE 1 = 1");
+        push_notebook_filter(&mut query, &params);
+        assert_eq!(
+            query.sql(),
+            "SELECT id FROM notes WHERE 1 = 1 AND notebook_id IN (WITH RECURSIVE tree (id) AS (\
+             SELECT id FROM notebooks WHERE id = ? UNION ALL SELECT b.id FROM notebooks b \
+             JOIN tree ON b.parent_id = tree.id) SELECT id FROM tree)"
+        );
+
+        params.include_descendants = false;
+        let mut query = QueryBuilder::<MySql>::new("SELECT id FROM notes WHERE 1 = 1");
+        push_notebook_filter(&mut query, &params);
+        assert_eq!(
+            query.sql(),
+            "SELECT id FROM notes WHERE 1 = 1 AND notebook_id = ?"
+        );
+    }
+}
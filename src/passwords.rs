@@ -0,0 +1,314 @@
+use crate::{
+    access_tokens,
+    auth::validate_password,
+    client_ip::ClientIp,
+    extractors::SessionUser,
+    logging, revocation,
+    state::AppState,
+    users,
+    utils::{generate_opaque_token, hash_password, hash_token, verify_password},
+};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::Row;
+use std::env;
+
+/// Reset token lifetime in minutes, from PASSWORD_RESET_TTL_MINUTES (default 60)
+fn reset_ttl_minutes() -> i64 {
+    env::var("PASSWORD_RESET_TTL_MINUTES")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|minutes| *minutes > 0)
+        .unwrap_or(60)
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+/// Change the caller's password; requires the current one
+pub async fn change_password(
+    State(state): State<AppState>,
+    client_ip: ClientIp,
+    SessionUser(auth): SessionUser,
+    Json(req): Json<ChangePasswordRequest>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let user = match users::find_by_username(&state.db, &auth.username).await {
+        Ok(Some(user)) if user.is_active => user,
+        Ok(_) => {
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(json!({ "error": "Unauthorized" })),
+            );
+        }
+        Err(e) => {
+            logging::log_db_error("select", &e.to_string());
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Internal server error" })),
+            );
+        }
+    };
+
+    if !verify_password(&user.password_hash, &req.current_password) {
+        logging::log_security_event(
+            "password_change_rejected",
+            &format!(
+                "user {} from {} gave a wrong current password",
+                user.username, client_ip
+            ),
+        );
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": "Current password is incorrect" })),
+        );
+    }
+
+    if let Err(message) = validate_password(&req.new_password) {
+        return (StatusCode::BAD_REQUEST, Json(json!({ "error": message })));
+    }
+
+    let password_hash = match hash_password(&req.new_password) {
+        Ok(hash) => hash,
+        Err(e) => {
+            logging::log_auth_error("password hashing", &e.to_string());
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Failed to change password" })),
+            );
+        }
+    };
+
+    // The new hash and the session and token revocation commit together, so the
+    // password never changes while old sessions or access tokens stay valid
+    let result = async {
+        let mut tx = state.db.begin().await?;
+        users::set_password_hash(&mut *tx, &user.username, &password_hash).await?;
+        let cutoff = revocation::revoke_all_in(&mut tx, &user.username).await?;
+        access_tokens::revoke_all_in(&mut tx, &user.username).await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(cutoff)
+    }
+    .await;
+
+    let cutoff = match result {
+        Ok(cutoff) => cutoff,
+        Err(e) => {
+            logging::log_db_error("update", &e.to_string());
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Failed to change password" })),
+            );
+        }
+    };
+
+    state.revocations.apply_user_cutoff(&user.username, cutoff);
+    logging::log_security_event("password_changed", &user.username);
+
+    (
+        StatusCode::OK,
+        Json(json!({ "message": "Password changed. Please log in again." })),
+    )
+}
+
+/// Issue a single-use reset token for another account (admin only).
+///
+/// The account's sessions and personal access tokens are revoked immediately and
+/// any earlier unused reset tokens for it stop working.
+pub async fn create_reset_token(
+    State(state): State<AppState>,
+    SessionUser(auth): SessionUser,
+    Path(username): Path<String>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    match users::find_by_username(&state.db, &username).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({ "error": "User not found" })),
+            );
+        }
+        Err(e) => {
+            logging::log_db_error("select", &e.to_string());
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Internal server error" })),
+            );
+        }
+    }
+
+    let token = generate_opaque_token();
+    let expires_at = chrono::Utc::now() + chrono::Duration::minutes(reset_ttl_minutes());
+
+    let result = async {
+        let mut tx = state.db.begin().await?;
+        sqlx::query(
+            "UPDATE password_reset_tokens SET used_at = NOW()
+             WHERE user = ? AND used_at IS NULL",
+        )
+        .bind(&username)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "INSERT INTO password_reset_tokens (user, token_hash, created_by, expires_at)
+             VALUES (?, ?, ?, ?)",
+        )
+        .bind(&username)
+        .bind(hash_token(&token))
+        .bind(&auth.username)
+        .bind(expires_at)
+        .execute(&mut *tx)
+        .await?;
+        let cutoff = revocation::revoke_all_in(&mut tx, &username).await?;
+        access_tokens::revoke_all_in(&mut tx, &username).await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(cutoff)
+    }
+    .await;
+
+    let cutoff = match result {
+        Ok(cutoff) => cutoff,
+        Err(e) => {
+            logging::log_db_error("insert", &e.to_string());
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Failed to create reset token" })),
+            );
+        }
+    };
+
+    state.revocations.apply_user_cutoff(&username, cutoff);
+    logging::log_security_event(
+        "password_reset_issued",
+        &format!(
+            "admin {} issued a reset token for {}",
+            auth.username, username
+        ),
+    );
+
+    (
+        StatusCode::CREATED,
+        Json(json!({
+            "username": username,
+            "reset_token": token,
+            "expires_at": expires_at
+        })),
+    )
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    pub new_password: String,
+}
+
+/// Set a new password with a reset token issued by an admin
+pub async fn reset_password(
+    State(state): State<AppState>,
+    client_ip: ClientIp,
+    Json(req): Json<ResetPasswordRequest>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    if let Err(message) = validate_password(&req.new_password) {
+        return (StatusCode::BAD_REQUEST, Json(json!({ "error": message })));
+    }
+
+    let password_hash = match hash_password(&req.new_password) {
+        Ok(hash) => hash,
+        Err(e) => {
+            logging::log_auth_error("password hashing", &e.to_string());
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Failed to reset password" })),
+            );
+        }
+    };
+
+    // Claim the token and set the password together so a token works exactly once
+    let result = async {
+        let mut tx = state.db.begin().await?;
+        let row = sqlx::query(
+            "SELECT id, user FROM password_reset_tokens
+             WHERE token_hash = ? AND used_at IS NULL AND expires_at > NOW()
+             FOR UPDATE",
+        )
+        .bind(hash_token(&req.token))
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let id: i64 = row.try_get("id")?;
+        let username: String = row.try_get("user")?;
+
+        sqlx::query("UPDATE password_reset_tokens SET used_at = NOW() WHERE id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        users::set_password_hash(&mut *tx, &username, &password_hash).await?;
+        let cutoff = revocation::revoke_all_in(&mut tx, &username).await?;
+        access_tokens::revoke_all_in(&mut tx, &username).await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(Some((username, cutoff)))
+    }
+    .await;
+
+    let (username, cutoff) = match result {
+        Ok(Some(found)) => found,
+        Ok(None) => {
+            logging::log_security_event(
+                "password_reset_rejected",
+                &format!("invalid or expired reset token from {}", client_ip),
+            );
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": "Invalid or expired reset token" })),
+            );
+        }
+        Err(e) => {
+            logging::log_db_error("update", &e.to_string());
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Failed to reset password" })),
+            );
+        }
+    };
+
+    state.revocations.apply_user_cutoff(&username, cutoff);
+    logging::log_security_event("password_reset_completed", &username);
+
+    (
+        StatusCode::OK,
+        Json(json!({ "message": "Password has been reset. Please log in." })),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_change_password_request_deserialization() {
+        let req: ChangePasswordRequest = serde_json::from_str(
+            r#"{"current_password": "old-secret", "new_password": "new-secret"}"#,
+        )
+        .unwrap();
+        assert_eq!(req.current_password, "old-secret");
+        assert_eq!(req.new_password, "new-secret");
+    }
+
+    #[test]
+    fn test_reset_ttl_default() {
+        unsafe {
+            env::remove_var("PASSWORD_RESET_TTL_MINUTES");
+        }
+        assert_eq!(reset_ttl_minutes(), 60);
+    }
+}
@@ -0,0 +1,87 @@
+use axum::http::Method;
+use serde::{Deserialize, Serialize};
+
+/// Account roles, ordered from least to most privileged
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Read-only access to their own and public notes
+    #[default]
+    Viewer,
+    /// Can create, update and delete their own notes
+    Editor,
+    /// Editor rights plus moderation of every user's public notes and account roles
+    Admin,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Viewer, Role::Editor, Role::Admin];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Role> {
+        Role::ALL.into_iter().find(|role| role.as_str() == value)
+    }
+
+    /// Highest role in a list of role names; unknown names are ignored
+    pub fn highest<S: AsRef<str>>(roles: &[S]) -> Role {
+        roles
+            .iter()
+            .filter_map(|role| Role::parse(role.as_ref()))
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+/// Minimum role for an `/admin` route.
+///
+/// Moderation and user management are admin-only, any other write needs an editor,
+/// and everything else is readable by viewers.
+pub fn required_role(method: &Method, path: &str) -> Role {
+    if path.starts_with("/moderation") || path.starts_with("/users") {
+        Role::Admin
+    } else if method == Method::GET || method == Method::HEAD {
+        Role::Viewer
+    } else {
+        Role::Editor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_ordering_and_parsing() {
+        assert!(Role::Viewer < Role::Editor && Role::Editor < Role::Admin);
+        for role in Role::ALL {
+            assert_eq!(Role::parse(role.as_str()), Some(role));
+        }
+        assert_eq!(Role::parse("root"), None);
+    }
+
+    #[test]
+    fn test_highest_role() {
+        assert_eq!(Role::highest(&["viewer", "admin"]), Role::Admin);
+        assert_eq!(Role::highest(&["unknown"]), Role::Viewer);
+        assert_eq!(Role::highest::<String>(&[]), Role::Viewer);
+    }
+
+    #[test]
+    fn test_required_role() {
+        assert_eq!(required_role(&Method::GET, "/contents"), Role::Viewer);
+        assert_eq!(required_role(&Method::POST, "/contents"), Role::Editor);
+        assert_eq!(required_role(&Method::DELETE, "/contents/1"), Role::Editor);
+        assert_eq!(
+            required_role(&Method::GET, "/moderation/stats"),
+            Role::Admin
+        );
+        assert_eq!(required_role(&Method::PUT, "/users/bob/role"), Role::Admin);
+    }
+}
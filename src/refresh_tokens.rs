@@ -0,0 +1,182 @@
+use crate::{
+    logging,
+    utils::{generate_opaque_token, hash_token},
+};
+use sqlx::{MySqlPool, Row};
+use std::env;
+
+/// Outcome of presenting a refresh token for rotation
+pub enum RotateOutcome {
+    /// The token was valid; carries the username, its session and the replacement refresh token
+    Rotated {
+        username: String,
+        session_id: String,
+        refresh_token: String,
+    },
+    /// The token is unknown, expired or revoked
+    Invalid,
+    /// The token was already rotated once; its whole family has been revoked
+    Reused,
+}
+
+/// Refresh token lifetime in days, from REFRESH_TOKEN_TTL_DAYS (default 30)
+pub(crate) fn ttl_days() -> i64 {
+    env::var("REFRESH_TOKEN_TTL_DAYS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|days| *days > 0)
+        .unwrap_or(30)
+}
+
+async fn insert_token<'e, E>(
+    executor: E,
+    username: &str,
+    family_id: &str,
+) -> Result<String, sqlx::Error>
+where
+    E: sqlx::Executor<'e, Database = sqlx::MySql>,
+{
+    let token = generate_opaque_token();
+
+    sqlx::query(
+        "INSERT INTO refresh_tokens (user, token_hash, family_id, expires_at, created_at)
+         VALUES (?, ?, ?, DATE_ADD(NOW(), INTERVAL ? DAY), NOW())",
+    )
+    .bind(username)
+    .bind(hash_token(&token))
+    .bind(family_id)
+    .bind(ttl_days())
+    .execute(executor)
+    .await?;
+
+    Ok(token)
+}
+
+/// Issue the first refresh token of a login; the session ID names its family
+pub async fn issue(
+    db: &MySqlPool,
+    username: &str,
+    session_id: &str,
+) -> Result<String, sqlx::Error> {
+    let token = insert_token(db, username, session_id).await?;
+    logging::log_db_operation("insert", "refresh_tokens");
+    Ok(token)
+}
+
+/// Exchange a refresh token for a new one in the same family.
+///
+/// Each token may be used exactly once. Presenting an already-used token means it
+/// was copied, so every token in its family is revoked and the caller must log in again.
+pub async fn rotate(db: &MySqlPool, token: &str) -> Result<RotateOutcome, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let row = sqlx::query(
+        "SELECT id, user, family_id, used_at IS NOT NULL AS used,
+                revoked_at IS NOT NULL AS revoked, expires_at <= NOW() AS expired
+         FROM refresh_tokens
+         WHERE token_hash = ?
+         FOR UPDATE",
+    )
+    .bind(hash_token(token))
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(row) = row else {
+        return Ok(RotateOutcome::Invalid);
+    };
+
+    let id: i64 = row.try_get("id")?;
+    let username: String = row.try_get("user")?;
+    let family_id: String = row.try_get("family_id")?;
+    let used: bool = row.try_get("used")?;
+    let revoked: bool = row.try_get("revoked")?;
+    let expired: bool = row.try_get("expired")?;
+
+    if used {
+        revoke_family_in(&mut *tx, &family_id).await?;
+        tx.commit().await?;
+        logging::log_security_event(
+            "refresh_token_reuse",
+            &format!("family revoked for user {}", username),
+        );
+        return Ok(RotateOutcome::Reused);
+    }
+
+    if revoked || expired {
+        return Ok(RotateOutcome::Invalid);
+    }
+
+    sqlx::query("UPDATE refresh_tokens SET used_at = NOW() WHERE id = ?")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    let refresh_token = insert_token(&mut *tx, &username, &family_id).await?;
+    tx.commit().await?;
+
+    logging::log_db_operation("rotate", "refresh_tokens");
+
+    Ok(RotateOutcome::Rotated {
+        username,
+        session_id: family_id,
+        refresh_token,
+    })
+}
+
+/// Revoke the family of a refresh token presented by its owner (used at logout)
+pub async fn revoke(db: &MySqlPool, username: &str, token: &str) -> Result<u64, sqlx::Error> {
+    let family_id: Option<String> = sqlx::query_scalar(
+        "SELECT family_id FROM refresh_tokens WHERE token_hash = ? AND user = ?",
+    )
+    .bind(hash_token(token))
+    .bind(username)
+    .fetch_optional(db)
+    .await?;
+
+    match family_id {
+        Some(family_id) => revoke_family_in(db, &family_id).await,
+        None => Ok(0),
+    }
+}
+
+async fn revoke_family_in<'e, E>(executor: E, family_id: &str) -> Result<u64, sqlx::Error>
+where
+    E: sqlx::Executor<'e, Database = sqlx::MySql>,
+{
+    let result = sqlx::query(
+        "UPDATE refresh_tokens SET revoked_at = NOW()
+         WHERE family_id = ? AND revoked_at IS NULL",
+    )
+    .bind(family_id)
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ttl_days_default_and_override() {
+        unsafe {
+            env::remove_var("REFRESH_TOKEN_TTL_DAYS");
+        }
+        assert_eq!(ttl_days(), 30);
+
+        unsafe {
+            env::set_var("REFRESH_TOKEN_TTL_DAYS", "7");
+        }
+        assert_eq!(ttl_days(), 7);
+
+        unsafe {
+            env::set_var("REFRESH_TOKEN_TTL_DAYS", "-1");
+        }
+        assert_eq!(ttl_days(), 30);
+
+        unsafe {
+            env::remove_var("REFRESH_TOKEN_TTL_DAYS");
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use crate::{
+    content::{self, UpdateOutcome},
+    etag,
+    extractors::AuthUser,
+    logging,
+    models::{NoteRevision, RevisionSummary, UpdateNoteRequest},
+    state::AppState,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::Json as ResponseJson,
+};
+use serde::Deserialize;
+use serde_json::json;
+use similar::TextDiff;
+use sqlx::{FromRow, MySqlConnection, MySqlPool};
+
+/// Lines of unchanged context around each hunk of a diff
+const DIFF_CONTEXT: usize = 3;
+
+/// A note's title and content as read before an edit, with the version they belong to
+#[derive(FromRow)]
+pub struct Snapshot {
+    pub version: i32,
+    pub title: String,
+    pub content: String,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Archive `snapshot` as the note's next revision.
+///
+/// Call inside the transaction that modifies the note, after an UPDATE conditioned
+/// on `snapshot.version` changed it, so the archived copy is exactly what the edit
+/// replaced and commits together with it.
+pub async fn archive(
+    conn: &mut MySqlConnection,
+    note_id: i64,
+    snapshot: &Snapshot,
+) -> Result<i32, sqlx::Error> {
+    let revision: i32 = sqlx::query_scalar(
+        "SELECT CAST(COALESCE(MAX(revision), 0) + 1 AS SIGNED) FROM note_revisions WHERE note_id = ?",
+    )
+    .bind(note_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        "INSERT INTO note_revisions (note_id, revision, title, content, edited_at, created_at)
+         VALUES (?, ?, ?, ?, ?, NOW())",
+    )
+    .bind(note_id)
+    .bind(revision)
+    .bind(&snapshot.title)
+    .bind(&snapshot.content)
+    .bind(snapshot.updated_at)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(revision)
+}
+
+/// Remove the history of a deleted note
+pub async fn delete_for_note(conn: &mut MySqlConnection, note_id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM note_revisions WHERE note_id = ?")
+        .bind(note_id)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Unified diff from `old` to `new`, line by line
+fn unified_diff(old: &str, new: &str, old_label: &str, new_label: &str) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(DIFF_CONTEXT)
+        .header(old_label, new_label)
+        .to_string()
+}
+
+fn db_error(operation: &'static str) -> impl Fn(sqlx::Error) -> StatusCode {
+    move |e| {
+        logging::log_db_error(operation, &e.to_string());
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+async fn owns_note(db: &MySqlPool, username: &str, note_id: i64) -> Result<bool, StatusCode> {
+    let found: Option<i64> =
+        sqlx::query_scalar("SELECT id FROM notes WHERE id = ? AND user = ? AND deleted_at IS NULL")
+            .bind(note_id)
+            .bind(username)
+            .fetch_optional(db)
+            .await
+            .map_err(db_error("select"))?;
+    Ok(found.is_some())
+}
+
+async fn fetch_revision(
+    db: &MySqlPool,
+    note_id: i64,
+    revision: i32,
+) -> Result<Option<NoteRevision>, StatusCode> {
+    sqlx::query_as::<_, NoteRevision>(
+        "SELECT revision, title, content, edited_at, created_at
+         FROM note_revisions
+         WHERE note_id = ? AND revision = ?",
+    )
+    .bind(note_id)
+    .bind(revision)
+    .fetch_optional(db)
+    .await
+    .map_err(db_error("select"))
+}
+
+/// List the archived versions of one of the user's notes, newest first
+pub async fn list_revisions(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<ResponseJson<Vec<RevisionSummary>>, StatusCode> {
+    if !owns_note(&state.db, &auth.username, id).await? {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let revisions = sqlx::query_as::<_, RevisionSummary>(
+        "SELECT revision, title, CAST(CHAR_LENGTH(content) AS SIGNED) AS content_length,
+                edited_at, created_at
+         FROM note_revisions
+         WHERE note_id = ?
+         ORDER BY revision DESC",
+    )
+    .bind(id)
+    .fetch_all(&*state.db)
+    .await
+    .map_err(db_error("select"))?;
+
+    logging::log_db_operation("select", "note_revisions");
+    Ok(ResponseJson(revisions))
+}
+
+/// Get one archived version of a note
+pub async fn get_revision(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((id, revision)): Path<(i64, i32)>,
+) -> Result<ResponseJson<NoteRevision>, StatusCode> {
+    if !owns_note(&state.db, &auth.username, id).await? {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    fetch_revision(&state.db, id, revision)
+        .await?
+        .map(ResponseJson)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+#[derive(Deserialize)]
+pub struct DiffParams {
+    pub from: i32,
+    /// Revision to compare against; the current note when absent
+    pub to: Option<i32>,
+}
+
+/// Show how a note's content changed between two revisions
+pub async fn diff_revisions(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<i64>,
+    Query(params): Query<DiffParams>,
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    let current: Option<(String, String)> = sqlx::query_as(
+        "SELECT title, content FROM notes WHERE id = ? AND user = ? AND deleted_at IS NULL",
+    )
+    .bind(id)
+    .bind(&auth.username)
+    .fetch_optional(&*state.db)
+    .await
+    .map_err(db_error("select"))?;
+    let Some(current) = current else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    let from = fetch_revision(&state.db, id, params.from)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let (to_title, to_content, to_label) = match params.to {
+        Some(to) => {
+            let to_revision = fetch_revision(&state.db, id, to)
+                .await?
+                .ok_or(StatusCode::NOT_FOUND)?;
+            (
+                to_revision.title,
+                to_revision.content,
+                format!("revision {}", to),
+            )
+        }
+        None => (current.0, current.1, "current".to_string()),
+    };
+
+    let diff = unified_diff(
+        &from.content,
+        &to_content,
+        &format!("revision {}", params.from),
+        &to_label,
+    );
+
+    Ok(ResponseJson(json!({
+        "from": params.from,
+        "to": params.to,
+        "from_title": from.title,
+        "to_title": to_title,
+        "diff": diff
+    })))
+}
+
+/// Make an old revision the current version of a note.
+///
+/// The version being replaced is archived first, so a restore can itself be undone.
+/// Honours `If-Match` like any other note update.
+pub async fn restore_revision(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((id, revision)): Path<(i64, i32)>,
+    headers: HeaderMap,
+) -> Result<(StatusCode, HeaderMap, ResponseJson<serde_json::Value>), StatusCode> {
+    let username = auth.username;
+
+    let restored: Option<(String, String)> = sqlx::query_as(
+        "SELECT title, content FROM note_revisions WHERE note_id = ? AND revision = ?",
+    )
+    .bind(id)
+    .bind(revision)
+    .fetch_optional(&*state.db)
+    .await
+    .map_err(db_error("select"))?;
+    let Some((title, content)) = restored else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    let request = UpdateNoteRequest {
+        title: Some(title),
+        content: Some(content),
+        is_public: None,
+        tags: None,
+    };
+    let result = content::apply_update(&state.db, id, &headers, &request, None, |query| {
+        query.push("user = ").push_bind(username.clone());
+    })
+    .await
+    .map_err(|e| {
+        logging::log_db_error("update", &e.to_string());
+        logging::log_note_error("restore", &username, &e.to_string());
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let (version, archived) = match result {
+        UpdateOutcome::Updated { version, archived } => (version, archived),
+        UpdateOutcome::Conflict(version) => return Ok(content::precondition_failed(version)),
+        UpdateOutcome::NotFound => return Err(StatusCode::NOT_FOUND),
+    };
+
+    logging::log_note_operation("restore", &username);
+    let mut headers = HeaderMap::new();
+    headers.insert(header::ETAG, etag::for_version(version));
+    Ok((
+        StatusCode::OK,
+        headers,
+        ResponseJson(json!({
+            "message": "Revision restored successfully",
+            "restored": revision,
+            "archived_as": archived,
+            "version": version
+        })),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff() {
+        let diff = unified_diff(
+            "first\nsecond\nthird\n",
+            "first\nchanged\nthird\n",
+            "revision 1",
+            "current",
+        );
+        assert_eq!(
+            diff,
+            "--- revision 1\n+++ current\n@@ -1,3 +1,3 @@\n first\n-second\n+changed\n third\n"
+        );
+
+        assert_eq!(unified_diff("same\n", "same\n", "a", "b"), "");
+    }
+
+    #[test]
+    fn test_diff_params_from_query() {
+        let uri = "/admin/contents/1/revisions/diff?from=2".parse().unwrap();
+        let Query(params) = Query::<DiffParams>::try_from_uri(&uri).unwrap();
+        assert_eq!(params.from, 2);
+        assert_eq!(params.to, None);
+    }
+}
@@ -0,0 +1,328 @@
+use crate::{logging, utils::Claims};
+use sqlx::{MySqlConnection, MySqlPool, Row};
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+/// In-memory view of revoked access tokens, backed by the revoked_tokens table,
+/// the sessions.revoked_at column and the users.sessions_revoked_at column.
+///
+/// Token validation only consults this cache so it stays synchronous; writes go to
+/// the database first and the cache is reloaded periodically to pick up revocations
+/// made by other instances and to drop entries for tokens that have expired anyway.
+#[derive(Clone, Default)]
+pub struct RevocationList {
+    inner: Arc<RwLock<Revocations>>,
+}
+
+#[derive(Default)]
+struct Revocations {
+    /// Revoked token IDs mapped to the token expiry (unix seconds)
+    tokens: HashMap<String, i64>,
+    /// Per-user cutoff: tokens issued at or before this time (unix milliseconds) are
+    /// revoked. Compared against `iat_ms`, so a token issued right after the revocation
+    /// stays valid; tokens without it only have `iat` and count from the start of its second.
+    user_cutoffs: HashMap<String, i64>,
+    /// Revoked session IDs mapped to the revocation time (unix seconds)
+    sessions: HashMap<String, i64>,
+}
+
+/// How long a revoked session stays in the cache: the access token lifetime,
+/// after which every token carrying its `sid` has expired anyway
+const SESSION_RETENTION_SECS: i64 = 3600;
+
+impl RevocationList {
+    /// Check whether a decoded token has been revoked
+    pub fn is_revoked(&self, claims: &Claims) -> bool {
+        let revocations = self.inner.read().unwrap_or_else(|e| e.into_inner());
+
+        if revocations.tokens.contains_key(&claims.jti) {
+            return true;
+        }
+
+        if let Some(sid) = &claims.sid
+            && revocations.sessions.contains_key(sid)
+        {
+            return true;
+        }
+
+        let issued_at_ms = claims.iat_ms.unwrap_or(claims.iat as i64 * 1000);
+        matches!(
+            revocations.user_cutoffs.get(&claims.sub),
+            Some(cutoff) if issued_at_ms <= *cutoff
+        )
+    }
+
+    fn insert_token(&self, jti: &str, exp: i64) {
+        let mut revocations = self.inner.write().unwrap_or_else(|e| e.into_inner());
+        revocations.tokens.insert(jti.to_string(), exp);
+    }
+
+    fn insert_user_cutoff(&self, username: &str, cutoff: i64) {
+        let mut revocations = self.inner.write().unwrap_or_else(|e| e.into_inner());
+        revocations
+            .user_cutoffs
+            .insert(username.to_string(), cutoff);
+    }
+
+    fn insert_session(&self, session_id: &str, revoked_at: i64) {
+        let mut revocations = self.inner.write().unwrap_or_else(|e| e.into_inner());
+        revocations
+            .sessions
+            .insert(session_id.to_string(), revoked_at);
+    }
+
+    /// Revoke a single access token by its ID
+    pub async fn revoke_token(
+        &self,
+        db: &MySqlPool,
+        jti: &str,
+        username: &str,
+        exp: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT IGNORE INTO revoked_tokens (jti, user, expires_at, revoked_at)
+             VALUES (?, ?, FROM_UNIXTIME(?), NOW())",
+        )
+        .bind(jti)
+        .bind(username)
+        .bind(exp)
+        .execute(db)
+        .await?;
+
+        self.insert_token(jti, exp);
+        logging::log_db_operation("insert", "revoked_tokens");
+        Ok(())
+    }
+
+    /// Revoke every access and refresh token issued to a user so far
+    pub async fn revoke_all_for_user(
+        &self,
+        db: &MySqlPool,
+        username: &str,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = db.begin().await?;
+        let cutoff = revoke_all_in(&mut tx, username).await?;
+        tx.commit().await?;
+
+        self.apply_user_cutoff(username, cutoff);
+        Ok(())
+    }
+
+    /// Make a cutoff written by `revoke_all_in` take effect in this process.
+    /// Call once the transaction that wrote it has committed.
+    pub fn apply_user_cutoff(&self, username: &str, cutoff: i64) {
+        self.insert_user_cutoff(username, cutoff);
+        logging::log_db_operation("revoke_all", "users");
+    }
+
+    /// Revoke one of a user's sessions: its refresh token family and every access
+    /// token carrying its ID. Returns `false` if the user has no such active session.
+    pub async fn revoke_session(
+        &self,
+        db: &MySqlPool,
+        username: &str,
+        session_id: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let revoked_at = chrono::Utc::now().timestamp();
+
+        let result = sqlx::query(
+            "UPDATE sessions SET revoked_at = FROM_UNIXTIME(?)
+             WHERE id = ? AND user = ? AND revoked_at IS NULL",
+        )
+        .bind(revoked_at)
+        .bind(session_id)
+        .bind(username)
+        .execute(db)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        sqlx::query(
+            "UPDATE refresh_tokens SET revoked_at = NOW()
+             WHERE family_id = ? AND revoked_at IS NULL",
+        )
+        .bind(session_id)
+        .execute(db)
+        .await?;
+
+        self.insert_session(session_id, revoked_at);
+        logging::log_db_operation("revoke", "sessions");
+        Ok(true)
+    }
+
+    /// Replace the cache contents with the current database state
+    pub async fn reload(&self, db: &MySqlPool) -> Result<(), sqlx::Error> {
+        let token_rows = sqlx::query(
+            "SELECT jti, UNIX_TIMESTAMP(expires_at) AS exp
+             FROM revoked_tokens
+             WHERE expires_at > NOW()",
+        )
+        .fetch_all(db)
+        .await?;
+
+        let cutoff_rows = sqlx::query(
+            "SELECT username,
+                    CAST(UNIX_TIMESTAMP(sessions_revoked_at) * 1000 AS SIGNED) AS cutoff
+             FROM users
+             WHERE sessions_revoked_at IS NOT NULL",
+        )
+        .fetch_all(db)
+        .await?;
+
+        let session_rows = sqlx::query(
+            "SELECT id, UNIX_TIMESTAMP(revoked_at) AS revoked_at
+             FROM sessions
+             WHERE revoked_at > DATE_SUB(NOW(), INTERVAL ? SECOND)",
+        )
+        .bind(SESSION_RETENTION_SECS)
+        .fetch_all(db)
+        .await?;
+
+        let mut tokens = HashMap::with_capacity(token_rows.len());
+        for row in token_rows {
+            tokens.insert(row.try_get("jti")?, row.try_get::<i64, _>("exp")?);
+        }
+
+        let mut user_cutoffs = HashMap::with_capacity(cutoff_rows.len());
+        for row in cutoff_rows {
+            user_cutoffs.insert(row.try_get("username")?, row.try_get::<i64, _>("cutoff")?);
+        }
+
+        let mut sessions = HashMap::with_capacity(session_rows.len());
+        for row in session_rows {
+            sessions.insert(row.try_get("id")?, row.try_get::<i64, _>("revoked_at")?);
+        }
+
+        let mut revocations = self.inner.write().unwrap_or_else(|e| e.into_inner());
+        *revocations = Revocations {
+            tokens,
+            user_cutoffs,
+            sessions,
+        };
+        Ok(())
+    }
+
+    /// Periodically reload the cache and purge rows for tokens that have expired
+    pub fn spawn_refresh_task(&self, db: Arc<MySqlPool>, interval: Duration) {
+        let revocations = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+
+                if let Err(e) = sqlx::query("DELETE FROM revoked_tokens WHERE expires_at <= NOW()")
+                    .execute(&*db)
+                    .await
+                {
+                    logging::log_db_error("delete", &e.to_string());
+                }
+
+                if let Err(e) = revocations.reload(&db).await {
+                    logging::log_db_error("select", &e.to_string());
+                }
+            }
+        });
+    }
+}
+
+/// Revoke every token and session of a user inside the caller's transaction, so the
+/// revocation commits together with the change that required it (e.g. a new password).
+/// Returns the cutoff (unix milliseconds) to pass to `RevocationList::apply_user_cutoff`
+/// after commit.
+pub async fn revoke_all_in(conn: &mut MySqlConnection, username: &str) -> Result<i64, sqlx::Error> {
+    let cutoff = chrono::Utc::now().timestamp_millis();
+
+    sqlx::query(
+        "UPDATE users SET sessions_revoked_at = FROM_UNIXTIME(? / 1000) WHERE username = ?",
+    )
+    .bind(cutoff)
+    .bind(username)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        "UPDATE refresh_tokens SET revoked_at = NOW()
+         WHERE user = ? AND revoked_at IS NULL",
+    )
+    .bind(username)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        "UPDATE sessions SET revoked_at = FROM_UNIXTIME(? / 1000)
+         WHERE user = ? AND revoked_at IS NULL",
+    )
+    .bind(cutoff)
+    .bind(username)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(cutoff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(sub: &str, jti: &str, iat: usize) -> Claims {
+        Claims {
+            sub: sub.to_string(),
+            exp: iat + 3600,
+            iat,
+            iat_ms: None,
+            jti: jti.to_string(),
+            sid: None,
+            roles: vec![],
+        }
+    }
+
+    #[test]
+    fn test_revoked_token_id() {
+        let list = RevocationList::default();
+        let token = claims("alice", "token-1", 1_000);
+
+        assert!(!list.is_revoked(&token));
+        list.insert_token("token-1", 4_600);
+        assert!(list.is_revoked(&token));
+        assert!(!list.is_revoked(&claims("alice", "token-2", 1_000)));
+    }
+
+    #[test]
+    fn test_user_cutoff_compares_milliseconds() {
+        let list = RevocationList::default();
+        list.insert_user_cutoff("alice", 2_000_500);
+
+        let issued = |jti: &str, iat_ms: i64| Claims {
+            iat_ms: Some(iat_ms),
+            ..claims("alice", jti, (iat_ms / 1000) as usize)
+        };
+        assert!(list.is_revoked(&issued("before", 2_000_499)));
+        assert!(list.is_revoked(&issued("at", 2_000_500)));
+        // Same second as the cutoff, but issued after it
+        assert!(!list.is_revoked(&issued("after", 2_000_501)));
+
+        // Tokens without iat_ms count from the start of their second
+        assert!(list.is_revoked(&claims("alice", "legacy", 2_000)));
+        assert!(!list.is_revoked(&claims("alice", "legacy-new", 2_001)));
+        assert!(!list.is_revoked(&claims("bob", "other", 1_000)));
+    }
+
+    #[test]
+    fn test_revoked_session_revokes_its_tokens() {
+        let list = RevocationList::default();
+        let mut laptop = claims("alice", "token-1", 1_000);
+        laptop.sid = Some("session-1".to_string());
+        let mut phone = claims("alice", "token-2", 1_000);
+        phone.sid = Some("session-2".to_string());
+
+        list.insert_session("session-1", 2_000);
+        assert!(list.is_revoked(&laptop));
+        assert!(!list.is_revoked(&phone));
+        assert!(!list.is_revoked(&claims("alice", "token-3", 1_000)));
+    }
+}
@@ -0,0 +1,500 @@
+use crate::{
+    extractors::AuthUser,
+    logging,
+    pagination::{DEFAULT_LIMIT, MAX_LIMIT, Page},
+    state::AppState,
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::Json as ResponseJson,
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, MySql, MySqlPool, QueryBuilder, mysql::MySqlDatabaseError};
+
+/// Longest search query accepted
+const MAX_QUERY_LEN: usize = 200;
+/// MySQL error raised for full-text queries with invalid boolean syntax
+const ER_PARSE_ERROR: u16 = 1064;
+/// Approximate snippet length in characters
+const SNIPPET_LEN: usize = 200;
+/// Characters of context kept before the first match in a snippet
+const SNIPPET_LEAD: usize = 60;
+
+#[derive(Debug, Deserialize)]
+pub struct SearchParams {
+    /// MySQL boolean-mode query: `+must -exclude "exact phrase" prefix*`
+    pub q: String,
+    pub limit: Option<u32>,
+    /// `next_cursor` from the previous page
+    pub cursor: Option<String>,
+}
+
+impl SearchParams {
+    fn limit(&self) -> u32 {
+        self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+    }
+}
+
+#[derive(FromRow)]
+struct SearchRow {
+    id: i64,
+    title: String,
+    content: String,
+    user: String,
+    is_public: bool,
+    created_at: chrono::DateTime<chrono::Utc>,
+    updated_at: chrono::DateTime<chrono::Utc>,
+    score: f64,
+}
+
+/// A matching note with its relevance and HTML-escaped highlights
+#[derive(Serialize)]
+pub struct SearchResult {
+    pub id: i64,
+    pub title: String,
+    pub user: String,
+    pub is_public: bool,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+    pub score: f64,
+    /// Title with matched terms wrapped in `<mark>`
+    pub title_highlight: String,
+    /// Excerpt of the content around the first match, with matched terms in `<mark>`
+    pub snippet: String,
+}
+
+/// Operators that may prefix a word, phrase or group in boolean mode
+const PREFIX_OPERATORS: &[char] = &['+', '-', '~', '<', '>'];
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Rewrite `query` into boolean-mode syntax InnoDB always accepts.
+///
+/// Characters that are neither word characters nor operators separate words,
+/// operators with nothing to apply to are dropped and parentheses are balanced.
+/// `None` when no word is left to search for.
+fn boolean_query(query: &str) -> Option<String> {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut depth = 0;
+    let mut has_word = false;
+    let mut rest = query;
+
+    while let Some(start) = rest.find(|c: char| is_word_char(c) || "+-~<>()\"*".contains(c)) {
+        rest = &rest[start..];
+        let after_operators = rest.trim_start_matches(PREFIX_OPERATORS);
+        let operator = &rest[..rest.len() - after_operators.len()];
+        let operator = operator.get(..1).unwrap_or("");
+        rest = after_operators;
+
+        if let Some(phrase) = rest.strip_prefix('"') {
+            let (phrase, remainder) = match phrase.find('"') {
+                Some(end) => (&phrase[..end], &phrase[end + 1..]),
+                None => (phrase, ""),
+            };
+            let words: Vec<&str> = phrase
+                .split(|c: char| !is_word_char(c))
+                .filter(|word| !word.is_empty())
+                .collect();
+            if !words.is_empty() {
+                tokens.push(format!("{}\"{}\"", operator, words.join(" ")));
+                has_word = true;
+            }
+            rest = remainder;
+        } else if let Some(remainder) = rest.strip_prefix('(') {
+            tokens.push(format!("{}(", operator));
+            depth += 1;
+            rest = remainder;
+        } else if let Some(remainder) = rest.strip_prefix(')') {
+            if depth > 0 {
+                tokens.push(")".to_string());
+                depth -= 1;
+            }
+            rest = remainder;
+        } else {
+            let end = rest.find(|c: char| !is_word_char(c)).unwrap_or(rest.len());
+            let (word, remainder) = rest.split_at(end);
+            if word.is_empty() {
+                // A stray `*` or an operator on its own
+                rest = rest.trim_start_matches(|c: char| {
+                    !is_word_char(c) && c != '"' && c != '(' && c != ')'
+                });
+                continue;
+            }
+            let prefix = if remainder.starts_with('*') { "*" } else { "" };
+            tokens.push(format!("{}{}{}", operator, word, prefix));
+            has_word = true;
+            // Operators inside a word, as in `e-mail`, only separate it
+            rest = remainder.trim_start_matches(|c: char| "+-~<>*".contains(c));
+        }
+    }
+
+    tokens.extend((0..depth).map(|_| ")".to_string()));
+    has_word.then(|| tokens.join(" "))
+}
+
+/// A word or phrase a query looks for
+#[derive(Debug, PartialEq)]
+struct Term {
+    text: String,
+    /// `word*`: also matches longer words starting with `text`
+    prefix: bool,
+}
+
+/// Words and phrases a query looks for, without boolean operators.
+///
+/// Excluded terms (`-word`) are dropped since they never appear in results.
+fn query_terms(query: &str) -> Vec<Term> {
+    let mut terms: Vec<Term> = Vec::new();
+    let mut rest = query;
+
+    while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+        rest = &rest[start..];
+        let excluded = rest.starts_with('-');
+        let token = rest.trim_start_matches(['+', '-', '~', '<', '>', '(', ')']);
+
+        let (term, remainder) = if let Some(phrase) = token.strip_prefix('"') {
+            match phrase.find('"') {
+                Some(end) => (&phrase[..end], &phrase[end + 1..]),
+                None => (phrase, ""),
+            }
+        } else {
+            let end = token.find(char::is_whitespace).unwrap_or(token.len());
+            (&token[..end], &token[end..])
+        };
+
+        let term = term.trim_end_matches([')', '(']);
+        let prefix = term.ends_with('*');
+        let term = term.trim_end_matches('*').trim();
+        if !excluded && !term.is_empty() && !terms.iter().any(|t| t.text == term) {
+            terms.push(Term {
+                text: term.to_string(),
+                prefix,
+            });
+        }
+        rest = remainder;
+    }
+
+    terms
+}
+
+/// Byte ranges of `text` matching any term (ASCII case-insensitive) on word
+/// boundaries, merged and sorted. A prefix term marks the whole word it starts.
+fn match_ranges(text: &str, terms: &[Term]) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    for term in terms {
+        let len = term.text.len();
+        let starts_word = term.text.starts_with(is_word_char);
+        let ends_word = term.text.ends_with(is_word_char);
+        for (start, _) in text.char_indices() {
+            if starts_word && text[..start].ends_with(is_word_char) {
+                continue;
+            }
+            let Some(candidate) = text.get(start..start + len) else {
+                continue;
+            };
+            if !candidate.eq_ignore_ascii_case(&term.text) {
+                continue;
+            }
+
+            let rest = &text[start + len..];
+            let word_end = rest.find(|c: char| !is_word_char(c)).unwrap_or(rest.len());
+            if word_end == 0 || !ends_word {
+                ranges.push((start, start + len));
+            } else if term.prefix {
+                ranges.push((start, start + len + word_end));
+            }
+        }
+    }
+
+    ranges.sort_unstable();
+    let mut merged: Vec<(usize, usize)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Escape `text` as HTML and wrap every match of `terms` in `<mark>`
+fn highlight(text: &str, terms: &[Term]) -> String {
+    let mut html = String::with_capacity(text.len());
+    let mut position = 0;
+    for (start, end) in match_ranges(text, terms) {
+        html.push_str(&escape_html(&text[position..start]));
+        html.push_str("<mark>");
+        html.push_str(&escape_html(&text[start..end]));
+        html.push_str("</mark>");
+        position = end;
+    }
+    html.push_str(&escape_html(&text[position..]));
+    html
+}
+
+/// Excerpt of `content` around the first match, highlighted
+fn snippet(content: &str, terms: &[Term]) -> String {
+    let first_match = match_ranges(content, terms)
+        .first()
+        .map(|(start, _)| content[..*start].chars().count())
+        .unwrap_or(0);
+
+    let total = content.chars().count();
+    let start = first_match.saturating_sub(SNIPPET_LEAD);
+    let excerpt: String = content.chars().skip(start).take(SNIPPET_LEN).collect();
+
+    let mut html = String::new();
+    if start > 0 {
+        html.push('…');
+    }
+    html.push_str(&highlight(&excerpt, terms));
+    if start + SNIPPET_LEN < total {
+        html.push('…');
+    }
+    html
+}
+
+fn encode_cursor(offset: u32) -> String {
+    URL_SAFE_NO_PAD.encode(offset.to_string())
+}
+
+fn decode_cursor(cursor: &str) -> Option<u32> {
+    let bytes = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+    String::from_utf8(bytes).ok()?.parse().ok()
+}
+
+/// A query the full-text parser still rejects is the client's mistake, not ours
+fn search_error(e: sqlx::Error) -> StatusCode {
+    if let sqlx::Error::Database(db_err) = &e
+        && db_err
+            .try_downcast_ref::<MySqlDatabaseError>()
+            .is_some_and(|db_err| db_err.number() == ER_PARSE_ERROR)
+    {
+        return StatusCode::BAD_REQUEST;
+    }
+    logging::log_db_error("select", &e.to_string());
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+/// Run a ranked full-text search. `scope` appends the condition deciding which
+/// notes the caller may see.
+async fn search_notes(
+    db: &MySqlPool,
+    params: &SearchParams,
+    scope: impl Fn(&mut QueryBuilder<'_, MySql>),
+) -> Result<Page<SearchResult>, StatusCode> {
+    let query = params.q.trim();
+    if query.chars().count() > MAX_QUERY_LEN {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let query = boolean_query(query).ok_or(StatusCode::BAD_REQUEST)?;
+    let offset = match &params.cursor {
+        Some(cursor) => decode_cursor(cursor).ok_or(StatusCode::BAD_REQUEST)?,
+        None => 0,
+    };
+    let limit = params.limit();
+
+    let mut count = QueryBuilder::new("SELECT COUNT(*) FROM notes WHERE deleted_at IS NULL AND ");
+    scope(&mut count);
+    count
+        .push(" AND MATCH(title, content) AGAINST (")
+        .push_bind(query.clone())
+        .push(" IN BOOLEAN MODE)");
+
+    let mut select = QueryBuilder::new(
+        "SELECT id, title, content, user, is_public, created_at, updated_at, \
+         MATCH(title, content) AGAINST (",
+    );
+    select
+        .push_bind(query.clone())
+        .push(" IN BOOLEAN MODE) AS score FROM notes WHERE deleted_at IS NULL AND ");
+    scope(&mut select);
+    select
+        .push(" AND MATCH(title, content) AGAINST (")
+        .push_bind(query.clone())
+        .push(" IN BOOLEAN MODE) ORDER BY score DESC, id DESC LIMIT ")
+        .push_bind(limit)
+        .push(" OFFSET ")
+        .push_bind(offset);
+
+    let total_count: i64 = count
+        .build_query_scalar()
+        .fetch_one(db)
+        .await
+        .map_err(search_error)?;
+
+    let rows = select
+        .build_query_as::<SearchRow>()
+        .fetch_all(db)
+        .await
+        .map_err(search_error)?;
+
+    let next_offset = offset + rows.len() as u32;
+    let next_cursor = (i64::from(next_offset) < total_count && rows.len() as u32 == limit)
+        .then(|| encode_cursor(next_offset));
+
+    let terms = query_terms(&query);
+    let items = rows
+        .into_iter()
+        .map(|row| SearchResult {
+            title_highlight: highlight(&row.title, &terms),
+            snippet: snippet(&row.content, &terms),
+            id: row.id,
+            title: row.title,
+            user: row.user,
+            is_public: row.is_public,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            score: row.score,
+        })
+        .collect();
+
+    Ok(Page {
+        items,
+        next_cursor,
+        total_count,
+    })
+}
+
+/// Search public notes
+pub async fn search_public_contents(
+    State(state): State<AppState>,
+    Query(params): Query<SearchParams>,
+) -> Result<ResponseJson<Page<SearchResult>>, StatusCode> {
+    logging::log_api_request("GET", "/contents/search", 200);
+
+    let page = search_notes(&state.db, &params, |query| {
+        query.push("is_public = true");
+    })
+    .await?;
+
+    logging::log_db_operation("search", "public_notes");
+    Ok(ResponseJson(page))
+}
+
+/// Search the authenticated user's own notes
+pub async fn search_user_contents(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Query(params): Query<SearchParams>,
+) -> Result<ResponseJson<Page<SearchResult>>, StatusCode> {
+    let username = auth.username;
+
+    logging::log_api_request("GET", "/admin/contents/search", 200);
+
+    let page = search_notes(&state.db, &params, |query| {
+        query.push("user = ").push_bind(username.clone());
+    })
+    .await?;
+
+    logging::log_db_operation("search", "user_notes");
+    Ok(ResponseJson(page))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(text: &str) -> Term {
+        Term {
+            text: text.to_string(),
+            prefix: false,
+        }
+    }
+
+    #[test]
+    fn test_boolean_query_keeps_operators_and_drops_the_rest() {
+        assert_eq!(
+            boolean_query(r#"+rust -java "async runtime" tok* ~maybe (>high <low)"#).as_deref(),
+            Some(r#"+rust -java "async runtime" tok* ~maybe ( >high <low )"#)
+        );
+        assert_eq!(
+            boolean_query(r#"user@example.com +-e-mail "a @ b"* ((x"#).as_deref(),
+            Some(r#"user example com +e mail "a b" ( ( x ) )"#)
+        );
+        assert_eq!(boolean_query("@"), None);
+        assert_eq!(boolean_query("*"), None);
+        assert_eq!(boolean_query("+ - ( )"), None);
+    }
+
+    #[test]
+    fn test_query_terms_strip_boolean_operators() {
+        let terms = query_terms(r#"+rust -java "async runtime" tok* ~maybe (>high <low)"#);
+        let texts: Vec<&str> = terms.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            vec!["rust", "async runtime", "tok", "maybe", "high", "low"]
+        );
+        assert!(terms[2].prefix);
+        assert!(!terms[0].prefix);
+
+        assert_eq!(query_terms("  "), Vec::<Term>::new());
+        assert_eq!(
+            query_terms(r#""unterminated phrase"#),
+            vec![term("unterminated phrase")]
+        );
+    }
+
+    #[test]
+    fn test_highlight_escapes_and_marks() {
+        let terms = vec![term("rust"), term("<b>")];
+        assert_eq!(
+            highlight("Rust & <b>trust</b>", &terms),
+            "<mark>Rust</mark> &amp; <mark>&lt;b&gt;</mark>trust&lt;/b&gt;"
+        );
+        assert_eq!(highlight("ไทย rust", &terms), "ไทย <mark>rust</mark>");
+    }
+
+    #[test]
+    fn test_highlight_matches_whole_words_unless_prefix() {
+        let terms = query_terms("meet");
+        assert_eq!(
+            highlight("meet meeting Meet_up", &terms),
+            "<mark>meet</mark> meeting Meet_up"
+        );
+
+        let terms = query_terms("meet* \"error handling\"");
+        assert_eq!(
+            highlight("Meetings, unmeet; error handling errors handling", &terms),
+            "<mark>Meetings</mark>, unmeet; <mark>error handling</mark> errors handling"
+        );
+    }
+
+    #[test]
+    fn test_snippet_windows_around_first_match() {
+        let content = format!("{} needle {}", "a".repeat(300), "b".repeat(300));
+        let snippet = snippet(&content, &[term("needle")]);
+
+        assert!(snippet.starts_with('…'));
+        assert!(snippet.ends_with('…'));
+        assert!(snippet.contains("<mark>needle</mark>"));
+
+        let short = super::snippet("no match here", &[term("needle")]);
+        assert_eq!(short, "no match here");
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        assert_eq!(decode_cursor(&encode_cursor(40)), Some(40));
+        assert_eq!(decode_cursor("!!"), None);
+    }
+}
@@ -0,0 +1,170 @@
+use crate::{
+    client_ip::ClientIp,
+    extractors::SessionUser,
+    logging::{self, middleware::extract_user_agent},
+    models::Session,
+    state::AppState,
+};
+use axum::{
+    extract::{Json, Path, State},
+    http::{HeaderMap, StatusCode},
+};
+use serde_json::json;
+use sqlx::MySqlPool;
+use uuid::Uuid;
+
+/// Longest user agent stored for a session; longer values are truncated
+const MAX_USER_AGENT_LEN: usize = 255;
+
+/// Client IP and user agent of a request, as recorded for a session.
+///
+/// The IP is a parsed address, so it always fits `sessions.ip_address VARCHAR(45)`.
+fn client_details(client_ip: ClientIp, headers: &HeaderMap) -> (String, String) {
+    let user_agent: String = extract_user_agent(headers)
+        .chars()
+        .take(MAX_USER_AGENT_LEN)
+        .collect();
+    (client_ip.to_string(), user_agent)
+}
+
+/// Record a new login and return its session ID.
+///
+/// The ID doubles as the refresh token family and is embedded in every access
+/// token of the session as the `sid` claim, so revoking it ends both.
+pub async fn create(
+    db: &MySqlPool,
+    username: &str,
+    client_ip: ClientIp,
+    headers: &HeaderMap,
+) -> Result<String, sqlx::Error> {
+    let id = Uuid::new_v4().to_string();
+    let (ip_address, user_agent) = client_details(client_ip, headers);
+
+    sqlx::query(
+        "INSERT INTO sessions (id, user, ip_address, user_agent, created_at, last_used_at)
+         VALUES (?, ?, ?, ?, NOW(), NOW())",
+    )
+    .bind(&id)
+    .bind(username)
+    .bind(ip_address)
+    .bind(user_agent)
+    .execute(db)
+    .await?;
+
+    logging::log_db_operation("insert", "sessions");
+    Ok(id)
+}
+
+/// Record activity on a session.
+///
+/// Writes at most once a minute per session so busy clients do not turn every
+/// request into an UPDATE.
+pub async fn touch(
+    db: &MySqlPool,
+    id: &str,
+    client_ip: ClientIp,
+    headers: &HeaderMap,
+) -> Result<(), sqlx::Error> {
+    let (ip_address, user_agent) = client_details(client_ip, headers);
+
+    sqlx::query(
+        "UPDATE sessions SET last_used_at = NOW(), ip_address = ?, user_agent = ?
+         WHERE id = ? AND revoked_at IS NULL
+           AND last_used_at < DATE_SUB(NOW(), INTERVAL 1 MINUTE)",
+    )
+    .bind(ip_address)
+    .bind(user_agent)
+    .bind(id)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// List the current user's active logins, most recently used first
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    SessionUser(auth): SessionUser,
+) -> Result<Json<Vec<Session>>, StatusCode> {
+    // A session stays active while its refresh token family can still be rotated
+    let mut sessions = sqlx::query_as::<_, Session>(
+        "SELECT s.id, s.ip_address, s.user_agent, s.created_at, s.last_used_at
+         FROM sessions s
+         WHERE s.user = ? AND s.revoked_at IS NULL
+           AND EXISTS (
+               SELECT 1 FROM refresh_tokens r
+               WHERE r.family_id = s.id AND r.used_at IS NULL
+                 AND r.revoked_at IS NULL AND r.expires_at > NOW()
+           )
+         ORDER BY s.last_used_at DESC",
+    )
+    .bind(&auth.username)
+    .fetch_all(&*state.db)
+    .await
+    .map_err(|e| {
+        logging::log_db_error("select", &e.to_string());
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    for session in &mut sessions {
+        session.current = auth.token.session_id.as_deref() == Some(session.id.as_str());
+    }
+
+    Ok(Json(sessions))
+}
+
+/// End one of the current user's sessions, e.g. on a lost laptop
+pub async fn revoke_session(
+    State(state): State<AppState>,
+    SessionUser(auth): SessionUser,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let revoked = state
+        .revocations
+        .revoke_session(&state.db, &auth.username, &id)
+        .await
+        .map_err(|e| {
+            logging::log_db_error("update", &e.to_string());
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    if !revoked {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    logging::log_security_event(
+        "session_revoked",
+        &format!("user {} session {}", auth.username, id),
+    );
+    Ok(Json(json!({ "message": "Session revoked" })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_details() {
+        let client_ip = ClientIp("203.0.113.7".parse().unwrap());
+        let mut headers = HeaderMap::new();
+        assert_eq!(
+            client_details(client_ip, &headers),
+            ("203.0.113.7".to_string(), "unknown".to_string())
+        );
+
+        // Forwarding headers are resolved by ClientIp, never stored as sent
+        headers.insert("x-forwarded-for", "x".repeat(200).parse().unwrap());
+        headers.insert("user-agent", "a".repeat(400).parse().unwrap());
+        let (ip_address, user_agent) = client_details(client_ip, &headers);
+        assert_eq!(ip_address, "203.0.113.7");
+        assert_eq!(user_agent.len(), MAX_USER_AGENT_LEN);
+
+        // The longest textual IPv6 address still fits the column
+        let longest = ClientIp(
+            "ffff:ffff:ffff:ffff:ffff:ffff:255.255.255.255"
+                .parse()
+                .unwrap(),
+        );
+        assert!(client_details(longest, &headers).0.len() <= 45);
+    }
+}
@@ -1,7 +1,23 @@
+use crate::{
+    auth_provider::AuthProviders, client_ip::TrustedProxies, markdown::RenderCache,
+    oidc::OidcClient, revocation::RevocationList, throttle::LoginThrottle,
+    two_factor::MfaChallenges,
+};
 use sqlx::MySqlPool;
 use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: Arc<MySqlPool>,
+    pub revocations: RevocationList,
+    pub login_throttle: LoginThrottle,
+    pub mfa_challenges: MfaChallenges,
+    /// Markdown rendered for `?format=html`, reused until a note changes
+    pub render_cache: RenderCache,
+    /// Password login backends (LDAP, then local accounts)
+    pub auth_providers: Arc<AuthProviders>,
+    /// OpenID Connect login, when OIDC_* is configured
+    pub oidc: Option<Arc<OidcClient>>,
+    /// Proxies allowed to report the client address in X-Forwarded-For
+    pub trusted_proxies: Arc<TrustedProxies>,
 }
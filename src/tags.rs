@@ -0,0 +1,416 @@
+use crate::{extractors::AuthUser, logging, models::Note, state::AppState};
+use axum::{
+    extract::{Json, Path, State},
+    http::StatusCode,
+    response::Json as ResponseJson,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{FromRow, MySql, MySqlConnection, MySqlPool, QueryBuilder, Row};
+use std::collections::HashMap;
+
+/// Longest tag name accepted
+pub const MAX_TAG_LEN: usize = 50;
+/// Most tags a single note can carry
+pub const MAX_TAGS_PER_NOTE: usize = 20;
+
+/// Trim and check a tag name. Commas are rejected because listings take a
+/// comma-separated `tags` filter.
+pub fn validate_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_TAG_LEN {
+        return Err(format!(
+            "Tag names must be between 1 and {} characters",
+            MAX_TAG_LEN
+        ));
+    }
+    if name.contains(',') {
+        return Err("Tag names cannot contain commas".to_string());
+    }
+    Ok(name.to_string())
+}
+
+/// Validate the tags sent with a note, dropping case-insensitive duplicates
+pub fn normalize(tags: &[String]) -> Result<Vec<String>, String> {
+    let mut normalized: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let tag = validate_name(tag)?;
+        if !normalized.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+            normalized.push(tag);
+        }
+    }
+    if normalized.len() > MAX_TAGS_PER_NOTE {
+        return Err(format!(
+            "A note can have at most {} tags",
+            MAX_TAGS_PER_NOTE
+        ));
+    }
+    Ok(normalized)
+}
+
+/// Look up a user's tag by name, creating it if needed; returns its ID
+async fn ensure_tag(
+    conn: &mut MySqlConnection,
+    username: &str,
+    name: &str,
+) -> Result<i64, sqlx::Error> {
+    // LAST_INSERT_ID(id) makes an existing row's ID available like a fresh insert's
+    let result = sqlx::query(
+        "INSERT INTO tags (user, name, created_at) VALUES (?, ?, NOW())
+         ON DUPLICATE KEY UPDATE id = LAST_INSERT_ID(id)",
+    )
+    .bind(username)
+    .bind(name)
+    .execute(conn)
+    .await?;
+
+    Ok(result.last_insert_id() as i64)
+}
+
+/// Delete a user's tags that no longer label any note
+async fn prune_unused(conn: &mut MySqlConnection, username: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "DELETE t FROM tags t
+         LEFT JOIN note_tags nt ON nt.tag_id = t.id
+         WHERE t.user = ? AND nt.tag_id IS NULL",
+    )
+    .bind(username)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Replace a note's tags; tags are created in the owner's namespace as needed
+pub async fn set_note_tags(
+    conn: &mut MySqlConnection,
+    username: &str,
+    note_id: i64,
+    tags: &[String],
+) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM note_tags WHERE note_id = ?")
+        .bind(note_id)
+        .execute(&mut *conn)
+        .await?;
+
+    for tag in tags {
+        let tag_id = ensure_tag(conn, username, tag).await?;
+        sqlx::query("INSERT IGNORE INTO note_tags (note_id, tag_id) VALUES (?, ?)")
+            .bind(note_id)
+            .bind(tag_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    prune_unused(conn, username).await
+}
+
+/// Remove a deleted note's tag links
+pub async fn detach_note(
+    conn: &mut MySqlConnection,
+    username: &str,
+    note_id: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM note_tags WHERE note_id = ?")
+        .bind(note_id)
+        .execute(&mut *conn)
+        .await?;
+
+    prune_unused(conn, username).await
+}
+
+/// Tag names for each of `note_ids`, sorted by name
+pub async fn load_for_notes(
+    db: &MySqlPool,
+    note_ids: &[i64],
+) -> Result<HashMap<i64, Vec<String>>, sqlx::Error> {
+    let mut tags: HashMap<i64, Vec<String>> = HashMap::new();
+    if note_ids.is_empty() {
+        return Ok(tags);
+    }
+
+    let mut query = QueryBuilder::<MySql>::new(
+        "SELECT nt.note_id, t.name FROM note_tags nt JOIN tags t ON t.id = nt.tag_id \
+         WHERE nt.note_id IN (",
+    );
+    let mut ids = query.separated(", ");
+    for id in note_ids {
+        ids.push_bind(*id);
+    }
+    query.push(") ORDER BY t.name");
+
+    for row in query.build().fetch_all(db).await? {
+        tags.entry(row.try_get("note_id")?)
+            .or_default()
+            .push(row.try_get("name")?);
+    }
+    Ok(tags)
+}
+
+/// Fill in `tags` on notes read from the notes table
+pub async fn attach(db: &MySqlPool, notes: &mut [Note]) -> Result<(), sqlx::Error> {
+    let ids: Vec<i64> = notes.iter().map(|note| note.id).collect();
+    let mut tags = load_for_notes(db, &ids).await?;
+    for note in notes {
+        note.tags = tags.remove(&note.id).unwrap_or_default();
+    }
+    Ok(())
+}
+
+#[derive(FromRow, Serialize)]
+pub struct TagCount {
+    pub name: String,
+    pub note_count: i64,
+}
+
+/// List the user's tags with the number of notes carrying each
+pub async fn list_tags(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<ResponseJson<Vec<TagCount>>, StatusCode> {
+    logging::log_api_request("GET", "/admin/tags", 200);
+
+    sqlx::query_as::<_, TagCount>(
+        "SELECT t.name, COUNT(nt.note_id) AS note_count
+         FROM tags t
+         JOIN note_tags nt ON nt.tag_id = t.id
+         JOIN notes n ON n.id = nt.note_id AND n.deleted_at IS NULL
+         WHERE t.user = ?
+         GROUP BY t.id, t.name
+         ORDER BY t.name",
+    )
+    .bind(&auth.username)
+    .fetch_all(&*state.db)
+    .await
+    .map(ResponseJson)
+    .map_err(|e| {
+        logging::log_db_error("select", &e.to_string());
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+#[derive(Deserialize)]
+pub struct RenameTagRequest {
+    pub name: String,
+}
+
+/// Rename one of the user's tags on every note carrying it
+pub async fn rename_tag(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(name): Path<String>,
+    Json(request): Json<RenameTagRequest>,
+) -> Result<(StatusCode, ResponseJson<serde_json::Value>), StatusCode> {
+    let username = auth.username;
+    let new_name = match validate_name(&request.name) {
+        Ok(new_name) => new_name,
+        Err(message) => {
+            return Ok((
+                StatusCode::BAD_REQUEST,
+                ResponseJson(json!({ "error": message })),
+            ));
+        }
+    };
+
+    let result = async {
+        let mut tx = state.db.begin().await?;
+        let tag_id: Option<i64> =
+            sqlx::query_scalar("SELECT id FROM tags WHERE user = ? AND name = ? FOR UPDATE")
+                .bind(&username)
+                .bind(&name)
+                .fetch_optional(&mut *tx)
+                .await?;
+        let Some(tag_id) = tag_id else {
+            return Ok(RenameOutcome::NotFound);
+        };
+
+        // Names compare case-insensitively, so a case-only change keeps the same row
+        let existing: Option<i64> =
+            sqlx::query_scalar("SELECT id FROM tags WHERE user = ? AND name = ?")
+                .bind(&username)
+                .bind(&new_name)
+                .fetch_optional(&mut *tx)
+                .await?;
+        if existing.is_some_and(|existing| existing != tag_id) {
+            return Ok(RenameOutcome::Conflict);
+        }
+
+        sqlx::query("UPDATE tags SET name = ? WHERE id = ?")
+            .bind(&new_name)
+            .bind(tag_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(RenameOutcome::Renamed)
+    }
+    .await;
+
+    match result {
+        Ok(RenameOutcome::Renamed) => {
+            logging::log_db_operation("update", "tags");
+            Ok((
+                StatusCode::OK,
+                ResponseJson(json!({ "name": new_name, "previous_name": name })),
+            ))
+        }
+        Ok(RenameOutcome::Conflict) => Ok(rename_conflict()),
+        Ok(RenameOutcome::NotFound) => Err(StatusCode::NOT_FOUND),
+        // A concurrent rename may have claimed the name after the check
+        Err(sqlx::Error::Database(db_err)) if db_err.is_unique_violation() => Ok(rename_conflict()),
+        Err(e) => {
+            logging::log_db_error("update", &e.to_string());
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+enum RenameOutcome {
+    Renamed,
+    /// Another of the user's tags already has the new name
+    Conflict,
+    NotFound,
+}
+
+fn rename_conflict() -> (StatusCode, ResponseJson<serde_json::Value>) {
+    (
+        StatusCode::CONFLICT,
+        ResponseJson(json!({
+            "error": "A tag with that name already exists; merge the tags instead"
+        })),
+    )
+}
+
+#[derive(Deserialize)]
+pub struct MergeTagsRequest {
+    /// Tags to fold into `target`; they are deleted afterwards
+    pub sources: Vec<String>,
+    /// Tag that ends up on every note that had any of the sources (created if needed)
+    pub target: String,
+}
+
+/// Merge several of the user's tags into one
+pub async fn merge_tags(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(request): Json<MergeTagsRequest>,
+) -> Result<(StatusCode, ResponseJson<serde_json::Value>), StatusCode> {
+    let username = auth.username;
+    let target = match validate_name(&request.target) {
+        Ok(target) => target,
+        Err(message) => {
+            return Ok((
+                StatusCode::BAD_REQUEST,
+                ResponseJson(json!({ "error": message })),
+            ));
+        }
+    };
+    if request.sources.is_empty() {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            ResponseJson(json!({ "error": "At least one source tag is required" })),
+        ));
+    }
+
+    let result = async {
+        let mut tx = state.db.begin().await?;
+        let target_id = ensure_tag(&mut tx, &username, &target).await?;
+
+        let mut merged = Vec::new();
+        for source in &request.sources {
+            let source_id: Option<i64> =
+                sqlx::query_scalar("SELECT id FROM tags WHERE user = ? AND name = ?")
+                    .bind(&username)
+                    .bind(source.trim())
+                    .fetch_optional(&mut *tx)
+                    .await?;
+            let Some(source_id) = source_id.filter(|id| *id != target_id) else {
+                continue;
+            };
+
+            sqlx::query(
+                "INSERT IGNORE INTO note_tags (note_id, tag_id)
+                 SELECT note_id, ? FROM note_tags WHERE tag_id = ?",
+            )
+            .bind(target_id)
+            .bind(source_id)
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query("DELETE FROM note_tags WHERE tag_id = ?")
+                .bind(source_id)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("DELETE FROM tags WHERE id = ?")
+                .bind(source_id)
+                .execute(&mut *tx)
+                .await?;
+            merged.push(source.trim().to_string());
+        }
+
+        if merged.is_empty() {
+            // Nothing to merge; do not leave a newly created, unused target behind
+            tx.rollback().await?;
+            return Ok(None);
+        }
+
+        let note_count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM note_tags nt
+             JOIN notes n ON n.id = nt.note_id AND n.deleted_at IS NULL
+             WHERE nt.tag_id = ?",
+        )
+        .bind(target_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(Some((merged, note_count)))
+    }
+    .await;
+
+    match result {
+        Ok(Some((merged, note_count))) => {
+            logging::log_db_operation("merge", "tags");
+            Ok((
+                StatusCode::OK,
+                ResponseJson(json!({
+                    "target": target,
+                    "merged": merged,
+                    "note_count": note_count
+                })),
+            ))
+        }
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            logging::log_db_error("update", &e.to_string());
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_tags() {
+        let tags = vec![
+            " Work ".to_string(),
+            "work".to_string(),
+            "ideas".to_string(),
+        ];
+        assert_eq!(normalize(&tags).unwrap(), vec!["Work", "ideas"]);
+
+        assert!(normalize(&["".to_string()]).is_err());
+        assert!(normalize(&["a,b".to_string()]).is_err());
+        assert!(normalize(&["x".repeat(MAX_TAG_LEN + 1)]).is_err());
+
+        let many: Vec<String> = (0..=MAX_TAGS_PER_NOTE).map(|i| format!("t{}", i)).collect();
+        assert!(normalize(&many).is_err());
+    }
+
+    #[test]
+    fn test_merge_request_deserialization() {
+        let request: MergeTagsRequest =
+            serde_json::from_str(r#"{"sources": ["todo", "to-do"], "target": "tasks"}"#).unwrap();
+        assert_eq!(request.sources, vec!["todo", "to-do"]);
+        assert_eq!(request.target, "tasks");
+    }
+}
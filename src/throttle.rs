@@ -0,0 +1,180 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Failed attempts allowed per username before backoff starts
+const ACCOUNT_FREE_ATTEMPTS: u32 = 5;
+/// Failed attempts allowed per client IP before backoff starts
+const IP_FREE_ATTEMPTS: u32 = 20;
+/// First lockout duration; doubles with every further failure
+const BASE_LOCKOUT: Duration = Duration::from_secs(30);
+/// Upper bound for a single lockout
+const MAX_LOCKOUT: Duration = Duration::from_secs(15 * 60);
+/// Failure counters are forgotten after this much quiet time
+const FAILURE_WINDOW: Duration = Duration::from_secs(60 * 60);
+/// Stale entries are pruned once the table grows past this size
+const PRUNE_THRESHOLD: usize = 10_000;
+
+#[derive(Clone, Copy)]
+struct Attempts {
+    failures: u32,
+    last_failure: Instant,
+    locked_until: Option<Instant>,
+}
+
+/// In-memory login throttle keyed on both the username and the client IP.
+///
+/// Each key gets a number of free failures, after which every further failure locks
+/// it for an exponentially growing period (capped at `MAX_LOCKOUT`).
+#[derive(Clone, Default)]
+pub struct LoginThrottle {
+    entries: Arc<Mutex<HashMap<String, Attempts>>>,
+}
+
+fn account_key(username: &str) -> String {
+    format!("user:{}", username.to_lowercase())
+}
+
+fn ip_key(ip: &str) -> String {
+    format!("ip:{}", ip)
+}
+
+fn lockout_for(failures: u32, free_attempts: u32) -> Option<Duration> {
+    if failures < free_attempts {
+        return None;
+    }
+    let exponent = (failures - free_attempts).min(16);
+    Some((BASE_LOCKOUT * 2u32.pow(exponent)).min(MAX_LOCKOUT))
+}
+
+impl LoginThrottle {
+    /// Remaining lockout for this username/IP pair, if either is locked
+    pub fn check(&self, username: &str, ip: &str) -> Option<Duration> {
+        self.check_at(username, ip, Instant::now())
+    }
+
+    /// Record a failed attempt; returns the lockout it triggered, if any
+    pub fn record_failure(&self, username: &str, ip: &str) -> Option<Duration> {
+        self.record_failure_at(username, ip, Instant::now())
+    }
+
+    /// Clear the account counter after a successful login.
+    ///
+    /// The IP counter is deliberately left alone so a valid login from an attacker's
+    /// own account cannot reset the budget for guessing other accounts.
+    pub fn record_success(&self, username: &str) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.remove(&account_key(username));
+    }
+
+    fn check_at(&self, username: &str, ip: &str, now: Instant) -> Option<Duration> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+
+        [account_key(username), ip_key(ip)]
+            .iter()
+            .filter_map(|key| entries.get(key)?.locked_until)
+            .filter(|until| *until > now)
+            .map(|until| until - now)
+            .max()
+    }
+
+    fn record_failure_at(&self, username: &str, ip: &str, now: Instant) -> Option<Duration> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+
+        if entries.len() > PRUNE_THRESHOLD {
+            entries.retain(|_, a| now.duration_since(a.last_failure) < FAILURE_WINDOW);
+        }
+
+        let mut triggered = None;
+        for (key, free_attempts) in [
+            (account_key(username), ACCOUNT_FREE_ATTEMPTS),
+            (ip_key(ip), IP_FREE_ATTEMPTS),
+        ] {
+            let attempts = entries.entry(key).or_insert(Attempts {
+                failures: 0,
+                last_failure: now,
+                locked_until: None,
+            });
+
+            if now.duration_since(attempts.last_failure) >= FAILURE_WINDOW {
+                attempts.failures = 0;
+                attempts.locked_until = None;
+            }
+
+            attempts.failures += 1;
+            attempts.last_failure = now;
+
+            if let Some(lockout) = lockout_for(attempts.failures, free_attempts) {
+                attempts.locked_until = Some(now + lockout);
+                triggered = triggered.max(Some(lockout));
+            }
+        }
+
+        triggered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockout_grows_exponentially_and_caps() {
+        assert_eq!(lockout_for(4, 5), None);
+        assert_eq!(lockout_for(5, 5), Some(Duration::from_secs(30)));
+        assert_eq!(lockout_for(6, 5), Some(Duration::from_secs(60)));
+        assert_eq!(lockout_for(7, 5), Some(Duration::from_secs(120)));
+        assert_eq!(lockout_for(100, 5), Some(MAX_LOCKOUT));
+    }
+
+    #[test]
+    fn test_account_locked_after_free_attempts() {
+        let throttle = LoginThrottle::default();
+        let now = Instant::now();
+
+        for _ in 0..ACCOUNT_FREE_ATTEMPTS - 1 {
+            assert_eq!(throttle.record_failure_at("alice", "1.2.3.4", now), None);
+        }
+        assert_eq!(throttle.check_at("alice", "1.2.3.4", now), None);
+
+        assert_eq!(
+            throttle.record_failure_at("alice", "1.2.3.4", now),
+            Some(BASE_LOCKOUT)
+        );
+        // Locked regardless of the IP the next attempt comes from
+        assert_eq!(
+            throttle.check_at("Alice", "5.6.7.8", now),
+            Some(BASE_LOCKOUT)
+        );
+        assert_eq!(
+            throttle.check_at("alice", "1.2.3.4", now + BASE_LOCKOUT),
+            None
+        );
+    }
+
+    #[test]
+    fn test_ip_locked_across_usernames() {
+        let throttle = LoginThrottle::default();
+        let now = Instant::now();
+
+        for i in 0..IP_FREE_ATTEMPTS {
+            throttle.record_failure_at(&format!("user{}", i), "1.2.3.4", now);
+        }
+        assert!(throttle.check_at("someone-else", "1.2.3.4", now).is_some());
+        assert!(throttle.check_at("someone-else", "5.6.7.8", now).is_none());
+    }
+
+    #[test]
+    fn test_success_resets_account_only() {
+        let throttle = LoginThrottle::default();
+        let now = Instant::now();
+
+        for _ in 0..ACCOUNT_FREE_ATTEMPTS {
+            throttle.record_failure_at("alice", "1.2.3.4", now);
+        }
+        throttle.record_success("alice");
+        assert_eq!(throttle.check_at("alice", "5.6.7.8", now), None);
+    }
+}
@@ -0,0 +1,125 @@
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha1::Sha1;
+
+/// Time step in seconds (RFC 6238 default)
+const STEP_SECONDS: u64 = 30;
+/// Number of digits in a code
+const DIGITS: u32 = 6;
+/// Accepted clock drift, in steps either side of the current one
+const SKEW_STEPS: u64 = 1;
+
+/// Generate a new random 160-bit secret, base32-encoded for authenticator apps
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 20];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    BASE32_NOPAD.encode(&bytes)
+}
+
+/// Build the otpauth:// URI that authenticator apps read from a QR code
+pub fn otpauth_uri(issuer: &str, username: &str, secret: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{user}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={STEP_SECONDS}",
+        issuer = percent_encode(issuer),
+        user = percent_encode(username),
+        secret = secret,
+    )
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// HOTP value (RFC 4226) for a raw key and counter
+fn hotp(key: &[u8], counter: u64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(&counter.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    // Dynamic truncation
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+
+    binary % 10u32.pow(DIGITS)
+}
+
+/// Verify a code against a base32 secret at the given unix time.
+///
+/// Returns the matching time step so callers can reject replays of the same code.
+pub fn verify(secret: &str, code: &str, unix_time: u64) -> Option<u64> {
+    let key = BASE32_NOPAD
+        .decode(secret.trim_end_matches('=').as_bytes())
+        .ok()?;
+
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let code: u32 = code.parse().ok()?;
+
+    let current = unix_time / STEP_SECONDS;
+    (current.saturating_sub(SKEW_STEPS)..=current + SKEW_STEPS)
+        .find(|step| hotp(&key, *step) == code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 6238 appendix B test secret ("12345678901234567890")
+    const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    #[test]
+    fn test_rfc6238_vectors() {
+        // Six-digit suffixes of the SHA-1 test vectors
+        assert_eq!(verify(RFC_SECRET, "287082", 59), Some(1));
+        assert_eq!(verify(RFC_SECRET, "081804", 1111111109), Some(37037036));
+        assert_eq!(verify(RFC_SECRET, "050471", 1111111111), Some(37037037));
+        assert_eq!(verify(RFC_SECRET, "005924", 1234567890), Some(41152263));
+    }
+
+    #[test]
+    fn test_rejects_wrong_or_malformed_codes() {
+        assert_eq!(verify(RFC_SECRET, "000000", 59), None);
+        assert_eq!(verify(RFC_SECRET, "28708", 59), None);
+        assert_eq!(verify(RFC_SECRET, "abcdef", 59), None);
+        assert_eq!(verify("not base32!", "287082", 59), None);
+    }
+
+    #[test]
+    fn test_accepts_adjacent_step() {
+        // Code for step 1 is still accepted during step 2
+        assert_eq!(verify(RFC_SECRET, "287082", 60 + 29), Some(1));
+        assert_eq!(verify(RFC_SECRET, "287082", 90 + 29), None);
+    }
+
+    #[test]
+    fn test_generated_secret_round_trips() {
+        let secret = generate_secret();
+        assert_eq!(secret.len(), 32);
+        assert!(BASE32_NOPAD.decode(secret.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_otpauth_uri_encodes_labels() {
+        let uri = otpauth_uri("My Notes", "alice", "ABC");
+        assert_eq!(
+            uri,
+            "otpauth://totp/My%20Notes:alice?secret=ABC&issuer=My%20Notes&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+}
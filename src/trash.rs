@@ -0,0 +1,261 @@
+use crate::{extractors::AuthUser, logging, models::TrashedNote, revisions, state::AppState, tags};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json as ResponseJson,
+};
+use serde_json::json;
+use sqlx::{MySqlConnection, MySqlPool};
+use std::{env, sync::Arc, time::Duration};
+
+/// How often the background task looks for expired notes
+const PURGE_INTERVAL: Duration = Duration::from_secs(3600);
+/// Most notes purged per transaction by the background task
+const PURGE_BATCH: i64 = 500;
+
+/// Days a note stays in the trash, from TRASH_RETENTION_DAYS (default 30).
+/// `0` keeps trashed notes until they are deleted by hand.
+pub fn retention_days() -> i64 {
+    env::var("TRASH_RETENTION_DAYS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|days| *days >= 0)
+        .unwrap_or(30)
+}
+
+/// Permanently delete a note with its tag links and revision history
+pub async fn purge_note(
+    conn: &mut MySqlConnection,
+    owner: &str,
+    note_id: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM notes WHERE id = ?")
+        .bind(note_id)
+        .execute(&mut *conn)
+        .await?;
+    tags::detach_note(conn, owner, note_id).await?;
+    revisions::delete_for_note(conn, note_id).await
+}
+
+/// Permanently delete notes that have been in the trash longer than `retention_days`
+pub async fn purge_expired(db: &MySqlPool, retention_days: i64) -> Result<u64, sqlx::Error> {
+    let mut purged = 0;
+    loop {
+        let mut tx = db.begin().await?;
+        let expired: Vec<(i64, String)> = sqlx::query_as(
+            "SELECT id, user FROM notes
+             WHERE deleted_at IS NOT NULL AND deleted_at < DATE_SUB(NOW(), INTERVAL ? DAY)
+             LIMIT ?
+             FOR UPDATE",
+        )
+        .bind(retention_days)
+        .bind(PURGE_BATCH)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        for (id, owner) in &expired {
+            purge_note(&mut tx, owner, *id).await?;
+        }
+        tx.commit().await?;
+
+        purged += expired.len() as u64;
+        if (expired.len() as i64) < PURGE_BATCH {
+            return Ok(purged);
+        }
+    }
+}
+
+/// Periodically purge expired notes from the trash, unless retention is disabled
+pub fn spawn_purge_task(db: Arc<MySqlPool>) {
+    let retention_days = retention_days();
+    if retention_days == 0 {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(PURGE_INTERVAL);
+        loop {
+            ticker.tick().await;
+
+            match purge_expired(&db, retention_days).await {
+                Ok(0) => {}
+                Ok(purged) => {
+                    logging::log_db_operation("purge", &format!("{} trashed notes", purged))
+                }
+                Err(e) => logging::log_db_error("delete", &e.to_string()),
+            }
+        }
+    });
+}
+
+/// List the user's trashed notes, most recently deleted first
+pub async fn list_trash(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<ResponseJson<Vec<TrashedNote>>, StatusCode> {
+    logging::log_api_request("GET", "/admin/trash", 200);
+
+    let mut notes = sqlx::query_as::<_, TrashedNote>(
+        "SELECT id, title, content, is_public, notebook_id, created_at, updated_at, deleted_at
+         FROM notes
+         WHERE user = ? AND deleted_at IS NOT NULL
+         ORDER BY deleted_at DESC, id DESC",
+    )
+    .bind(&auth.username)
+    .fetch_all(&*state.db)
+    .await
+    .map_err(|e| {
+        logging::log_db_error("select", &e.to_string());
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let retention_days = retention_days();
+    if retention_days > 0 {
+        for note in &mut notes {
+            note.purge_after = Some(note.deleted_at + chrono::Duration::days(retention_days));
+        }
+    }
+
+    Ok(ResponseJson(notes))
+}
+
+/// Move a note out of the trash
+pub async fn restore_from_trash(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<(StatusCode, ResponseJson<serde_json::Value>), StatusCode> {
+    let result = sqlx::query(
+        "UPDATE notes SET deleted_at = NULL, updated_at = updated_at
+         WHERE id = ? AND user = ? AND deleted_at IS NOT NULL",
+    )
+    .bind(id)
+    .bind(&auth.username)
+    .execute(&*state.db)
+    .await
+    .map_err(|e| {
+        logging::log_db_error("update", &e.to_string());
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    if result.rows_affected() == 0 {
+        logging::log_api_request("POST", &format!("/admin/trash/{}/restore", id), 404);
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    logging::log_note_operation("restore", &auth.username);
+    Ok((
+        StatusCode::OK,
+        ResponseJson(json!({"message": "Note restored successfully"})),
+    ))
+}
+
+/// Permanently delete one trashed note
+pub async fn delete_from_trash(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<(StatusCode, ResponseJson<serde_json::Value>), StatusCode> {
+    let username = auth.username;
+
+    let result = async {
+        let mut tx = state.db.begin().await?;
+        let trashed = sqlx::query(
+            "SELECT id FROM notes WHERE id = ? AND user = ? AND deleted_at IS NOT NULL FOR UPDATE",
+        )
+        .bind(id)
+        .bind(&username)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if trashed.is_none() {
+            return Ok(false);
+        }
+
+        purge_note(&mut tx, &username, id).await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(true)
+    }
+    .await
+    .map_err(|e| {
+        logging::log_db_error("delete", &e.to_string());
+        logging::log_note_error("purge", &username, &e.to_string());
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    if !result {
+        logging::log_api_request("DELETE", &format!("/admin/trash/{}", id), 404);
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    logging::log_db_operation("delete", "notes");
+    Ok((
+        StatusCode::OK,
+        ResponseJson(json!({"message": "Note permanently deleted"})),
+    ))
+}
+
+/// Permanently delete every note in the user's trash
+pub async fn empty_trash(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<(StatusCode, ResponseJson<serde_json::Value>), StatusCode> {
+    let username = auth.username;
+
+    let result = async {
+        let mut tx = state.db.begin().await?;
+        let trashed: Vec<i64> = sqlx::query_scalar(
+            "SELECT id FROM notes WHERE user = ? AND deleted_at IS NOT NULL FOR UPDATE",
+        )
+        .bind(&username)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        for id in &trashed {
+            purge_note(&mut tx, &username, *id).await?;
+        }
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(trashed.len())
+    }
+    .await
+    .map_err(|e| {
+        logging::log_db_error("delete", &e.to_string());
+        logging::log_note_error("purge", &username, &e.to_string());
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    logging::log_db_operation("delete", "notes");
+    Ok((
+        StatusCode::OK,
+        ResponseJson(json!({
+            "message": "Trash emptied",
+            "deleted": result
+        })),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retention_days_default_and_override() {
+        unsafe {
+            env::remove_var("TRASH_RETENTION_DAYS");
+        }
+        assert_eq!(retention_days(), 30);
+
+        unsafe {
+            env::set_var("TRASH_RETENTION_DAYS", "0");
+        }
+        assert_eq!(retention_days(), 0);
+
+        unsafe {
+            env::set_var("TRASH_RETENTION_DAYS", "-5");
+        }
+        assert_eq!(retention_days(), 30);
+
+        unsafe {
+            env::remove_var("TRASH_RETENTION_DAYS");
+        }
+    }
+}
@@ -0,0 +1,515 @@
+use crate::{
+    extractors::{AuthUser, SessionUser},
+    logging,
+    models::User,
+    state::AppState,
+    totp, users,
+    utils::{generate_opaque_token, hash_token},
+};
+use axum::{
+    extract::{Json, State},
+    http::StatusCode,
+};
+use rand::Rng;
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::{MySqlConnection, MySqlPool};
+use std::{
+    collections::HashMap,
+    env,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// How long a password-verified login may wait for its second factor
+const CHALLENGE_TTL: Duration = Duration::from_secs(5 * 60);
+/// Wrong codes allowed per challenge before it is discarded
+const CHALLENGE_MAX_ATTEMPTS: u32 = 5;
+/// Number of recovery codes issued at a time
+const RECOVERY_CODE_COUNT: usize = 10;
+/// Recovery code alphabet, without easily confused characters (0/o, 1/l/i)
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+struct Challenge {
+    username: String,
+    expires_at: Instant,
+    failed_attempts: u32,
+}
+
+/// Pending logins that passed the password check and still need a second factor.
+///
+/// Challenges are keyed by the hash of an opaque single-use token handed to the client.
+#[derive(Clone, Default)]
+pub struct MfaChallenges {
+    entries: Arc<Mutex<HashMap<String, Challenge>>>,
+}
+
+impl MfaChallenges {
+    /// Start a challenge for a user and return the token the client must send back
+    pub fn create(&self, username: &str) -> String {
+        let token = generate_opaque_token();
+        let now = Instant::now();
+
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|_, c| c.expires_at > now);
+        entries.insert(
+            hash_token(&token),
+            Challenge {
+                username: username.to_string(),
+                expires_at: now + CHALLENGE_TTL,
+                failed_attempts: 0,
+            },
+        );
+
+        token
+    }
+
+    /// Username for a live challenge token
+    pub fn username(&self, token: &str) -> Option<String> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries
+            .get(&hash_token(token))
+            .filter(|c| c.expires_at > Instant::now())
+            .map(|c| c.username.clone())
+    }
+
+    /// Count a wrong code; the challenge is dropped after too many
+    pub fn record_failure(&self, token: &str) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let key = hash_token(token);
+        if let Some(challenge) = entries.get_mut(&key) {
+            challenge.failed_attempts += 1;
+            if challenge.failed_attempts >= CHALLENGE_MAX_ATTEMPTS {
+                entries.remove(&key);
+            }
+        }
+    }
+
+    /// Consume a challenge after a successful second factor
+    pub fn complete(&self, token: &str) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.remove(&hash_token(token));
+    }
+}
+
+fn unix_now() -> u64 {
+    chrono::Utc::now().timestamp().max(0) as u64
+}
+
+fn issuer() -> String {
+    env::var("TOTP_ISSUER").unwrap_or_else(|_| "Notepad".to_string())
+}
+
+/// Generate a recovery code such as `k7m2p-x9qhr`
+fn generate_recovery_code() -> String {
+    let mut rng = rand::thread_rng();
+    let mut code: String = (0..10)
+        .map(|_| RECOVERY_CODE_ALPHABET[rng.gen_range(0..RECOVERY_CODE_ALPHABET.len())] as char)
+        .collect();
+    code.insert(5, '-');
+    code
+}
+
+/// Normalize user input so codes match regardless of case, spaces or dashes
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Replace a user's recovery codes with a fresh set inside the caller's transaction,
+/// returning the plain codes
+async fn replace_recovery_codes(
+    conn: &mut MySqlConnection,
+    username: &str,
+) -> Result<Vec<String>, sqlx::Error> {
+    let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| generate_recovery_code())
+        .collect();
+
+    sqlx::query("DELETE FROM recovery_codes WHERE user = ?")
+        .bind(username)
+        .execute(&mut *conn)
+        .await?;
+
+    for code in &codes {
+        sqlx::query(
+            "INSERT INTO recovery_codes (user, code_hash, created_at) VALUES (?, ?, NOW())",
+        )
+        .bind(username)
+        .bind(hash_token(&normalize_recovery_code(code)))
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    logging::log_db_operation("replace", "recovery_codes");
+
+    Ok(codes)
+}
+
+/// Check a TOTP or recovery code for a user with 2FA enabled.
+///
+/// TOTP codes are accepted at most once per time step, and recovery codes at most once.
+pub async fn verify_second_factor(
+    db: &MySqlPool,
+    user: &User,
+    code: &str,
+) -> Result<bool, sqlx::Error> {
+    if let Some(secret) = &user.totp_secret
+        && let Some(step) = totp::verify(secret, code, unix_now())
+    {
+        // Only advance if this step is newer than the last accepted one
+        let result = sqlx::query(
+            "UPDATE users SET totp_last_step = ?
+             WHERE username = ? AND (totp_last_step IS NULL OR totp_last_step < ?)",
+        )
+        .bind(step as i64)
+        .bind(&user.username)
+        .bind(step as i64)
+        .execute(db)
+        .await?;
+
+        return Ok(result.rows_affected() == 1);
+    }
+
+    let result = sqlx::query(
+        "UPDATE recovery_codes SET used_at = NOW()
+         WHERE user = ? AND code_hash = ? AND used_at IS NULL",
+    )
+    .bind(&user.username)
+    .bind(hash_token(&normalize_recovery_code(code)))
+    .execute(db)
+    .await?;
+
+    if result.rows_affected() == 1 {
+        logging::log_security_event("recovery_code_used", &user.username);
+        return Ok(true);
+    }
+
+    Ok(false)
+}
+
+/// Load the account behind an authenticated session
+async fn current_user(
+    state: &AppState,
+    auth: &AuthUser,
+) -> Result<User, (StatusCode, Json<serde_json::Value>)> {
+    match users::find_by_username(&state.db, &auth.username).await {
+        Ok(Some(user)) => Ok(user),
+        Ok(None) => Err((
+            StatusCode::UNAUTHORIZED,
+            Json(json!({ "error": "Unauthorized" })),
+        )),
+        Err(e) => {
+            logging::log_db_error("select", &e.to_string());
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Internal server error" })),
+            ))
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CodeRequest {
+    code: String,
+}
+
+/// Start enrollment: generate a secret and return it with its otpauth URI
+pub async fn setup(
+    State(state): State<AppState>,
+    SessionUser(auth): SessionUser,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let user = match current_user(&state, &auth).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+
+    if user.totp_enabled {
+        return (
+            StatusCode::CONFLICT,
+            Json(json!({ "error": "Two-factor authentication is already enabled" })),
+        );
+    }
+
+    let secret = totp::generate_secret();
+
+    if let Err(e) = sqlx::query(
+        "UPDATE users SET totp_secret = ?, totp_enabled = false, totp_last_step = NULL
+         WHERE username = ?",
+    )
+    .bind(&secret)
+    .bind(&user.username)
+    .execute(&*state.db)
+    .await
+    {
+        logging::log_db_error("update", &e.to_string());
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": "Failed to start enrollment" })),
+        );
+    }
+
+    (
+        StatusCode::OK,
+        Json(json!({
+            "secret": secret,
+            "otpauth_uri": totp::otpauth_uri(&issuer(), &user.username, &secret)
+        })),
+    )
+}
+
+/// Finish enrollment with a code from the authenticator app; returns recovery codes once
+pub async fn confirm(
+    State(state): State<AppState>,
+    SessionUser(auth): SessionUser,
+    Json(req): Json<CodeRequest>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let user = match current_user(&state, &auth).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+
+    let secret = match (&user.totp_secret, user.totp_enabled) {
+        (Some(secret), false) => secret,
+        (_, true) => {
+            return (
+                StatusCode::CONFLICT,
+                Json(json!({ "error": "Two-factor authentication is already enabled" })),
+            );
+        }
+        (None, false) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": "Enrollment has not been started" })),
+            );
+        }
+    };
+
+    let Some(step) = totp::verify(secret, &req.code, unix_now()) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "Invalid code" })),
+        );
+    };
+
+    // Enabling 2FA and storing its recovery codes commit together, so the account
+    // is never left with 2FA on and no way to recover it
+    let result = async {
+        let mut tx = state.db.begin().await?;
+        let enabled = sqlx::query(
+            "UPDATE users SET totp_enabled = true, totp_last_step = ?
+             WHERE username = ? AND totp_enabled = false",
+        )
+        .bind(step as i64)
+        .bind(&user.username)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        if enabled == 0 {
+            return Ok(None);
+        }
+
+        let codes = replace_recovery_codes(&mut tx, &user.username).await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(Some(codes))
+    }
+    .await;
+
+    let recovery_codes = match result {
+        Ok(Some(codes)) => codes,
+        Ok(None) => {
+            return (
+                StatusCode::CONFLICT,
+                Json(json!({ "error": "Two-factor authentication is already enabled" })),
+            );
+        }
+        Err(e) => {
+            logging::log_db_error("update", &e.to_string());
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Failed to enable two-factor authentication" })),
+            );
+        }
+    };
+
+    logging::log_security_event("2fa_enabled", &user.username);
+    (
+        StatusCode::OK,
+        Json(json!({
+            "message": "Two-factor authentication enabled",
+            "recovery_codes": recovery_codes
+        })),
+    )
+}
+
+/// Turn off 2FA; requires a current TOTP or recovery code
+pub async fn disable(
+    State(state): State<AppState>,
+    SessionUser(auth): SessionUser,
+    Json(req): Json<CodeRequest>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let user = match current_user(&state, &auth).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+
+    if !user.totp_enabled {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "Two-factor authentication is not enabled" })),
+        );
+    }
+
+    match verify_second_factor(&state.db, &user, &req.code).await {
+        Ok(true) => {}
+        Ok(false) => {
+            logging::log_auth_failure(&user.username, "invalid 2fa code on disable");
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": "Invalid code" })),
+            );
+        }
+        Err(e) => {
+            logging::log_db_error("update", &e.to_string());
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Internal server error" })),
+            );
+        }
+    }
+
+    let result = async {
+        let mut tx = state.db.begin().await?;
+        sqlx::query(
+            "UPDATE users SET totp_enabled = false, totp_secret = NULL, totp_last_step = NULL
+             WHERE username = ?",
+        )
+        .bind(&user.username)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM recovery_codes WHERE user = ?")
+            .bind(&user.username)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await
+    }
+    .await;
+
+    if let Err(e) = result {
+        logging::log_db_error("update", &e.to_string());
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": "Failed to disable two-factor authentication" })),
+        );
+    }
+
+    logging::log_security_event("2fa_disabled", &user.username);
+    (
+        StatusCode::OK,
+        Json(json!({ "message": "Two-factor authentication disabled" })),
+    )
+}
+
+/// Replace all recovery codes; requires a current TOTP or recovery code
+pub async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    SessionUser(auth): SessionUser,
+    Json(req): Json<CodeRequest>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let user = match current_user(&state, &auth).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+
+    if !user.totp_enabled {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "Two-factor authentication is not enabled" })),
+        );
+    }
+
+    match verify_second_factor(&state.db, &user, &req.code).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": "Invalid code" })),
+            );
+        }
+        Err(e) => {
+            logging::log_db_error("update", &e.to_string());
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Internal server error" })),
+            );
+        }
+    }
+
+    let result = async {
+        let mut tx = state.db.begin().await?;
+        let codes = replace_recovery_codes(&mut tx, &user.username).await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(codes)
+    }
+    .await;
+
+    match result {
+        Ok(codes) => {
+            logging::log_security_event("recovery_codes_regenerated", &user.username);
+            (StatusCode::OK, Json(json!({ "recovery_codes": codes })))
+        }
+        Err(e) => {
+            logging::log_db_error("insert", &e.to_string());
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Failed to generate recovery codes" })),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recovery_code_format() {
+        let code = generate_recovery_code();
+        assert_eq!(code.len(), 11);
+        assert_eq!(code.as_bytes()[5], b'-');
+        assert_eq!(normalize_recovery_code(&code).len(), 10);
+    }
+
+    #[test]
+    fn test_normalize_recovery_code() {
+        assert_eq!(normalize_recovery_code(" K7M2P-X9QHR "), "k7m2px9qhr");
+    }
+
+    #[test]
+    fn test_challenge_lifecycle() {
+        let challenges = MfaChallenges::default();
+        let token = challenges.create("alice");
+
+        assert_eq!(challenges.username(&token), Some("alice".to_string()));
+        assert_eq!(challenges.username("unknown"), None);
+
+        challenges.complete(&token);
+        assert_eq!(challenges.username(&token), None);
+    }
+
+    #[test]
+    fn test_challenge_dropped_after_max_failures() {
+        let challenges = MfaChallenges::default();
+        let token = challenges.create("alice");
+
+        for _ in 0..CHALLENGE_MAX_ATTEMPTS - 1 {
+            challenges.record_failure(&token);
+        }
+        assert!(challenges.username(&token).is_some());
+
+        challenges.record_failure(&token);
+        assert!(challenges.username(&token).is_none());
+    }
+}
@@ -0,0 +1,195 @@
+use crate::{
+    extractors::AuthUser, logging, models::User, rbac::Role, state::AppState,
+    utils::is_valid_password_hash,
+};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use sqlx::{MySqlExecutor, MySqlPool};
+use std::env;
+
+/// Look up an account by username
+pub async fn find_by_username(db: &MySqlPool, username: &str) -> Result<Option<User>, sqlx::Error> {
+    sqlx::query_as::<_, User>(
+        "SELECT id, username, password_hash, is_active, role, totp_secret, totp_enabled,
+                totp_last_step, created_at, updated_at
+         FROM users
+         WHERE username = ?",
+    )
+    .bind(username)
+    .fetch_optional(db)
+    .await
+}
+
+/// Insert a new account with an already-hashed password, returning its ID
+pub async fn create_user(
+    db: &MySqlPool,
+    username: &str,
+    password_hash: &str,
+    role: Role,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        "INSERT INTO users (username, password_hash, is_active, role, created_at, updated_at)
+         VALUES (?, ?, true, ?, NOW(), NOW())",
+    )
+    .bind(username)
+    .bind(password_hash)
+    .bind(role.as_str())
+    .execute(db)
+    .await?;
+
+    Ok(result.last_insert_id())
+}
+
+/// Replace an account's password hash
+pub async fn set_password_hash(
+    db: impl MySqlExecutor<'_>,
+    username: &str,
+    password_hash: &str,
+) -> Result<bool, sqlx::Error> {
+    let result =
+        sqlx::query("UPDATE users SET password_hash = ?, updated_at = NOW() WHERE username = ?")
+            .bind(password_hash)
+            .bind(username)
+            .execute(db)
+            .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Enable or disable an account; returns false if the account does not exist
+pub async fn set_active(db: &MySqlPool, username: &str, active: bool) -> Result<bool, sqlx::Error> {
+    let result =
+        sqlx::query("UPDATE users SET is_active = ?, updated_at = NOW() WHERE username = ?")
+            .bind(active)
+            .bind(username)
+            .execute(db)
+            .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Change an account's role; returns false if the account does not exist
+pub async fn set_role(db: &MySqlPool, username: &str, role: Role) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("UPDATE users SET role = ?, updated_at = NOW() WHERE username = ?")
+        .bind(role.as_str())
+        .bind(username)
+        .execute(db)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Create the initial admin account from ADMIN_USER/ADMIN_PASS_HASH if it does not exist yet.
+///
+/// This keeps single-user deployments working after the move to the users table;
+/// once the account exists the environment variables are no longer consulted.
+pub async fn bootstrap_admin_from_env(db: &MySqlPool) {
+    let (Ok(admin_user), Ok(mut admin_hash)) =
+        (env::var("ADMIN_USER"), env::var("ADMIN_PASS_HASH"))
+    else {
+        return;
+    };
+
+    // Remove surrounding quotes if present
+    if admin_hash.starts_with('"') && admin_hash.ends_with('"') {
+        admin_hash = admin_hash.trim_matches('"').to_string();
+    }
+
+    if admin_user.is_empty() {
+        logging::log_config_error("ADMIN_USER", "empty value");
+        return;
+    }
+
+    if !is_valid_password_hash(&admin_hash) {
+        logging::log_config_error("ADMIN_PASS_HASH", "invalid Argon2id or bcrypt hash");
+        return;
+    }
+
+    match find_by_username(db, &admin_user).await {
+        Ok(Some(_)) => {}
+        Ok(None) => match create_user(db, &admin_user, &admin_hash, Role::Admin).await {
+            Ok(_) => logging::log_db_operation("bootstrap_admin", "users"),
+            Err(e) => logging::log_db_error("insert", &e.to_string()),
+        },
+        Err(e) => logging::log_db_error("select", &e.to_string()),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UpdateRoleRequest {
+    pub role: Role,
+}
+
+/// Change an account's role (admin only).
+///
+/// Existing sessions are revoked so the new role takes effect on the next login.
+pub async fn update_role(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(username): Path<String>,
+    Json(req): Json<UpdateRoleRequest>,
+) -> (StatusCode, Json<Value>) {
+    if username == auth.username && req.role < Role::Admin {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "Admins cannot demote themselves" })),
+        );
+    }
+
+    match set_role(&state.db, &username, req.role).await {
+        Ok(false) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({ "error": "User not found" })),
+            );
+        }
+        Ok(true) => {}
+        Err(e) => {
+            logging::log_db_error("update", &e.to_string());
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Internal server error" })),
+            );
+        }
+    }
+
+    if let Err(e) = state
+        .revocations
+        .revoke_all_for_user(&state.db, &username)
+        .await
+    {
+        logging::log_db_error("update", &e.to_string());
+    }
+
+    logging::log_security_event(
+        "role_changed",
+        &format!(
+            "admin {} set role of {} to {}",
+            auth.username,
+            username,
+            req.role.as_str()
+        ),
+    );
+
+    (
+        StatusCode::OK,
+        Json(json!({ "username": username, "role": req.role })),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_role_request_deserialization() {
+        let req: UpdateRoleRequest = serde_json::from_str(r#"{"role": "viewer"}"#).unwrap();
+        assert_eq!(req.role, Role::Viewer);
+        assert!(serde_json::from_str::<UpdateRoleRequest>(r#"{"role": "root"}"#).is_err());
+    }
+}
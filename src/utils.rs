@@ -1,17 +1,61 @@
+use crate::{keys, rbac::Role, revocation::RevocationList};
+use argon2::{
+    Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version,
+    password_hash::SaltString,
+};
 use axum::http::HeaderMap;
-use bcrypt::{DEFAULT_COST, hash, verify};
-use jsonwebtoken::{DecodingKey, Validation, decode};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use jsonwebtoken::{DecodingKey, Validation, decode, decode_header};
 use jsonwebtoken::{EncodingKey, Header, encode};
+use rand::RngCore;
 use serde::{Deserialize, Serialize};
-use std::env;
+use sha2::{Digest, Sha256};
+use std::{env, sync::LazyLock};
 
 #[derive(Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
     pub exp: usize,
+    pub iat: usize,
+    /// Issue time in milliseconds, compared against revocation cutoffs; absent on
+    /// tokens issued before it was added
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iat_ms: Option<i64>,
+    pub jti: String,
+    /// Session the token belongs to; absent on tokens issued before sessions were tracked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+    #[serde(default)]
+    pub roles: Vec<String>,
 }
 
-pub fn generate_token(username: &str) -> Result<String, Box<dyn std::error::Error>> {
+pub fn generate_token(
+    username: &str,
+    role: Role,
+    session_id: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let issued_at = chrono::Utc::now();
+    let now = issued_at.timestamp();
+    let claims = Claims {
+        sub: username.to_string(),
+        exp: (now + 3600) as usize, // 1 hour expiration
+        iat: now as usize,
+        iat_ms: Some(issued_at.timestamp_millis()),
+        jti: uuid::Uuid::new_v4().to_string(),
+        sid: Some(session_id.to_string()),
+        roles: vec![role.as_str().to_string()],
+    };
+
+    // Sign with the asymmetric key when one is configured
+    if let Some(keys) = keys::current() {
+        let (kid, alg, key) = keys.signing_key();
+        let mut header = Header::new(alg);
+        header.kid = Some(kid.to_string());
+
+        return encode(&header, &claims, key)
+            .map_err(|e| Box::new(e) as Box<dyn std::error::Error>);
+    }
+
     let secret = env::var("JWT_SECRET").map_err(|_| "JWT_SECRET environment variable not set")?;
 
     // Validate JWT secret in production
@@ -21,11 +65,6 @@ pub fn generate_token(username: &str) -> Result<String, Box<dyn std::error::Erro
         return Err("JWT_SECRET must be at least 32 characters in production".into());
     }
 
-    let claims = Claims {
-        sub: username.to_string(),
-        exp: (chrono::Utc::now().timestamp() + 3600) as usize, // 1 hour expiration
-    };
-
     encode(
         &Header::default(),
         &claims,
@@ -34,62 +73,166 @@ pub fn generate_token(username: &str) -> Result<String, Box<dyn std::error::Erro
     .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
 }
 
-#[allow(dead_code)]
-pub fn hash_password(password: &str) -> String {
-    hash(password, DEFAULT_COST).unwrap()
+/// Argon2id cost parameters for new hashes.
+///
+/// ARGON2_MEMORY_KIB, ARGON2_ITERATIONS and ARGON2_PARALLELISM override the defaults
+/// (19 MiB, 2 passes, 1 lane, as recommended by OWASP). Raising them makes existing
+/// hashes outdated; they are upgraded the next time their owner logs in.
+fn argon2_params() -> Params {
+    let var = |name: &str, default: u32| {
+        env::var(name)
+            .ok()
+            .and_then(|v| v.parse::<u32>().ok())
+            .filter(|value| *value > 0)
+            .unwrap_or(default)
+    };
+
+    Params::new(
+        var("ARGON2_MEMORY_KIB", Params::DEFAULT_M_COST),
+        var("ARGON2_ITERATIONS", Params::DEFAULT_T_COST),
+        var("ARGON2_PARALLELISM", Params::DEFAULT_P_COST),
+        None,
+    )
+    .unwrap_or_default()
 }
 
+fn is_bcrypt_hash(hash: &str) -> bool {
+    hash.starts_with("$2b$") || hash.starts_with("$2a$") || hash.starts_with("$2y$")
+}
+
+/// Where the browser frontend is served, from FRONTEND_URL; also the CORS origin
+pub fn frontend_url() -> String {
+    env::var("FRONTEND_URL").unwrap_or_else(|_| "https://www.terradominus.life".to_string())
+}
+
+/// Hash a password with Argon2id, returning a PHC string (`$argon2id$v=19$...`)
+pub fn hash_password(password: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let mut salt = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut salt);
+    let salt = SaltString::encode_b64(&salt).map_err(|e| e.to_string())?;
+
+    let hash = Argon2::new(Algorithm::Argon2id, Version::V0x13, argon2_params())
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| e.to_string())?;
+
+    Ok(hash.to_string())
+}
+
+/// Check a password against a stored Argon2id or legacy bcrypt hash.
+///
+/// Anything else, including the `!oidc` and `!ldap` markers of accounts without a
+/// local password, never verifies.
 pub fn verify_password(hash: &str, password: &str) -> bool {
-    // Remove all sensitive logging - only log errors in production
-
-    // Check if hash looks like valid bcrypt format
-    if !hash.starts_with("$2b$") && !hash.starts_with("$2a$") && !hash.starts_with("$2y$") {
-        // Log this in development only for debugging
-        let is_development =
-            env::var("RUST_ENV").unwrap_or_else(|_| "development".to_string()) != "production";
-        if is_development {
-            eprintln!("❌ Hash doesn't start with bcrypt identifier ($2b$, $2a$, or $2y$)");
-        }
-        return false;
-    }
-
-    match verify(password, hash) {
-        Ok(result) => result,
-        Err(e) => {
-            // In production, we don't want to expose hash details in logs
-            // Just log that verification failed without sensitive data
-            let is_development =
-                env::var("RUST_ENV").unwrap_or_else(|_| "development".to_string()) != "production";
-            if is_development {
-                eprintln!("❌ Password verification error: {}", e);
-                eprintln!("💡 Hash length: {} characters", hash.len());
-            }
-            false
+    if is_bcrypt_hash(hash) {
+        return bcrypt::verify(password, hash).unwrap_or(false);
+    }
+
+    match PasswordHash::new(hash) {
+        Ok(parsed) if parsed.algorithm == argon2::ARGON2ID_IDENT => Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok(),
+        _ => false,
+    }
+}
+
+/// Hash checked when there is no usable account, so a failed login costs the same
+/// whether or not the username exists
+static DUMMY_HASH: LazyLock<String> = LazyLock::new(|| {
+    hash_password("notepad-dummy-password").expect("hashing a fixed password cannot fail")
+});
+
+/// Spend the time of a real password check without any account to check against
+pub fn verify_dummy_password(password: &str) {
+    verify_password(&DUMMY_HASH, password);
+}
+
+/// Whether a stored hash should be replaced: bcrypt, or Argon2id with other parameters
+/// than `hash_password` currently uses
+pub fn needs_rehash(hash: &str) -> bool {
+    let Ok(parsed) = PasswordHash::new(hash) else {
+        return true;
+    };
+    if parsed.algorithm != argon2::ARGON2ID_IDENT {
+        return true;
+    }
+
+    let current = argon2_params();
+    match Params::try_from(&parsed) {
+        Ok(params) => {
+            params.m_cost() != current.m_cost()
+                || params.t_cost() != current.t_cost()
+                || params.p_cost() != current.p_cost()
         }
+        Err(_) => true,
+    }
+}
+
+/// Check that a stored password hash is a well-formed Argon2id or bcrypt hash
+pub fn is_valid_password_hash(hash: &str) -> bool {
+    if is_bcrypt_hash(hash) {
+        return hash.len() == 60;
     }
+
+    PasswordHash::new(hash)
+        .is_ok_and(|parsed| parsed.algorithm == argon2::ARGON2ID_IDENT && parsed.hash.is_some())
+}
+
+/// Generate a random opaque token (256 bits, URL-safe base64)
+pub fn generate_opaque_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Hash an opaque token for storage (hex-encoded SHA-256)
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
 }
 
-pub fn extract_user_from_token(headers: &HeaderMap) -> Result<String, Box<dyn std::error::Error>> {
+/// Get the bearer token from the Authorization header
+pub fn bearer_token(headers: &HeaderMap) -> Result<&str, Box<dyn std::error::Error>> {
     let auth_header = headers
         .get("authorization")
         .ok_or("Missing authorization header")?
         .to_str()
         .map_err(|_| "Invalid authorization header format")?;
 
-    if !auth_header.starts_with("Bearer ") {
-        return Err("Invalid authorization header format".into());
-    }
+    auth_header
+        .strip_prefix("Bearer ")
+        .ok_or_else(|| "Invalid authorization header format".into())
+}
 
-    let token = &auth_header[7..]; // Remove "Bearer " prefix
-    let secret = env::var("JWT_SECRET").map_err(|_| "JWT_SECRET environment variable not set")?;
+/// Decode and validate an access token, rejecting revoked ones
+pub fn decode_token(
+    token: &str,
+    revocations: &RevocationList,
+) -> Result<Claims, Box<dyn std::error::Error>> {
+    let token_data = if let Some(keys) = keys::current() {
+        // Asymmetric mode: the kid header selects one of the active verification keys
+        let kid = decode_header(token)?
+            .kid
+            .ok_or("Token is missing a key ID")?;
+        let (alg, key) = keys
+            .verification_key(&kid)
+            .ok_or("Token signed with an unknown key")?;
 
-    let token_data = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(secret.as_bytes()),
-        &Validation::default(),
-    )?;
+        decode::<Claims>(token, key, &Validation::new(alg))?
+    } else {
+        let secret =
+            env::var("JWT_SECRET").map_err(|_| "JWT_SECRET environment variable not set")?;
+
+        decode::<Claims>(
+            token,
+            &DecodingKey::from_secret(secret.as_bytes()),
+            &Validation::default(),
+        )?
+    };
 
-    Ok(token_data.claims.sub)
+    if revocations.is_revoked(&token_data.claims) {
+        return Err("Token has been revoked".into());
+    }
+
+    Ok(token_data.claims)
 }
 
 #[cfg(test)]
@@ -99,10 +242,11 @@ mod tests {
     #[test]
     fn test_hash_password() {
         let password = "test_password";
-        let hash = hash_password(password);
+        let hash = hash_password(password).unwrap();
 
-        // Verify the hash starts with bcrypt identifier
-        assert!(hash.starts_with("$2b$"));
+        // Verify the hash is an Argon2id PHC string
+        assert!(hash.starts_with("$argon2id$v=19$"));
+        assert!(is_valid_password_hash(&hash));
 
         // Verify the hash can verify the original password
         assert!(verify_password(&hash, password));
@@ -115,6 +259,81 @@ mod tests {
     fn test_verify_password_invalid_format() {
         let invalid_hash = "invalid_hash_format";
         assert!(!verify_password(invalid_hash, "password"));
+
+        // Accounts that log in through OIDC or LDAP have no usable local password
+        assert!(!verify_password("!oidc", ""));
+        assert!(!verify_password("!ldap", "!ldap"));
+    }
+
+    #[test]
+    fn test_dummy_hash_is_argon2id() {
+        assert!(is_valid_password_hash(&DUMMY_HASH));
+        assert!(!verify_password(&DUMMY_HASH, "guess"));
+    }
+
+    #[test]
+    fn test_legacy_bcrypt_hash_verifies_and_needs_rehash() {
+        let hash = bcrypt::hash("legacy_password", 4).unwrap();
+        assert!(is_valid_password_hash(&hash));
+        assert!(verify_password(&hash, "legacy_password"));
+        assert!(!verify_password(&hash, "wrong_password"));
+        assert!(needs_rehash(&hash));
+    }
+
+    #[test]
+    fn test_needs_rehash_on_changed_cost() {
+        let hash = hash_password("test_password").unwrap();
+        assert!(!needs_rehash(&hash));
+
+        let weaker = Argon2::new(
+            Algorithm::Argon2id,
+            Version::V0x13,
+            Params::new(8 * 1024, 1, 1, None).unwrap(),
+        )
+        .hash_password(
+            b"test_password",
+            &SaltString::encode_b64(&[7u8; 16]).unwrap(),
+        )
+        .unwrap()
+        .to_string();
+        assert!(verify_password(&weaker, "test_password"));
+        assert!(needs_rehash(&weaker));
+
+        assert!(needs_rehash("!oidc"));
+    }
+
+    #[test]
+    fn test_is_valid_password_hash() {
+        let hash = "$2b$12$LQv3c1yqBWVHxkd0LHAkCOYz6TtxMQJqhN8/LewdBPj/RK.s5uO8W";
+        assert!(is_valid_password_hash(hash));
+        assert!(!is_valid_password_hash("$2b$12$short"));
+        assert!(!is_valid_password_hash(
+            "$argon2i$v=19$m=16,t=2,p=1$c2FsdHNhbHQ$aGFzaA"
+        ));
+        assert!(!is_valid_password_hash("plaintext"));
+    }
+
+    #[test]
+    fn test_opaque_token_hashing() {
+        let token = generate_opaque_token();
+        assert_eq!(token.len(), 43);
+        assert_ne!(token, generate_opaque_token());
+
+        let hashed = hash_token(&token);
+        assert_eq!(hashed.len(), 64);
+        assert_eq!(hashed, hash_token(&token));
+    }
+
+    #[test]
+    fn test_bearer_token() {
+        let mut headers = HeaderMap::new();
+        assert!(bearer_token(&headers).is_err());
+
+        headers.insert("authorization", "Basic abc".parse().unwrap());
+        assert!(bearer_token(&headers).is_err());
+
+        headers.insert("authorization", "Bearer abc.def".parse().unwrap());
+        assert_eq!(bearer_token(&headers).unwrap(), "abc.def");
     }
 
     #[test]
@@ -126,7 +345,7 @@ mod tests {
             );
         }
 
-        let result = generate_token("testuser");
+        let result = generate_token("testuser", Role::Editor, "session-1");
         assert!(result.is_ok());
 
         let token = result.unwrap();
@@ -139,7 +358,7 @@ mod tests {
             env::remove_var("JWT_SECRET");
         }
 
-        let result = generate_token("testuser");
+        let result = generate_token("testuser", Role::Editor, "session-1");
         assert!(result.is_err());
     }
 
@@ -152,7 +371,7 @@ mod tests {
             env::set_var("JWT_SECRET", "short");
         }
 
-        let result = generate_token("testuser");
+        let result = generate_token("testuser", Role::Editor, "session-1");
         assert!(result.is_err());
 
         // Clean up